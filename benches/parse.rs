@@ -0,0 +1,44 @@
+use std::{fs, io};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+
+use peppi::game_parser::GameParser;
+use peppi::parse::{Handlers, ParseOptions};
+
+const REPLAYS:&[&str] = &["game", "ics", "v2.0", "netplay_name", "transform"];
+
+struct Discard;
+
+impl Handlers for Discard {}
+
+fn parse(c:&mut Criterion) {
+	for replay in REPLAYS {
+		let bytes = fs::read(format!("test/replays/{}.slp", replay)).unwrap();
+		let mut group = c.benchmark_group(*replay);
+		group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+		group.bench_with_input(BenchmarkId::from_parameter("events"), &bytes, |b, bytes| b.iter(|| {
+			peppi::parse(io::Cursor::new(bytes), &mut Discard).unwrap();
+		}));
+
+		group.bench_with_input(BenchmarkId::from_parameter("slice"), &bytes, |b, bytes| b.iter(|| {
+			peppi::parse::parse_slice(bytes, &mut Discard).unwrap();
+		}));
+
+		group.bench_with_input(BenchmarkId::from_parameter("skip"), &bytes, |b, bytes| b.iter(|| {
+			let mut opts = ParseOptions { skip_frames: true, ..Default::default() };
+			peppi::parse_opts(io::Cursor::new(bytes), &mut Discard, &mut opts).unwrap();
+		}));
+
+		group.bench_with_input(BenchmarkId::from_parameter("game"), &bytes, |b, bytes| b.iter(|| {
+			let mut game_parser = GameParser::default();
+			peppi::parse(io::Cursor::new(bytes), &mut game_parser).unwrap();
+			game_parser.into_game().unwrap()
+		}));
+
+		group.finish();
+	}
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
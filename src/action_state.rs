@@ -2,8 +2,8 @@ use std::fmt;
 
 use super::character::{Internal};
 
-#[derive(Copy, Clone, PartialEq, serde::Serialize)]
-#[serde(untagged)]
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(untagged))]
 pub enum State {
 	Common(Common),
 	Bowser(Bowser),
@@ -74,6 +74,41 @@ impl State {
 			}
 		}
 	}
+
+	/// Name of this state's constant, if there is one.
+	pub fn name(&self) -> Option<&'static str> {
+		match *self {
+			State::Common(s) => s.name(),
+			State::Bowser(s) => s.name(),
+			State::CaptainFalcon(s) => s.name(),
+			State::DonkeyKong(s) => s.name(),
+			State::DrMario(s) => s.name(),
+			State::Falco(s) => s.name(),
+			State::Fox(s) => s.name(),
+			State::GameAndWatch(s) => s.name(),
+			State::Ganondorf(s) => s.name(),
+			State::Jigglypuff(s) => s.name(),
+			State::Kirby(s) => s.name(),
+			State::Link(s) => s.name(),
+			State::Luigi(s) => s.name(),
+			State::Mario(s) => s.name(),
+			State::Marth(s) => s.name(),
+			State::Mewtwo(s) => s.name(),
+			State::Nana(s) => s.name(),
+			State::Ness(s) => s.name(),
+			State::Peach(s) => s.name(),
+			State::Pichu(s) => s.name(),
+			State::Pikachu(s) => s.name(),
+			State::Popo(s) => s.name(),
+			State::Roy(s) => s.name(),
+			State::Samus(s) => s.name(),
+			State::Sheik(s) => s.name(),
+			State::Yoshi(s) => s.name(),
+			State::YoungLink(s) => s.name(),
+			State::Zelda(s) => s.name(),
+			State::Unknown(_) => None,
+		}
+	}
 }
 
 impl fmt::Debug for State {
@@ -112,6 +147,78 @@ impl fmt::Debug for State {
 	}
 }
 
+impl From<State> for u16 {
+	fn from(state:State) -> u16 {
+		match state {
+			State::Common(s) => s.0,
+			State::Bowser(s) => s.0,
+			State::CaptainFalcon(s) => s.0,
+			State::DonkeyKong(s) => s.0,
+			State::DrMario(s) => s.0,
+			State::Falco(s) => s.0,
+			State::Fox(s) => s.0,
+			State::GameAndWatch(s) => s.0,
+			State::Ganondorf(s) => s.0,
+			State::Jigglypuff(s) => s.0,
+			State::Kirby(s) => s.0,
+			State::Link(s) => s.0,
+			State::Luigi(s) => s.0,
+			State::Mario(s) => s.0,
+			State::Marth(s) => s.0,
+			State::Mewtwo(s) => s.0,
+			State::Nana(s) => s.0,
+			State::Ness(s) => s.0,
+			State::Peach(s) => s.0,
+			State::Pichu(s) => s.0,
+			State::Pikachu(s) => s.0,
+			State::Popo(s) => s.0,
+			State::Roy(s) => s.0,
+			State::Samus(s) => s.0,
+			State::Sheik(s) => s.0,
+			State::Yoshi(s) => s.0,
+			State::YoungLink(s) => s.0,
+			State::Zelda(s) => s.0,
+			State::Unknown(s) => s,
+		}
+	}
+}
+
+/// Only the state's value is serialized, which isn't enough to tell character-specific
+/// states apart. So states above 340 come back as `State::Unknown` until resolved
+/// against a character with `State::from` (as `frame::Post` and `game::Frames` do).
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for State {
+	fn deserialize<D:serde::de::Deserializer<'de>>(deserializer:D) -> std::result::Result<Self, D::Error> {
+		super::pseudo_enum::deserialize(deserializer).map(|v| State::from(v, Internal(255)))
+	}
+}
+
+/// Renders the state's name, or `Unknown(<id>)` if it has none.
+impl fmt::Display for State {
+	fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.name() {
+			Some(name) => write!(f, "{}", name),
+			_ => write!(f, "Unknown({})", u16::from(*self)),
+		}
+	}
+}
+
+/// Implements `Display` for per-character state types like `State`'s.
+macro_rules! state_display {
+	($( $name:ident ),* $(,)?) => {
+		$( impl fmt::Display for $name {
+			fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+				match self.name() {
+					Some(name) => write!(f, "{}", name),
+					_ => write!(f, "Unknown({})", self.0),
+				}
+			}
+		} )*
+	}
+}
+
+state_display!(Common, Bowser, CaptainFalcon, DonkeyKong, DrMario, Falco, Fox, GameAndWatch, Ganondorf, Jigglypuff, Kirby, Link, Luigi, Mario, Marth, Mewtwo, Nana, Ness, Peach, Pichu, Pikachu, Popo, Roy, Samus, Sheik, Yoshi, YoungLink, Zelda);
+
 query_impl!(State);
 
 pseudo_enum!(Common:u16 {
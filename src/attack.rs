@@ -1,3 +1,5 @@
+use std::fmt;
+
 pseudo_enum!(Attack:u8 {
 	01 => NON_STALING,
 	02 => JAB_1,
@@ -90,3 +92,111 @@ pseudo_enum!(Attack:u8 {
 	92 => SUPER_SCOPE_CHARGED,
 	93 => HAMMER,
 });
+
+impl Attack {
+	/// Move name as commonly used by players (e.g. "Fair", "Up-Smash").
+	pub fn display_name(&self) -> Option<&'static str> {
+		Some(match *self {
+			Attack::NON_STALING => "Non-Staling",
+			Attack::JAB_1 => "Jab 1",
+			Attack::JAB_2 => "Jab 2",
+			Attack::JAB_3 => "Jab 3",
+			Attack::RAPID_JABS => "Rapid Jabs",
+			Attack::DASH_ATTACK => "Dash Attack",
+			Attack::SIDE_TILT => "F-Tilt",
+			Attack::UP_TILT => "Up-Tilt",
+			Attack::DOWN_TILT => "Down-Tilt",
+			Attack::SIDE_SMASH => "F-Smash",
+			Attack::UP_SMASH => "Up-Smash",
+			Attack::DOWN_SMASH => "Down-Smash",
+			Attack::NAIR => "Nair",
+			Attack::FAIR => "Fair",
+			Attack::BAIR => "Bair",
+			Attack::UAIR => "Uair",
+			Attack::DAIR => "Dair",
+			Attack::NEUTRAL_SPECIAL => "Neutral-B",
+			Attack::SIDE_SPECIAL => "Side-B",
+			Attack::UP_SPECIAL => "Up-B",
+			Attack::DOWN_SPECIAL => "Down-B",
+			Attack::KIRBY_HAT_MARIO_NEUTRAL_SPECIAL => "Neutral-B (Mario Hat)",
+			Attack::KIRBY_HAT_FOX_NEUTRAL_SPECIAL => "Neutral-B (Fox Hat)",
+			Attack::KIRBY_HAT_CFALCON_NEUTRAL_SPECIAL => "Neutral-B (Captain Falcon Hat)",
+			Attack::KIRBY_HAT_DKNEUTRAL_SPECIAL => "Neutral-B (Donkey Kong Hat)",
+			Attack::KIRBY_HAT_BOWSER_NEUTRAL_SPECIAL => "Neutral-B (Bowser Hat)",
+			Attack::KIRBY_HAT_LINK_NEUTRAL_SPECIAL => "Neutral-B (Link Hat)",
+			Attack::KIRBY_HAT_SHEIK_NEUTRAL_SPECIAL => "Neutral-B (Sheik Hat)",
+			Attack::KIRBY_HAT_NESS_NEUTRAL_SPECIAL => "Neutral-B (Ness Hat)",
+			Attack::KIRBY_HAT_PEACH_NEUTRAL_SPECIAL => "Neutral-B (Peach Hat)",
+			Attack::KIRBY_HAT_ICE_CLIMBER_NEUTRAL_SPECIAL => "Neutral-B (Ice Climbers Hat)",
+			Attack::KIRBY_HAT_PIKACHU_NEUTRAL_SPECIAL => "Neutral-B (Pikachu Hat)",
+			Attack::KIRBY_HAT_SAMUS_NEUTRAL_SPECIAL => "Neutral-B (Samus Hat)",
+			Attack::KIRBY_HAT_YOSHI_NEUTRAL_SPECIAL => "Neutral-B (Yoshi Hat)",
+			Attack::KIRBY_HAT_JIGGLYPUFF_NEUTRAL_SPECIAL => "Neutral-B (Jigglypuff Hat)",
+			Attack::KIRBY_HAT_MEWTWO_NEUTRAL_SPECIAL => "Neutral-B (Mewtwo Hat)",
+			Attack::KIRBY_HAT_LUIGI_NEUTRAL_SPECIAL => "Neutral-B (Luigi Hat)",
+			Attack::KIRBY_HAT_MARTH_NEUTRAL_SPECIAL => "Neutral-B (Marth Hat)",
+			Attack::KIRBY_HAT_ZELDA_NEUTRAL_SPECIAL => "Neutral-B (Zelda Hat)",
+			Attack::KIRBY_HAT_YOUNG_LINK_NEUTRAL_SPECIAL => "Neutral-B (Young Link Hat)",
+			Attack::KIRBY_HAT_DOC_NEUTRAL_SPECIAL => "Neutral-B (Dr. Mario Hat)",
+			Attack::KIRBY_HAT_FALCO_NEUTRAL_SPECIAL => "Neutral-B (Falco Hat)",
+			Attack::KIRBY_HAT_PICHU_NEUTRAL_SPECIAL => "Neutral-B (Pichu Hat)",
+			Attack::KIRBY_HAT_GAME_AND_WATCH_NEUTRAL_SPECIAL => "Neutral-B (Mr. Game & Watch Hat)",
+			Attack::KIRBY_HAT_GANON_NEUTRAL_SPECIAL => "Neutral-B (Ganondorf Hat)",
+			Attack::KIRBY_HAT_ROY_NEUTRAL_SPECIAL => "Neutral-B (Roy Hat)",
+			Attack::GET_UP_ATTACK_FROM_BACK => "Get-Up Attack (Back)",
+			Attack::GET_UP_ATTACK_FROM_FRONT => "Get-Up Attack (Front)",
+			Attack::PUMMEL => "Pummel",
+			Attack::FORWARD_THROW => "Forward Throw",
+			Attack::BACK_THROW => "Back Throw",
+			Attack::UP_THROW => "Up Throw",
+			Attack::DOWN_THROW => "Down Throw",
+			Attack::CARGO_FORWARD_THROW => "Cargo Forward Throw",
+			Attack::CARGO_BACK_THROW => "Cargo Back Throw",
+			Attack::CARGO_UP_THROW => "Cargo Up Throw",
+			Attack::CARGO_DOWN_THROW => "Cargo Down Throw",
+			Attack::LEDGE_GET_UP_ATTACK_100 => "Ledge Attack (100%+)",
+			Attack::LEDGE_GET_UP_ATTACK => "Ledge Attack",
+			Attack::BEAM_SWORD_JAB => "Beam Sword Jab",
+			Attack::BEAM_SWORD_TILT_SWING => "Beam Sword Tilt",
+			Attack::BEAM_SWORD_SMASH_SWING => "Beam Sword Smash",
+			Attack::BEAM_SWORD_DASH_SWING => "Beam Sword Dash Attack",
+			Attack::HOME_RUN_BAT_JAB => "Home-Run Bat Jab",
+			Attack::HOME_RUN_BAT_TILT_SWING => "Home-Run Bat Tilt",
+			Attack::HOME_RUN_BAT_SMASH_SWING => "Home-Run Bat Smash",
+			Attack::HOME_RUN_BAT_DASH_SWING => "Home-Run Bat Dash Attack",
+			Attack::PARASOL_JAB => "Parasol Jab",
+			Attack::PARASOL_TILT_SWING => "Parasol Tilt",
+			Attack::PARASOL_SMASH_SWING => "Parasol Smash",
+			Attack::PARASOL_DASH_SWING => "Parasol Dash Attack",
+			Attack::FAN_JAB => "Fan Jab",
+			Attack::FAN_TILT_SWING => "Fan Tilt",
+			Attack::FAN_SMASH_SWING => "Fan Smash",
+			Attack::FAN_DASH_SWING => "Fan Dash Attack",
+			Attack::STAR_ROD_JAB => "Star Rod Jab",
+			Attack::STAR_ROD_TILT_SWING => "Star Rod Tilt",
+			Attack::STAR_ROD_SMASH_SWING => "Star Rod Smash",
+			Attack::STAR_ROD_DASH_SWING => "Star Rod Dash Attack",
+			Attack::LIPS_STICK_JAB => "Lip's Stick Jab",
+			Attack::LIPS_STICK_TILT_SWING => "Lip's Stick Tilt",
+			Attack::LIPS_STICK_SMASH_SWING => "Lip's Stick Smash",
+			Attack::LIPS_STICK_DASH_SWING => "Lip's Stick Dash Attack",
+			Attack::OPEN_PARASOL => "Parasol Open",
+			Attack::RAY_GUN_SHOOT => "Ray Gun",
+			Attack::FIRE_FLOWER_SHOOT => "Fire Flower",
+			Attack::SCREW_ATTACK => "Screw Attack",
+			Attack::SUPER_SCOPE_RAPID => "Super Scope (Rapid)",
+			Attack::SUPER_SCOPE_CHARGED => "Super Scope (Charged)",
+			Attack::HAMMER => "Hammer",
+			_ => return None,
+		})
+	}
+}
+
+impl fmt::Display for Attack {
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+		match self.display_name() {
+			Some(name) => write!(f, "{}", name),
+			_ => write!(f, "Unknown({})", self.0),
+		}
+	}
+}
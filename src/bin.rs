@@ -34,14 +34,42 @@ fn inspect(paths:&[&str], config:&peppi::Config) -> Result<(), String> {
 			// FIXME: handle newlines more smarter, esp. for queries with multiple `[]`.
 			println!("");
 		} else if config.json {
-			println!("{}", serde_json::to_string(&game).map_err(|e| format!("{:?}", e))?);
+			println!("{}", serde_json::to_string(&game.export(&config.export)).map_err(|e| format!("{:?}", e))?);
 		} else {
-			println!("{:#?}", game);
+			println!("{:#?}", game.export(&config.export));
 		}
 	}
 	Ok(())
 }
 
+/// Prints how the replay at `other` differs from the one at `path`, one line per
+/// difference. Pre-frame random seeds are ignored unless `include_seed`.
+fn diff(path:&str, other:&str, include_seed:bool) -> Result<(), String> {
+	let a = peppi::game(path::Path::new(path)).map_err(|e| format!("{:?}", e))?;
+	let b = peppi::game(path::Path::new(other)).map_err(|e| format!("{:?}", e))?;
+	let mut same = true;
+	if a.start != b.start {
+		println!("start differs");
+		same = false;
+	}
+	if a.end != b.end {
+		println!("end differs");
+		same = false;
+	}
+	if a.metadata != b.metadata {
+		println!("metadata differs");
+		same = false;
+	}
+	for d in a.diff_frames(&b, include_seed) {
+		println!("port {} ({}): frame {} differs", d.port, if d.is_follower { "follower" } else { "leader" }, d.index);
+		same = false;
+	}
+	if same {
+		println!("no differences");
+	}
+	Ok(())
+}
+
 fn main() {
 	pretty_env_logger::init();
 
@@ -57,6 +85,9 @@ fn main() {
 			.help("Output frame data")
 			.short("f")
 			.long("frames"))
+		.arg(Arg::with_name("no-followers")
+			.help("Omit follower (Nana) frame data")
+			.long("no-followers"))
 		.arg(Arg::with_name("QUERY")
 			.help("Print a subset of parsed data (jq-like)")
 			.short("q")
@@ -66,6 +97,13 @@ fn main() {
 			.help("Append names for known constants")
 			.short("n")
 			.long("names"))
+		.arg(Arg::with_name("OTHER")
+			.help("Compare with another replay file instead of printing")
+			.long("diff")
+			.takes_value(true))
+		.arg(Arg::with_name("include-seed")
+			.help("With --diff, also compare Pre-frame random seeds")
+			.long("include-seed"))
 		.arg(Arg::with_name("FILE")
 			.help("Replay file to parse")
 			.required(true)
@@ -77,13 +115,18 @@ fn main() {
 	let config = peppi::Config {
 		json: matches.is_present("json"),
 		frames: matches.is_present("frames"),
+		export: peppi::ExportOptions { include_followers: !matches.is_present("no-followers") },
 		enum_names: matches.is_present("names"),
 		query: matches.value_of("QUERY").map(|q| parse_query(q)),
 	};
 
 	unsafe { peppi::CONFIG = config.clone() };
 
-	if let Err(e) = inspect(&[&path], &config) {
+	let result = match matches.value_of("OTHER") {
+		Some(other) => diff(&path, other, matches.is_present("include-seed")),
+		_ => inspect(&[&path], &config),
+	};
+	if let Err(e) = result {
 		error!("{}", e);
 	}
 }
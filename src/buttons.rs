@@ -38,3 +38,20 @@ pseudo_bitmask!(Logical:u32 {
 	1u32 << 00 => DPAD_LEFT,
 	0u32 => NONE,
 });
+
+impl Logical {
+	/// The buttons (and analog inputs) pressed, as individual flags.
+	pub fn pressed(&self) -> Vec<Logical> {
+		self.flags()
+	}
+}
+
+/// Buttons pressed in `cur` but not in `prev`, e.g. on consecutive frames.
+pub fn just_pressed(prev:Logical, cur:Logical) -> Logical {
+	Logical(cur.0 & !prev.0)
+}
+
+/// Buttons pressed in `prev` but not in `cur`, e.g. on consecutive frames.
+pub fn just_released(prev:Logical, cur:Logical) -> Logical {
+	Logical(prev.0 & !cur.0)
+}
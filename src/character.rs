@@ -1,3 +1,6 @@
+use std::convert::TryFrom;
+use std::fmt;
+
 pseudo_enum!(Internal:u8 {
 	00 => MARIO,
 	01 => FOX,
@@ -69,3 +72,126 @@ pseudo_enum!(External:u8 {
 	31 => SANDBAG,
 	32 => POPO,
 });
+
+impl Internal {
+	/// Human-readable name, as shown in-game where applicable.
+	pub fn display_name(&self) -> Option<&'static str> {
+		Some(match *self {
+			Internal::MARIO => "Mario",
+			Internal::FOX => "Fox",
+			Internal::CAPTAIN_FALCON => "Captain Falcon",
+			Internal::DONKEY_KONG => "Donkey Kong",
+			Internal::KIRBY => "Kirby",
+			Internal::BOWSER => "Bowser",
+			Internal::LINK => "Link",
+			Internal::SHEIK => "Sheik",
+			Internal::NESS => "Ness",
+			Internal::PEACH => "Peach",
+			Internal::POPO => "Popo",
+			Internal::NANA => "Nana",
+			Internal::PIKACHU => "Pikachu",
+			Internal::SAMUS => "Samus",
+			Internal::YOSHI => "Yoshi",
+			Internal::JIGGLYPUFF => "Jigglypuff",
+			Internal::MEWTWO => "Mewtwo",
+			Internal::LUIGI => "Luigi",
+			Internal::MARTH => "Marth",
+			Internal::ZELDA => "Zelda",
+			Internal::YOUNG_LINK => "Young Link",
+			Internal::DR_MARIO => "Dr. Mario",
+			Internal::FALCO => "Falco",
+			Internal::PICHU => "Pichu",
+			Internal::GAME_AND_WATCH => "Mr. Game & Watch",
+			Internal::GANONDORF => "Ganondorf",
+			Internal::ROY => "Roy",
+			Internal::MASTER_HAND => "Master Hand",
+			Internal::CRAZY_HAND => "Crazy Hand",
+			Internal::WIRE_FRAME_MALE => "Male Wireframe",
+			Internal::WIRE_FRAME_FEMALE => "Female Wireframe",
+			Internal::GIGA_BOWSER => "Giga Bowser",
+			Internal::SANDBAG => "Sandbag",
+			_ => return None,
+		})
+	}
+
+	/// The character-select ID for this character. Both Popo and Nana map to
+	/// `External::ICE_CLIMBERS`.
+	pub fn to_external(&self) -> Option<External> {
+		match *self {
+			Internal::POPO | Internal::NANA => Some(External::ICE_CLIMBERS),
+			c => External::try_from(c.name()?).ok(),
+		}
+	}
+}
+
+impl External {
+	/// Human-readable name, as shown in-game where applicable.
+	pub fn display_name(&self) -> Option<&'static str> {
+		match *self {
+			External::ICE_CLIMBERS => Some("Ice Climbers"),
+			c => c.to_internal()?.display_name(),
+		}
+	}
+
+	/// The in-game ID for this character. `External::ICE_CLIMBERS` maps to
+	/// `Internal::POPO`, the leader.
+	pub fn to_internal(&self) -> Option<Internal> {
+		match *self {
+			External::ICE_CLIMBERS => Some(Internal::POPO),
+			c => Internal::try_from(c.name()?).ok(),
+		}
+	}
+}
+
+/// Name of `character`'s costume (color) number `costume`, in character-select order,
+/// or `None` if the character doesn't have that many. The first is always "Default".
+pub fn costume_name(character:External, costume:u8) -> Option<&'static str> {
+	let costumes:&[&str] = match character {
+		External::CAPTAIN_FALCON => &["Default", "Black", "Red", "White", "Green", "Blue"],
+		External::DONKEY_KONG => &["Default", "Black", "Red", "Blue", "Green"],
+		External::FOX => &["Default", "Red", "Blue", "Green"],
+		External::GAME_AND_WATCH => &["Default", "Red", "Blue", "Green"],
+		External::KIRBY => &["Default", "Yellow", "Blue", "Red", "Green", "White"],
+		External::BOWSER => &["Default", "Red", "Blue", "Black"],
+		External::LINK => &["Default", "Red", "Blue", "Black", "White"],
+		External::LUIGI => &["Default", "White", "Blue", "Red"],
+		External::MARIO => &["Default", "Yellow", "Black", "Blue", "Green"],
+		External::MARTH => &["Default", "Red", "Green", "Black", "White"],
+		External::MEWTWO => &["Default", "Red", "Blue", "Green"],
+		External::NESS => &["Default", "Yellow", "Blue", "Green"],
+		External::PEACH => &["Default", "Daisy", "White", "Blue", "Green"],
+		External::PIKACHU => &["Default", "Red", "Party Hat", "Cowboy Hat"],
+		External::ICE_CLIMBERS => &["Default", "Green", "Orange", "Red"],
+		External::JIGGLYPUFF => &["Default", "Red", "Blue", "Headband", "Crown"],
+		External::SAMUS => &["Default", "Pink", "Black", "Green", "Purple"],
+		External::YOSHI => &["Default", "Red", "Blue", "Yellow", "Pink", "Cyan"],
+		External::ZELDA => &["Default", "Red", "Blue", "Green", "White"],
+		External::SHEIK => &["Default", "Red", "Blue", "Green", "White"],
+		External::FALCO => &["Default", "Red", "Blue", "Green"],
+		External::YOUNG_LINK => &["Default", "Red", "Blue", "White", "Black"],
+		External::DR_MARIO => &["Default", "Red", "Blue", "Green", "Black"],
+		External::ROY => &["Default", "Red", "Blue", "Green", "Yellow"],
+		External::PICHU => &["Default", "Red", "Blue", "Green"],
+		External::GANONDORF => &["Default", "Red", "Blue", "Green", "Purple"],
+		_ => &[],
+	};
+	costumes.get(costume as usize).copied()
+}
+
+impl fmt::Display for Internal {
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+		match self.display_name() {
+			Some(name) => write!(f, "{}", name),
+			_ => write!(f, "Unknown({})", self.0),
+		}
+	}
+}
+
+impl fmt::Display for External {
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+		match self.display_name() {
+			Some(name) => write!(f, "{}", name),
+			_ => write!(f, "Unknown({})", self.0),
+		}
+	}
+}
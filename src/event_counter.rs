@@ -0,0 +1,93 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Result;
+
+use super::{frame, game, parse};
+
+/// Number of occurrences and total size (including the code byte) of one event type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EventCount {
+	pub count: usize,
+	pub bytes: usize,
+}
+
+/// A `Handlers` implementation that tallies events by raw code, for profiling and
+/// format research. Unknown events are counted too.
+#[derive(Debug, Default)]
+pub struct EventCounter {
+	sizes: HashMap<u8, u16>,
+	pub counts: BTreeMap<u8, EventCount>,
+}
+
+impl EventCounter {
+	fn record(&mut self, code: u8) {
+		let size = self.sizes.get(&code).map_or(0, |s| *s as usize);
+		let c = self.counts.entry(code).or_default();
+		c.count += 1;
+		c.bytes += 1 + size; // +1 byte for the event code
+	}
+
+	/// Total bytes across all counted events.
+	pub fn total_bytes(&self) -> usize {
+		self.counts.values().map(|c| c.bytes).sum()
+	}
+
+	/// Fraction of counted bytes taken up by events with `code`.
+	pub fn fraction(&self, code: u8) -> f64 {
+		match (self.counts.get(&code), self.total_bytes()) {
+			(Some(c), total) if total > 0 => c.bytes as f64 / total as f64,
+			_ => 0.0,
+		}
+	}
+}
+
+impl parse::Handlers for EventCounter {
+	fn payload_sizes(&mut self, sizes: &HashMap<u8, u16>) -> Result<()> {
+		self.sizes = sizes.clone();
+		Ok(())
+	}
+
+	fn unknown_event(&mut self, code: u8, _: &[u8]) -> Result<()> {
+		self.record(code);
+		Ok(())
+	}
+
+	fn game_start(&mut self, _: game::Start) -> Result<()> {
+		self.record(parse::Event::GameStart as u8);
+		Ok(())
+	}
+
+	fn game_end(&mut self, _: game::End) -> Result<()> {
+		self.record(parse::Event::GameEnd as u8);
+		Ok(())
+	}
+
+	fn frame_start(&mut self, _: parse::FrameEvent<frame::Start, i32>) -> Result<()> {
+		self.record(parse::Event::FrameStart as u8);
+		Ok(())
+	}
+
+	fn frame_pre(&mut self, _: parse::FrameEvent<frame::Pre>) -> Result<()> {
+		self.record(parse::Event::FramePre as u8);
+		Ok(())
+	}
+
+	fn frame_post(&mut self, _: parse::FrameEvent<frame::Post>) -> Result<()> {
+		self.record(parse::Event::FramePost as u8);
+		Ok(())
+	}
+
+	fn item(&mut self, _: parse::FrameEvent<frame::Item, parse::ItemId>) -> Result<()> {
+		self.record(parse::Event::Item as u8);
+		Ok(())
+	}
+
+	fn frame_bookend(&mut self, _: parse::FrameEvent<frame::Bookend, i32>) -> Result<()> {
+		self.record(parse::Event::FrameBookend as u8);
+		Ok(())
+	}
+
+	fn gecko_list(&mut self, _: Vec<u8>) -> Result<()> {
+		self.record(parse::Event::GeckoList as u8);
+		Ok(())
+	}
+}
@@ -1,26 +1,47 @@
 use std::fmt;
 use std::convert::TryInto;
 
-use serde::{Serialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use super::{action_state, attack, buttons, character, game, triggers};
+use super::{action_state, attack, buttons, character, game, item, stage, triggers};
 
 pseudo_enum!(LCancel:u8 {
 	1 => SUCCESSFUL,
 	2 => UNSUCCESSFUL,
 });
 
+// The raw value is a float: negative for left, positive for right. A few frames
+// report exactly 0 (some spawn and respawn frames, and some follower states), which
+// is `NEUTRAL`.
 pseudo_enum!(Direction:u8 {
 	0 => LEFT,
 	1 => RIGHT,
+	2 => NEUTRAL,
 });
 
-#[derive(Copy, Clone, PartialEq, Serialize)]
+/// A point in stage coordinates: `x` increases to the right and `y` upward, with the
+/// origin at the center of the stage (on the legal stages, the top of the main platform).
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Position {
 	pub x: f32,
 	pub y: f32,
 }
 
+impl Position {
+	/// Euclidean distance between two positions.
+	pub fn distance(&self, other:&Position) -> f32 {
+		(self.x - other.x).hypot(self.y - other.y)
+	}
+
+	/// Whether this position is off `stage`. See `stage::Stage::is_offstage`, which
+	/// lists the supported stages.
+	pub fn is_offstage(&self, stage:stage::Stage) -> Option<bool> {
+		stage.is_offstage(self)
+	}
+}
+
 impl fmt::Debug for Position {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		write!(f, "({}, {})", self.x, self.y)
@@ -29,7 +50,8 @@ impl fmt::Debug for Position {
 
 query_impl!(Position);
 
-#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Buttons {
 	pub logical: buttons::Logical,
 	pub physical: buttons::Physical,
@@ -43,7 +65,8 @@ query_impl!(Buttons, self, f, config, query {
 	}
 });
 
-#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Triggers {
 	pub logical: triggers::Logical,
 	pub physical: triggers::Physical,
@@ -73,30 +96,46 @@ pseudo_enum!(HurtboxState:u8 {
 });
 
 pub trait Indexed {
-	/// 0-based frame index (in-game frame indexes start at -123)
-	fn array_index(&self) -> usize;
+	/// In-game frame index (starts at -123)
+	fn index(&self) -> i32;
+
+	/// 0-based frame index, counting from `game::FIRST_FRAME_INDEX`
+	///
+	/// Panics if the frame precedes `game::FIRST_FRAME_INDEX`, which corrupt replays
+	/// can contain. Use `try_array_index` for untrusted input.
+	fn array_index(&self) -> usize {
+		self.try_array_index().unwrap()
+	}
+
+	/// `array_index`, or `None` if the frame precedes `game::FIRST_FRAME_INDEX`.
+	fn try_array_index(&self) -> Option<usize> {
+		self.index().checked_sub(game::FIRST_FRAME_INDEX)?.try_into().ok()
+	}
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PreV1_4 {
 	pub damage: f32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PreV1_2 {
 	pub raw_analog_x: u8,
 
 	#[cfg(v1_4)]
-	#[serde(flatten)]
+	#[cfg_attr(feature = "serde", serde(flatten))]
 	pub v1_4: PreV1_4,
 
 	#[cfg(not(v1_4))]
-	#[serde(flatten)]
-	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "serde", serde(flatten))]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
 	pub v1_4: Option<PreV1_4>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Pre {
 	pub index: i32,
 
@@ -110,18 +149,106 @@ pub struct Pre {
 	pub state: action_state::State,
 
 	#[cfg(v1_2)]
-	#[serde(flatten)]
+	#[cfg_attr(feature = "serde", serde(flatten))]
 	pub v1_2: PreV1_2,
 
 	#[cfg(not(v1_2))]
-	#[serde(flatten)]
-	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "serde", serde(flatten))]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
 	pub v1_2: Option<PreV1_2>,
 }
 
+impl Pre {
+	/// Compares two frames ignoring `random_seed`, which can differ between otherwise
+	/// identical re-encodings of a replay. Every other field is considered
+	/// gameplay-significant, including `index` and the version-specific fields.
+	pub fn eq_ignoring_seed(&self, other:&Pre) -> bool {
+		Pre { random_seed: other.random_seed, ..*self } == *other
+	}
+
+	/// The joystick's raw x coordinate, as read from the controller before any
+	/// processing. `None` before v1.2. See `JoystickAnalog` for the ranges.
+	pub fn raw_joystick_x(&self) -> Option<i8> {
+		self.v1_2.as_ref().map(|v| v.raw_analog_x as i8)
+	}
+
+	/// The joystick both as processed by the game and, where recorded, raw.
+	pub fn joystick_analog(&self) -> JoystickAnalog {
+		JoystickAnalog {
+			processed: self.joystick,
+			raw_x: self.raw_joystick_x(),
+		}
+	}
+
+	/// This frame's recorded inputs, grouped: the processed sticks, the logical
+	/// trigger and the buttons, as Slippi stored them. Nothing is recomputed, so this
+	/// doesn't reflect UCF's dash-back or shield-drop fixes, which act on the game's
+	/// response to inputs rather than on the inputs themselves.
+	pub fn controller(&self) -> Controller {
+		Controller {
+			joystick: self.joystick,
+			cstick: self.cstick,
+			trigger: self.triggers.logical,
+			buttons: self.buttons,
+		}
+	}
+}
+
+/// Melee's analog stick deadzone: processed values closer to 0 than this become 0.
+pub const STICK_DEADZONE:f32 = 0.2875;
+
+/// Applies Melee's analog stick processing to raw stick values (as read from the
+/// controller, centered at 0):
+///
+/// 1. Each axis is scaled by 1/80, so that ±80 maps to ±1.
+/// 2. If the resulting point lies outside the unit circle, it's pulled back onto it
+///    (keeping its direction). This is what turns the gate's octagon into a circle,
+///    and is why diagonals never reach ±1 on either axis.
+/// 3. Each axis whose magnitude is below `STICK_DEADZONE` is set to 0.
+///
+/// The joystick and C-stick positions stored in `Pre` are already processed this way.
+pub fn process_stick(x:i8, y:i8) -> Position {
+	let (mut x, mut y) = (x as f32 / 80.0, y as f32 / 80.0);
+	let magnitude = x.hypot(y);
+	if magnitude > 1.0 {
+		x /= magnitude;
+		y /= magnitude;
+	}
+	let deadzone = |v:f32| match v.abs() < STICK_DEADZONE {
+		true => 0.0,
+		_ => v,
+	};
+	Position { x: deadzone(x), y: deadzone(y) }
+}
+
+/// A frame's joystick position, processed and raw.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JoystickAnalog {
+	/// As processed by the game (see `process_stick`): each axis is in [-1, 1], within
+	/// the unit circle, and 0 inside `STICK_DEADZONE`.
+	pub processed: Position,
+	/// Raw x coordinate as reported by the controller, centered at 0, in [-128, 127].
+	/// The game calibrates this before the processing `process_stick` describes, so
+	/// it's on a different scale: a stick held at the gate typically reads about ±100.
+	/// Slippi doesn't record the raw y coordinate in the versions this parser supports.
+	pub raw_x: Option<i8>,
+}
+
+/// A frame's recorded controller state (see `Pre::controller`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Controller {
+	/// Processed joystick position (see `process_stick`).
+	pub joystick: Position,
+	/// Processed C-stick position (see `process_stick`).
+	pub cstick: Position,
+	/// Analog trigger value used by the game: the greater of L and R, in [0, 1].
+	pub trigger: f32,
+	pub buttons: Buttons,
+}
+
 impl Indexed for Pre {
-	fn array_index(&self) -> usize {
-		(self.index - game::FIRST_FRAME_INDEX).try_into().unwrap()
+	fn index(&self) -> i32 {
+		self.index
 	}
 }
 
@@ -156,12 +283,14 @@ query_impl!(PreV1_4, self, f, config, query {
 	}
 });
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PostV2_1 {
 	pub hurtbox_state: HurtboxState,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PostV2_0 {
 	pub flags: StateFlags,
 	pub misc_as: f32,
@@ -171,30 +300,32 @@ pub struct PostV2_0 {
 	pub airborne: bool,
 
 	#[cfg(v2_1)]
-	#[serde(flatten)]
+	#[cfg_attr(feature = "serde", serde(flatten))]
 	pub v2_1: PostV2_1,
 
 	#[cfg(not(v2_1))]
-	#[serde(flatten)]
-	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "serde", serde(flatten))]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
 	pub v2_1: Option<PostV2_1>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PostV0_2 {
 	pub state_age: f32,
 
 	#[cfg(v2_0)]
-	#[serde(flatten)]
+	#[cfg_attr(feature = "serde", serde(flatten))]
 	pub v2_0: PostV2_0,
 
 	#[cfg(not(v2_0))]
-	#[serde(flatten)]
-	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "serde", serde(flatten))]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
 	pub v2_0: Option<PostV2_0>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(from = "PostDe"))]
 pub struct Post {
 	pub index: i32,
 
@@ -210,18 +341,113 @@ pub struct Post {
 	pub stocks: u8,
 
 	#[cfg(v0_2)]
-	#[serde(flatten)]
+	#[cfg_attr(feature = "serde", serde(flatten))]
 	pub v0_2: PostV0_2,
 
 	#[cfg(not(v0_2))]
-	#[serde(flatten)]
-	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "serde", serde(flatten))]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
 	pub v0_2: Option<PostV0_2>,
 }
 
+/// `Post` as serialized, whose `state` still has to be resolved against `character`
+/// (see `action_state::State`'s `Deserialize` impl).
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct PostDe {
+	index: i32,
+	position: Position,
+	direction: Direction,
+	damage: f32,
+	shield: f32,
+	state: action_state::State,
+	character: character::Internal,
+	last_attack_landed: Option<attack::Attack>,
+	combo_count: u8,
+	last_hit_by: u8,
+	stocks: u8,
+
+	#[cfg(v0_2)]
+	#[serde(flatten)]
+	v0_2: PostV0_2,
+
+	#[cfg(not(v0_2))]
+	#[serde(flatten)]
+	v0_2: Option<PostV0_2>,
+}
+
+#[cfg(feature = "serde")]
+impl From<PostDe> for Post {
+	fn from(p:PostDe) -> Post {
+		Post {
+			index: p.index,
+			position: p.position,
+			direction: p.direction,
+			damage: p.damage,
+			shield: p.shield,
+			state: action_state::State::from(p.state.into(), p.character),
+			character: p.character,
+			last_attack_landed: p.last_attack_landed,
+			combo_count: p.combo_count,
+			last_hit_by: p.last_hit_by,
+			stocks: p.stocks,
+			v0_2: p.v0_2,
+		}
+	}
+}
+
+/// Interpretation of `PostV2_0::misc_as`, whose meaning depends on the action state.
+/// Checked in this order:
+///
+/// | state                                                  | variant            |
+/// |--------------------------------------------------------|--------------------|
+/// | `HIT_STUN` flag, or `DAMAGE_HI_1` to `DAMAGE_FLY_ROLL` | `HitstunRemaining` |
+/// | airborne, or `GUARD_ON` to `GUARD_REFLECT`             | `AirtimeOrCharge`  |
+/// | anything else                                          | `Unknown`          |
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MiscAs {
+	/// Frames of hitstun remaining, counting down to 0.
+	HitstunRemaining(f32),
+	/// A per-state timer that counts up. In the shield states it's the frames since the
+	/// shield went up (carried across shieldstun and `GUARD_OFF`); while airborne it's
+	/// whatever timer or charge level the current action keeps there, and is often
+	/// left over from an earlier state.
+	AirtimeOrCharge(f32),
+	/// Any other state. The value is used for various per-state timers and charge
+	/// levels, but those uses aren't characterized yet.
+	Unknown(f32),
+}
+
+impl Post {
+	/// Typed view of `misc_as`, or `None` for replays older than v2.0.
+	pub fn misc_as_interpreted(&self) -> Option<MiscAs> {
+		use action_state::Common;
+		let v2_0 = self.v0_2.as_ref()?.v2_0.as_ref()?;
+		let common = match self.state {
+			action_state::State::Common(Common(s)) => Some(s),
+			_ => None,
+		};
+		let in_range = |first:Common, last:Common| common.is_some_and(|s| (first.0 ..= last.0).contains(&s));
+		Some(match () {
+			_ if in_range(Common::DAMAGE_HI_1, Common::DAMAGE_FLY_ROLL) || (v2_0.flags & StateFlags::HIT_STUN).0 != 0 =>
+				MiscAs::HitstunRemaining(v2_0.misc_as),
+			_ if in_range(Common::GUARD_ON, Common::GUARD_REFLECT) || v2_0.airborne =>
+				MiscAs::AirtimeOrCharge(v2_0.misc_as),
+			_ => MiscAs::Unknown(v2_0.misc_as),
+		})
+	}
+
+	/// Port of the player who last hit this character, or `None` if it hasn't been hit by
+	/// a player. The game records 6 for that, but any value outside 0-3 is treated the same.
+	/// Some hits (e.g. from some projectiles and items) aren't attributed to the attacker.
+	pub fn last_hit_by_port(&self) -> Option<u8> {
+		Some(self.last_hit_by).filter(|&p| (p as usize) < game::NUM_PORTS)
+	}
+}
+
 impl Indexed for Post {
-	fn array_index(&self) -> usize {
-		(self.index - game::FIRST_FRAME_INDEX).try_into().unwrap()
+	fn index(&self) -> i32 {
+		self.index
 	}
 }
 
@@ -270,3 +496,140 @@ query_impl!(PostV2_1, self, f, config, query {
 		s => Err(err!("unknown field `post.{}`", s)),
 	}
 });
+
+/// Both halves of one character's frame (see `frame_joiner::FrameJoiner`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Frame {
+	pub pre: Pre,
+	pub post: Post,
+}
+
+impl Indexed for Frame {
+	fn index(&self) -> i32 {
+		self.post.index
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ItemV3_6 {
+	/// Port of the character that owns this item, or `None` if unowned.
+	pub owner: Option<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ItemV3_2 {
+	/// Item-specific data, such as Samus missile type or Peach turnip face.
+	pub misc: [u8; 4],
+
+	#[cfg(v3_6)]
+	#[cfg_attr(feature = "serde", serde(flatten))]
+	pub v3_6: ItemV3_6,
+
+	#[cfg(not(v3_6))]
+	#[cfg_attr(feature = "serde", serde(flatten))]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub v3_6: Option<ItemV3_6>,
+}
+
+/// One on-screen item (including projectiles) on one frame, from an Item Update event.
+/// These were added in Slippi v3.0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Item {
+	pub index: i32,
+
+	pub r#type: item::Item,
+	pub state: u8,
+	/// `None` for items that don't face either way.
+	pub direction: Option<Direction>,
+	pub velocity: Position,
+	pub position: Position,
+	pub damage: u16,
+	/// Frames until the item expires.
+	pub timer: f32,
+	/// Unique per item spawned during the game, to tell same-typed items apart.
+	pub id: u32,
+
+	#[cfg(v3_2)]
+	#[cfg_attr(feature = "serde", serde(flatten))]
+	pub v3_2: ItemV3_2,
+
+	#[cfg(not(v3_2))]
+	#[cfg_attr(feature = "serde", serde(flatten))]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub v3_2: Option<ItemV3_2>,
+}
+
+impl Indexed for Item {
+	fn index(&self) -> i32 {
+		self.index
+	}
+}
+
+query_impl!(Item, self, f, config, query {
+	match &*query[0] {
+		"index" => self.index.query(f, config, &query[1..]),
+		"type" => self.r#type.query(f, config, &query[1..]),
+		"state" => self.state.query(f, config, &query[1..]),
+		"direction" => self.direction.query(f, config, &query[1..]),
+		"velocity" => self.velocity.query(f, config, &query[1..]),
+		"position" => self.position.query(f, config, &query[1..]),
+		"damage" => self.damage.query(f, config, &query[1..]),
+		"timer" => self.timer.query(f, config, &query[1..]),
+		"id" => self.id.query(f, config, &query[1..]),
+		"v3_2" => self.v3_2.query(f, config, &query[1..]),
+		_ => self.v3_2.query(f, config, query),
+	}
+});
+
+query_impl!(ItemV3_2, self, f, config, query {
+	match &*query[0] {
+		"misc" => self.misc.query(f, config, &query[1..]),
+		"v3_6" => self.v3_6.query(f, config, &query[1..]),
+		_ => self.v3_6.query(f, config, query),
+	}
+});
+
+query_impl!(ItemV3_6, self, f, config, query {
+	match &*query[0] {
+		"owner" => self.owner.query(f, config, &query[1..]),
+		s => Err(err!("unknown field `item.{}`", s)),
+	}
+});
+
+/// Marks the start of a frame's events, from a Frame Start event (added in Slippi v2.2).
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Start {
+	pub index: i32,
+	/// The game's random seed at the start of the frame.
+	pub random_seed: u32,
+	/// Frames since the scene (rather than the game) started, which keeps counting
+	/// through pauses (v3.10+).
+	pub scene_frame_counter: Option<u32>,
+}
+
+impl Indexed for Start {
+	fn index(&self) -> i32 {
+		self.index
+	}
+}
+
+/// Marks the end of a frame's events, from a Frame Bookend event (added in Slippi v3.0).
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Bookend {
+	pub index: i32,
+	/// Latest frame that can no longer be rolled back (v3.7+). For a frame with
+	/// a greater index, a later event for the same index supersedes this one.
+	pub latest_finalized_frame: Option<i32>,
+}
+
+impl Indexed for Bookend {
+	fn index(&self) -> i32 {
+		self.index
+	}
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::io::Result;
+
+use super::{frame, game, parse, ubjson};
+use super::frame::{Frame, Pre};
+use super::game::NUM_PORTS;
+use super::parse::{FrameEvent, Handlers};
+
+/// `Handlers` that also want each character's Pre and Post joined into one `Frame`.
+pub trait FrameHandlers: Handlers {
+	/// Called once both halves of a frame have arrived, right after `frame_post`.
+	fn frame(&mut self, _: FrameEvent<Frame>) -> Result<()> { Ok(()) }
+}
+
+/// Wraps a `FrameHandlers`, forwarding every event to it and also calling `frame` once
+/// a Post arrives for a Pre with the same index, port and follower flag. A Pre with no
+/// matching Post (because the replay was truncated, say) is never sent as a `Frame`,
+/// nor is a Post without its Pre. Rolled-back frames are sent again, like their halves.
+#[derive(Debug)]
+pub struct FrameJoiner<H> {
+	pub inner: H,
+	/// Latest unmatched Pre, by port and then follower flag.
+	pending: [[Option<Pre>; 2]; NUM_PORTS],
+}
+
+impl<H> FrameJoiner<H> {
+	pub fn new(inner:H) -> FrameJoiner<H> {
+		FrameJoiner { inner, pending: [[None; 2]; NUM_PORTS] }
+	}
+
+	fn pending(&mut self, id:parse::FrameId) -> Result<&mut Option<Pre>> {
+		self.pending.get_mut(id.port as usize)
+			.map(|p| &mut p[id.is_follower as usize])
+			.ok_or_else(|| err!("invalid port: {}", id.port))
+	}
+}
+
+impl<H:FrameHandlers> Handlers for FrameJoiner<H> {
+	fn payload_sizes(&mut self, sizes:&HashMap<u8, u16>) -> Result<()> {
+		self.inner.payload_sizes(sizes)
+	}
+
+	fn unknown_event(&mut self, code:u8, payload:&[u8]) -> Result<()> {
+		self.inner.unknown_event(code, payload)
+	}
+
+	fn raw_event(&mut self, code:u8, payload:&[u8]) -> Result<()> {
+		self.inner.raw_event(code, payload)
+	}
+
+	fn game_start(&mut self, s:game::Start) -> Result<()> {
+		self.inner.game_start(s)
+	}
+
+	fn game_end(&mut self, e:game::End) -> Result<()> {
+		self.inner.game_end(e)
+	}
+
+	fn frame_start(&mut self, s:FrameEvent<frame::Start, i32>) -> Result<()> {
+		self.inner.frame_start(s)
+	}
+
+	fn frame_pre(&mut self, e:FrameEvent<Pre>) -> Result<()> {
+		*self.pending(e.id)? = Some(e.event);
+		self.inner.frame_pre(e)
+	}
+
+	fn frame_post(&mut self, e:FrameEvent<frame::Post>) -> Result<()> {
+		let pre = self.pending(e.id)?.take().filter(|pre| pre.index == e.event.index);
+		let (id, post) = (e.id, e.event);
+		self.inner.frame_post(e)?;
+		match pre {
+			Some(pre) => self.inner.frame(FrameEvent { id, event: Frame { pre, post } }),
+			None => Ok(()),
+		}
+	}
+
+	fn item(&mut self, i:FrameEvent<frame::Item, parse::ItemId>) -> Result<()> {
+		self.inner.item(i)
+	}
+
+	fn frame_bookend(&mut self, b:FrameEvent<frame::Bookend, i32>) -> Result<()> {
+		self.inner.frame_bookend(b)
+	}
+
+	fn gecko_list(&mut self, codes:Vec<u8>) -> Result<()> {
+		self.inner.gecko_list(codes)
+	}
+
+	fn metadata(&mut self, m:ubjson::Map) -> Result<()> {
+		self.inner.metadata(m)
+	}
+}
@@ -1,25 +1,53 @@
-use std::fmt;
+use std::fmt::{self, Write};
+use std::convert::TryFrom;
 
-use serde::{Serialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use super::{character, frame, metadata, stage};
+use super::{character, frame, item, metadata, stage, ubjson};
 
 pub const NUM_PORTS:usize = 4;
 pub const FIRST_FRAME_INDEX:i32 = -123;
+/// First frame on which players can act (the rest of the countdown is before this).
+pub const FIRST_PLAYABLE_FRAME_INDEX:i32 = -39;
+/// Starting stocks under standard tournament rules (see `Game::is_competitive`).
+pub const COMPETITIVE_STOCKS:u8 = 4;
+/// Time limit in seconds under standard tournament rules (see `Game::is_competitive`).
+pub const COMPETITIVE_TIMER:u32 = 8 * 60;
 
-#[derive(Debug, PartialEq, Serialize)]
+/// Ordered by major, then minor, then patch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SlippiVersion(pub u8, pub u8, pub u8);
 
+impl SlippiVersion {
+	/// Whether this is version `major.minor.patch` or later.
+	pub fn at_least(&self, major:u8, minor:u8, patch:u8) -> bool {
+		*self >= SlippiVersion(major, minor, patch)
+	}
+}
+
+impl fmt::Display for SlippiVersion {
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}.{}.{}", self.0, self.1, self.2)
+	}
+}
+
 query_impl!(SlippiVersion);
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Slippi {
 	pub version: SlippiVersion,
+	/// The byte after the version, which the spec reserves but leaves unused. It's 0 in
+	/// every replay we know of; it's kept so nothing in Game Start is silently dropped.
+	pub unused_version_byte: u8,
 }
 
 query_impl!(Slippi, self, f, config, query {
 	match &*query[0] {
 		"version" => self.version.query(f, config, &query[1..]),
+		"unused_version_byte" => self.unused_version_byte.query(f, config, &query[1..]),
 		s => Err(err!("unknown field `slippi.{}`", s)),
 	}
 });
@@ -42,7 +70,8 @@ pseudo_enum!(TeamShade:u8 {
 	2 => DARK,
 });
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Team {
 	pub color: TeamColor,
 	pub shade: TeamShade,
@@ -66,7 +95,8 @@ pseudo_enum!(ShieldDrop:u32 {
 	2 => ARDUINO,
 });
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Ucf {
 	pub dash_back: Option<DashBack>,
 	pub shield_drop: Option<ShieldDrop>,
@@ -80,26 +110,29 @@ query_impl!(Ucf, self, f, config, query {
 	}
 });
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PlayerV1_3 {
 	pub name_tag: String,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PlayerV1_0 {
 	pub ucf: Ucf,
 
 	#[cfg(v1_3)]
-	#[serde(flatten)]
+	#[cfg_attr(feature = "serde", serde(flatten))]
 	pub v1_3: PlayerV1_3,
 
 	#[cfg(not(v1_3))]
-	#[serde(flatten)]
-	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "serde", serde(flatten))]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
 	pub v1_3: Option<PlayerV1_3>,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Player {
 	pub character: character::External,
 	pub r#type: PlayerType,
@@ -113,14 +146,47 @@ pub struct Player {
 	pub defense_ratio: f32,
 	pub model_scale: f32,
 
-	#[cfg(v1_0)] #[serde(flatten)]
+	#[cfg(v1_0)] #[cfg_attr(feature = "serde", serde(flatten))]
 	pub v1_0: PlayerV1_0,
 
-	#[cfg(not(v1_0))] #[serde(flatten)]
-	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg(not(v1_0))] #[cfg_attr(feature = "serde", serde(flatten))]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
 	pub v1_0: Option<PlayerV1_0>,
 }
 
+impl Player {
+	/// Name of this player's costume (e.g. "Red"). See `character::costume_name`.
+	pub fn costume_name(&self) -> Option<&'static str> {
+		character::costume_name(self.character, self.costume)
+	}
+
+	/// Stocks this player started with. Usually 4, but configurable in the rules.
+	pub fn starting_stocks(&self) -> u8 {
+		self.stocks
+	}
+
+	/// In-game name tag (local play), if set.
+	pub fn name_tag(&self) -> Option<&str> {
+		self.v1_0.as_ref()
+			.and_then(|v| v.v1_3.as_ref())
+			.map(|v| v.name_tag.as_str())
+			.filter(|n| !n.is_empty())
+	}
+
+	/// A label for this player: the netplay display name, connect code, or name tag,
+	/// whichever is present first, falling back to the character name. Netplay names
+	/// live in the metadata, so pass this player's entry from `Metadata::players`.
+	pub fn best_display_name(&self, metadata:Option<&metadata::MetadataPlayer>) -> String {
+		let netplay = metadata.and_then(|m| m.netplay_name.as_deref())
+			.filter(|n| !n.is_empty())
+			.or_else(|| metadata.and_then(|m| m.netplay_code.as_deref()).filter(|c| !c.is_empty()));
+		match netplay.or_else(|| self.name_tag()) {
+			Some(name) => name.to_string(),
+			None => self.character.display_name().map_or_else(|| self.character.0.to_string(), |n| n.to_string()),
+		}
+	}
+}
+
 query_impl!(PlayerV1_3, self, f, config, query {
 	match &*query[0] {
 		"name_tag" => self.name_tag.query(f, config, &query[1..]),
@@ -154,24 +220,27 @@ query_impl!(Player, self, f, config, query {
 	}
 });
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct StartV2_0 {
 	pub is_frozen_ps: bool,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct StartV1_5 {
 	pub is_pal: bool,
 
-	#[cfg(v2_0)] #[serde(flatten)]
+	#[cfg(v2_0)] #[cfg_attr(feature = "serde", serde(flatten))]
 	pub v2_0: StartV2_0,
 
-	#[cfg(not(v2_0))] #[serde(flatten)]
-	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg(not(v2_0))] #[cfg_attr(feature = "serde", serde(flatten))]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
 	pub v2_0: Option<StartV2_0>,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Start {
 	pub slippi: Slippi,
 	pub bitfield: [u8; 3],
@@ -185,14 +254,161 @@ pub struct Start {
 	pub players: [Option<Player>; NUM_PORTS],
 	pub random_seed: u32,
 
-	#[cfg(v1_5)] #[serde(flatten)]
+	#[cfg(v1_5)] #[cfg_attr(feature = "serde", serde(flatten))]
 	pub v1_5: StartV1_5,
 
-	#[cfg(not(v1_5))] #[serde(flatten)]
-	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg(not(v1_5))] #[cfg_attr(feature = "serde", serde(flatten))]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
 	pub v1_5: Option<StartV1_5>,
 }
 
+pseudo_enum!(ItemFrequency:i8 {
+	-1 => OFF,
+	0 => VERY_LOW,
+	1 => LOW,
+	2 => MEDIUM,
+	3 => HIGH,
+	4 => VERY_HIGH,
+});
+
+/// Item settings, decoded from `Start::item_spawn_frequency` and `Start::item_spawn_bitfield`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ItemRules {
+	pub frequency: ItemFrequency,
+	pub bitfield: [u8; 5],
+}
+
+impl ItemRules {
+	/// Whether `item` is switched on. Bit `n` of the bitfield (read as a 40-bit big-endian
+	/// number, counting from the least significant bit) is the switch for item id `n`.
+	/// Note that no items spawn at all if `frequency` is `OFF`, regardless of the switches.
+	pub fn is_enabled(&self, item:item::Item) -> bool {
+		let bits = self.bitfield.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+		item.0 < 40 && bits & (1 << item.0) != 0
+	}
+
+	/// Every item id whose switch is on, in id order.
+	pub fn enabled(&self) -> Vec<item::Item> {
+		(0 .. 40).map(item::Item).filter(|&i| self.is_enabled(i)).collect()
+	}
+}
+
+pseudo_enum!(GameMode:u8 {
+	0 => TIME,
+	1 => STOCK,
+	2 => COIN,
+	3 => BONUS,
+});
+
+pseudo_enum!(TimerType:u8 {
+	0 => NONE,
+	2 => DECREASING,
+	3 => INCREASING,
+});
+
+/// Match rules, decoded from the various raw fields of `Start`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rules {
+	/// Top three bits of `Start::bitfield[0]`.
+	pub mode: GameMode,
+	/// Bottom two bits of `Start::bitfield[0]`.
+	pub timer_type: TimerType,
+	/// Time limit in seconds (`Start::timer`).
+	pub timer: u32,
+	/// Starting stocks by port.
+	pub stocks: [Option<u8>; NUM_PORTS],
+	pub is_teams: bool,
+	/// Whether teammates can hit each other. Bit 0 of `Start::bitfield[1]`.
+	pub friendly_fire: bool,
+	/// Points for a self-destruct, applicable in time mode (`Start::self_destruct_score`).
+	pub self_destruct_score: i8,
+	pub items: ItemRules,
+	pub damage_ratio: f32,
+}
+
+impl Start {
+	pub fn item_rules(&self) -> ItemRules {
+		ItemRules {
+			frequency: ItemFrequency(self.item_spawn_frequency),
+			bitfield: self.item_spawn_bitfield,
+		}
+	}
+
+	/// Items switched on in the item settings. See `ItemRules::is_enabled`.
+	pub fn enabled_items(&self) -> Vec<item::Item> {
+		self.item_rules().enabled()
+	}
+
+	/// Whether any item switch is on. Items may still never spawn, if
+	/// `item_spawn_frequency` is `ItemFrequency::OFF`.
+	pub fn items_enabled(&self) -> bool {
+		self.item_spawn_bitfield.iter().any(|&b| b != 0)
+	}
+
+	/// Game mode, from `bitfield`.
+	pub fn mode(&self) -> GameMode {
+		GameMode(self.bitfield[0] >> 5)
+	}
+
+	pub fn is_stock_match(&self) -> bool {
+		self.mode() == GameMode::STOCK
+	}
+
+	pub fn is_time_match(&self) -> bool {
+		self.mode() == GameMode::TIME
+	}
+
+	/// Whether the match clock counts down, up, or not at all, from `bitfield`.
+	pub fn timer_type(&self) -> TimerType {
+		TimerType(self.bitfield[0] & 0b11)
+	}
+
+	/// Game time elapsed as of frame `index`, counting from "Go" on frame 0 (so it's 0
+	/// throughout the countdown, which starts at `FIRST_FRAME_INDEX`). Melee runs at
+	/// 60 frames per second.
+	pub fn elapsed(&self, index:i32) -> std::time::Duration {
+		let frames = index.max(0) as u64;
+		std::time::Duration::new(frames / 60, (frames % 60 * 1_000_000_000 / 60) as u32)
+	}
+
+	/// Time shown on the in-game clock as of frame `index`: the time remaining out of
+	/// `timer` if the clock counts down, or the `elapsed` time if it counts up. `None`
+	/// if there's no clock (`TimerType::NONE`).
+	pub fn frame_to_clock(&self, index:i32) -> Option<std::time::Duration> {
+		match self.timer_type() {
+			TimerType::DECREASING => Some(std::time::Duration::from_secs(self.timer as u64).saturating_sub(self.elapsed(index))),
+			TimerType::INCREASING => Some(self.elapsed(index)),
+			_ => None,
+		}
+	}
+
+	/// Whether teammates can hurt each other, from `bitfield`.
+	pub fn is_friendly_fire(&self) -> bool {
+		self.bitfield[1] & 0b1 != 0
+	}
+
+	/// All the match rules in one place. The raw fields remain available on `Start`.
+	pub fn rules(&self) -> Rules {
+		let mut stocks = [None; NUM_PORTS];
+		for (s, p) in stocks.iter_mut().zip(self.players.iter()) {
+			*s = p.as_ref().map(|p| p.starting_stocks());
+		}
+		Rules {
+			mode: self.mode(),
+			timer_type: self.timer_type(),
+			timer: self.timer,
+			stocks,
+			is_teams: self.is_teams,
+			friendly_fire: self.is_friendly_fire(),
+			self_destruct_score: self.self_destruct_score,
+			items: self.item_rules(),
+			damage_ratio: self.damage_ratio,
+		}
+	}
+}
+
 query_impl!(StartV2_0, self, f, config, query {
 	match &*query[0] {
 		"is_frozen_ps" => self.is_frozen_ps.query(f, config, &query[1..]),
@@ -234,20 +450,22 @@ pseudo_enum!(EndMethod:u8 {
 	7 => NO_CONTEST,
 });
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct EndV2_0 {
 	pub lras_initiator: i8,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct End {
 	pub method: EndMethod,
 
-	#[cfg(v2_0)] #[serde(flatten)]
+	#[cfg(v2_0)] #[cfg_attr(feature = "serde", serde(flatten))]
 	pub v2_0: EndV2_0,
 
-	#[cfg(not(v2_0))] #[serde(flatten)]
-	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg(not(v2_0))] #[cfg_attr(feature = "serde", serde(flatten))]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
 	pub v2_0: Option<EndV2_0>,
 }
 
@@ -258,6 +476,22 @@ query_impl!(EndV2_0, self, f, config, query {
 	}
 });
 
+impl End {
+	/// Whether a player quit with L+R+A+Start. See `lras_port` for who.
+	pub fn is_lras(&self) -> bool {
+		self.method == EndMethod::NO_CONTEST
+	}
+
+	/// Port of the player who quit with L+R+A+Start, if any. `lras_initiator` is -1 when
+	/// nobody did, and is missing before v2.0. Values that aren't a valid port are
+	/// treated like -1.
+	pub fn lras_port(&self) -> Option<u8> {
+		self.v2_0.as_ref()
+			.and_then(|v| u8::try_from(v.lras_initiator).ok())
+			.filter(|&p| (p as usize) < NUM_PORTS)
+	}
+}
+
 query_impl!(End, self, f, config, query {
 	match &*query[0] {
 		"method" => self.method.query(f, config, &query[1..]),
@@ -266,18 +500,41 @@ query_impl!(End, self, f, config, query {
 	}
 });
 
+#[cfg(feature = "serde")]
 fn skip_frames<T>(_:&T) -> bool {
 	!unsafe { super::CONFIG.frames }
 }
 
-#[derive(PartialEq, Serialize)]
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(from = "FramesDe"))]
 pub struct Frames {
-	#[serde(skip_serializing_if = "skip_frames")]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "skip_frames"))]
 	pub pre: Vec<frame::Pre>,
-	#[serde(skip_serializing_if = "skip_frames")]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "skip_frames"))]
 	pub post: Vec<frame::Post>,
 }
 
+/// `Frames` as serialized. `frame::Pre` has no character to resolve its `state`
+/// against, so this uses the character from the same frame's `frame::Post`.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct FramesDe {
+	#[serde(default)]
+	pre: Vec<frame::Pre>,
+	#[serde(default)]
+	post: Vec<frame::Post>,
+}
+
+#[cfg(feature = "serde")]
+impl From<FramesDe> for Frames {
+	fn from(mut f:FramesDe) -> Frames {
+		for (pre, post) in f.pre.iter_mut().zip(f.post.iter()) {
+			pre.state = super::action_state::State::from(pre.state.into(), post.character);
+		}
+		Frames { pre: f.pre, post: f.post }
+	}
+}
+
 query_impl!(Frames, self, f, config, query {
 	match &*query[0] {
 		"pre" => self.pre.query(f, config, &query[1..]),
@@ -286,6 +543,46 @@ query_impl!(Frames, self, f, config, query {
 	}
 });
 
+fn append_contiguous<T:Copy + frame::Indexed>(frames:&mut Vec<T>, other:&[T]) -> std::io::Result<()> {
+	if let (Some(last), Some(first)) = (frames.last(), other.first()) {
+		if first.index() != last.index() + 1 {
+			Err(err!("non-contiguous frames: frame {} followed by frame {}",
+				last.index(), first.index()))?;
+		}
+	}
+	frames.extend_from_slice(other);
+	Ok(())
+}
+
+impl Frames {
+	fn append(&mut self, other:&Frames) -> std::io::Result<()> {
+		append_contiguous(&mut self.pre, &other.pre)?;
+		append_contiguous(&mut self.post, &other.post)
+	}
+
+	/// Array position of frame `index`, if it's in range. `None` if any index repeats
+	/// (as with `parse::RollbackMode::KeepAll`), since there's then no single frame
+	/// for `index`, or if pre- and post-frame data don't line up.
+	fn position(&self, index:i32) -> Option<usize> {
+		let (first, last) = (self.pre.first()?.index, self.pre.last()?.index);
+		let span = i64::from(last) - i64::from(first) + 1;
+		if span != self.pre.len() as i64 || self.post.len() != self.pre.len() {
+			return None;
+		}
+		usize::try_from(index.checked_sub(first)?).ok()
+			.filter(|&i| i < self.pre.len())
+			.filter(|&i| self.pre[i].index == index && self.post[i].index == index)
+	}
+
+	fn slice(&self, range:&std::ops::Range<i32>) -> Frames {
+		let keep = |index:i32| range.contains(&index);
+		Frames {
+			pre: self.pre.iter().filter(|f| keep(f.index)).copied().collect(),
+			post: self.post.iter().filter(|f| keep(f.index)).copied().collect(),
+		}
+	}
+}
+
 impl fmt::Debug for Frames {
 	fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
 		match unsafe { super::CONFIG.frames } {
@@ -301,14 +598,51 @@ impl fmt::Debug for Frames {
 	}
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Port {
 	pub leader: Frames,
-	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
 	pub follower: Option<Frames>,
 }
 
-query_impl!(Port, self, f, config, query {
+impl Port {
+	/// This port as the exporters see it, with `opts` applied. See `Game::export`.
+	pub fn export(&self, opts:&super::ExportOptions) -> ExportedPort<'_> {
+		ExportedPort {
+			leader: &self.leader,
+			follower: self.follower.as_ref().filter(|_| opts.include_followers),
+		}
+	}
+}
+
+/// Queries go through `export`, with the query's `Config::export`.
+#[cfg(feature = "serde")]
+impl super::query::Query for Port {
+	fn query(&self, f:&mut dyn std::io::Write, config:&super::Config, query:&[&str]) -> std::io::Result<()> {
+		self.export(&config.export).query(f, config, query)
+	}
+}
+
+/// A borrowed `Port` with `ExportOptions` applied. Serializes like `Port`.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ExportedPort<'a> {
+	pub leader: &'a Frames,
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub follower: Option<&'a Frames>,
+}
+
+impl fmt::Debug for ExportedPort<'_> {
+	fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Port")
+			.field("leader", self.leader)
+			.field("follower", &self.follower)
+			.finish()
+	}
+}
+
+query_impl!(ExportedPort<'_>, self, f, config, query {
 	match &*query[0] {
 		"leader" => self.leader.query(f, config, &query[1..]),
 		"follower" => self.follower.query(f, config, &query[1..]),
@@ -316,7 +650,8 @@ query_impl!(Port, self, f, config, query {
 	}
 });
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Game {
 	pub start: Start,
 	pub end: End,
@@ -324,7 +659,37 @@ pub struct Game {
 	pub metadata: metadata::Metadata,
 }
 
-query_impl!(Game, self, f, config, query {
+/// Queries go through `export`, with the query's `Config::export`.
+#[cfg(feature = "serde")]
+impl super::query::Query for Game {
+	fn query(&self, f:&mut dyn std::io::Write, config:&super::Config, query:&[&str]) -> std::io::Result<()> {
+		self.export(&config.export).query(f, config, query)
+	}
+}
+
+/// A borrowed `Game` with `ExportOptions` applied (see `Game::export`). Serializes
+/// like `Game`.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ExportedGame<'a> {
+	pub start: &'a Start,
+	pub end: &'a End,
+	pub ports: [Option<ExportedPort<'a>>; NUM_PORTS],
+	pub metadata: &'a metadata::Metadata,
+}
+
+impl fmt::Debug for ExportedGame<'_> {
+	fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Game")
+			.field("start", self.start)
+			.field("end", self.end)
+			.field("ports", &self.ports)
+			.field("metadata", self.metadata)
+			.finish()
+	}
+}
+
+query_impl!(ExportedGame<'_>, self, f, config, query {
 	match &*query[0] {
 		"start" => self.start.query(f, config, &query[1..]),
 		"end" => self.end.query(f, config, &query[1..]),
@@ -333,3 +698,457 @@ query_impl!(Game, self, f, config, query {
 		s => Err(err!("unknown field `game.{}`", s)),
 	}
 });
+
+fn dump_frames(s:&mut String, port:usize, label:&str, frames:&Frames, index:i32) -> fmt::Result {
+	let idx = match frames.position(index) {
+		Some(idx) => idx,
+		_ => return Ok(()),
+	};
+	if let (Some(pre), Some(post)) = (frames.pre.get(idx), frames.post.get(idx)) {
+		writeln!(s, "port {} ({}): {}, {}, {:?}, {}%, {} stocks, buttons {:?}",
+			port,
+			label,
+			post.character.name().map_or_else(|| post.character.0.to_string(), |n| n.to_string()),
+			post.state.name().map_or_else(|| format!("{:?}", post.state), |n| n.to_string()),
+			post.position,
+			post.damage,
+			post.stocks,
+			pre.buttons.logical.names())?;
+	}
+	Ok(())
+}
+
+/// Occupancy of one port, combining its `Start` player slot with its frame data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PortState<'a> {
+	/// No player and no frames: the slot was intentionally left empty.
+	Empty,
+	/// A player with frame data.
+	Active(&'a Player, &'a Port),
+	/// A player without any frame data (e.g. a truncated replay).
+	MissingFrames(&'a Player),
+	/// Frame data without a player in `Start`. Indicates a malformed replay.
+	MissingPlayer(&'a Port),
+}
+
+/// All ports' data for a single frame.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameRef<'a> {
+	pub index: i32,
+	game: &'a Game,
+}
+
+impl<'a> FrameRef<'a> {
+	fn frames(&self, port:u8, follower:bool) -> Option<(&'a Frames, usize)> {
+		let p = self.game.ports.get(port as usize)?.as_ref()?;
+		let frames = match follower {
+			true => p.follower.as_ref()?,
+			_ => &p.leader,
+		};
+		Some((frames, frames.position(self.index)?))
+	}
+
+	/// Pre-frame data for the leader on `port`, if present.
+	pub fn pre(&self, port:u8) -> Option<&'a frame::Pre> {
+		self.frames(port, false).and_then(|(f, i)| f.pre.get(i))
+	}
+
+	/// Post-frame data for the leader on `port`, if present.
+	pub fn post(&self, port:u8) -> Option<&'a frame::Post> {
+		self.frames(port, false).and_then(|(f, i)| f.post.get(i))
+	}
+
+	/// Pre-frame data for the follower (Nana) on `port`, if present.
+	pub fn follower_pre(&self, port:u8) -> Option<&'a frame::Pre> {
+		self.frames(port, true).and_then(|(f, i)| f.pre.get(i))
+	}
+
+	/// Post-frame data for the follower (Nana) on `port`, if present.
+	pub fn follower_post(&self, port:u8) -> Option<&'a frame::Post> {
+		self.frames(port, true).and_then(|(f, i)| f.post.get(i))
+	}
+}
+
+/// A frame on which two games differ (see `Game::diff_frames`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameDiff {
+	pub port: u8,
+	pub is_follower: bool,
+	pub index: i32,
+}
+
+fn diff_frames(a:&Frames, b:&Frames, port:u8, is_follower:bool, include_seed:bool, out:&mut Vec<FrameDiff>) {
+	let len = a.pre.len().max(b.pre.len()).max(a.post.len()).max(b.post.len());
+	for i in 0 .. len {
+		let pre_eq = match (a.pre.get(i), b.pre.get(i)) {
+			(Some(x), Some(y)) => match include_seed {
+				true => x == y,
+				_ => x.eq_ignoring_seed(y),
+			},
+			(x, y) => x.is_none() && y.is_none(),
+		};
+		let (post_a, post_b) = (a.post.get(i), b.post.get(i));
+		if !pre_eq || post_a != post_b {
+			let index = a.pre.get(i).map(|p| p.index)
+				.or_else(|| b.pre.get(i).map(|p| p.index))
+				.or_else(|| post_a.or(post_b).map(|p| p.index))
+				.unwrap_or_default();
+			out.push(FrameDiff { port, is_follower, index });
+		}
+	}
+}
+
+/// A lost stock, as detected from a drop in `frame::Post::stocks`. This is what every
+/// stat in `stats` counts as a death. See `stats::kill_moves` for more detail about
+/// the hit responsible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Death {
+	/// Frame on which the stock count went down.
+	pub frame: i32,
+	/// `last_hit_by` as of the last frame before the death, or `None` for a
+	/// self-destruct (or a hit from a non-player, such as a stage hazard).
+	pub killed_by: Option<u8>,
+	/// Which of the player's stocks was lost (1 for the first), counted from
+	/// `Player::starting_stocks`.
+	pub stock: u8,
+}
+
+/// A personally-identifying field that's present (non-empty) in a replay.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PiiField {
+	/// In-game name tag of the player on this port.
+	NameTag(usize),
+	/// `metadata.players.N.names.netplay`
+	NetplayName(usize),
+	/// `metadata.players.N.names.code`
+	NetplayCode(usize),
+	/// `metadata.consoleNick`
+	ConsoleName,
+}
+
+fn non_empty_str(obj:Option<&ubjson::Object>) -> bool {
+	matches!(obj, Some(ubjson::Object::Str(s)) if !s.is_empty())
+}
+
+/// Which version-specific data a replay contains, based on its Slippi version.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+	/// `frame::PostV0_2` (action state age)
+	pub has_state_age: bool,
+	/// `PlayerV1_0` (UCF settings)
+	pub has_ucf: bool,
+	/// `frame::PreV1_2` (raw analog x)
+	pub has_raw_analog: bool,
+	/// `PlayerV1_3` (name tags)
+	pub has_name_tags: bool,
+	/// `frame::PreV1_4` (damage in pre-frame updates)
+	pub has_pre_damage: bool,
+	/// `StartV1_5` (PAL)
+	pub has_pal: bool,
+	/// `frame::PostV2_0` (state flags, ground, jumps, L-cancel), `StartV2_0`, `EndV2_0`
+	pub has_flags: bool,
+	/// `frame::PostV2_1` (hurtbox state)
+	pub has_hurtbox: bool,
+	/// `frame::Item` (Item Update events)
+	pub has_items: bool,
+	/// `frame::Bookend` (Frame Bookend events)
+	pub has_frame_bookend: bool,
+	/// `frame::ItemV3_2` (item-specific misc bytes)
+	pub has_item_misc: bool,
+	/// `frame::ItemV3_6` (item owner)
+	pub has_item_owner: bool,
+	/// `frame::Bookend::latest_finalized_frame` (rollback)
+	pub has_latest_finalized_frame: bool,
+}
+
+impl Game {
+	pub fn capabilities(&self) -> Capabilities {
+		let v = &self.start.slippi.version;
+		let since = |major, minor, patch| v.at_least(major, minor, patch);
+		Capabilities {
+			has_state_age: since(0, 2, 0),
+			has_ucf: since(1, 0, 0),
+			has_raw_analog: since(1, 2, 0),
+			has_name_tags: since(1, 3, 0),
+			has_pre_damage: since(1, 4, 0),
+			has_pal: since(1, 5, 0),
+			has_flags: since(2, 0, 0),
+			has_hurtbox: since(2, 1, 0),
+			has_items: since(3, 0, 0),
+			has_frame_bookend: since(3, 0, 0),
+			has_item_misc: since(3, 2, 0),
+			has_item_owner: since(3, 6, 0),
+			has_latest_finalized_frame: since(3, 7, 0),
+		}
+	}
+
+	/// Occupancy of `port`, distinguishing empty slots from missing frame data.
+	/// `None` if `port` isn't a valid port number.
+	pub fn port_state(&self, port:u8) -> Option<PortState<'_>> {
+		let port = port as usize;
+		Some(match (self.start.players.get(port)?, self.ports.get(port)?) {
+			(None, None) => PortState::Empty,
+			(Some(player), Some(p)) => PortState::Active(player, p),
+			(Some(player), None) => PortState::MissingFrames(player),
+			(None, Some(p)) => PortState::MissingPlayer(p),
+		})
+	}
+
+	/// Personal fields still present in this replay, in port order followed by
+	/// `ConsoleName`. Empty for a fully anonymized replay. Metadata fields are checked
+	/// in the raw `metadata.json`, since that's what gets written back out.
+	pub fn contains_pii(&self) -> Vec<PiiField> {
+		let mut fields = Vec::new();
+		let players = match self.metadata.json.get("players") {
+			Some(ubjson::Object::Map(players)) => Some(players),
+			_ => None,
+		};
+
+		for port in 0 .. NUM_PORTS {
+			let name_tag = self.start.players[port].as_ref()
+				.and_then(|p| p.v1_0.as_ref())
+				.and_then(|v| v.v1_3.as_ref())
+				.is_some_and(|v| !v.name_tag.is_empty());
+			if name_tag {
+				fields.push(PiiField::NameTag(port));
+			}
+
+			let names = match players.and_then(|p| p.get(&port.to_string())) {
+				Some(ubjson::Object::Map(player)) => match player.get("names") {
+					Some(ubjson::Object::Map(names)) => Some(names),
+					_ => None,
+				},
+				_ => None,
+			};
+			if let Some(names) = names {
+				if non_empty_str(names.get("netplay")) {
+					fields.push(PiiField::NetplayName(port));
+				}
+				if non_empty_str(names.get("code")) {
+					fields.push(PiiField::NetplayCode(port));
+				}
+			}
+		}
+
+		if non_empty_str(self.metadata.json.get("consoleNick")) {
+			fields.push(PiiField::ConsoleName);
+		}
+		fields
+	}
+
+	/// Whether any player is CPU-controlled.
+	pub fn has_cpu(&self) -> bool {
+		self.start.players.iter().flatten().any(|p| p.r#type == PlayerType::CPU)
+	}
+
+	/// `Player::best_display_name` for the player on `port`, or `None` for an empty port.
+	pub fn display_name(&self, port:u8) -> Option<String> {
+		let port = port as usize;
+		let player = self.start.players.get(port)?.as_ref()?;
+		let metadata = self.metadata.players.as_ref().and_then(|p| p[port].as_ref());
+		Some(player.best_display_name(metadata))
+	}
+
+	/// Whether this looks like a serious singles game: exactly two human players,
+	/// no CPUs, standard rules (a `COMPETITIVE_STOCKS`-stock match with a
+	/// `COMPETITIVE_TIMER`-second countdown, items off), and a tournament-legal stage.
+	pub fn is_competitive(&self) -> bool {
+		let humans = self.start.players.iter().flatten().filter(|p| p.r#type == PlayerType::HUMAN).count();
+		let rules = self.start.rules();
+		humans == 2 &&
+			!self.has_cpu() &&
+			!rules.is_teams &&
+			rules.mode == GameMode::STOCK &&
+			rules.timer_type == TimerType::DECREASING &&
+			rules.timer == COMPETITIVE_TIMER &&
+			rules.stocks.iter().flatten().all(|&s| s == COMPETITIVE_STOCKS) &&
+			rules.items.frequency == ItemFrequency::OFF &&
+			self.start.stage.is_tournament_legal()
+	}
+
+	/// This game as the exporters see it, with `opts` applied. Serialize the result
+	/// (e.g. with `serde_json::to_writer`) for JSON output.
+	pub fn export(&self, opts:&super::ExportOptions) -> ExportedGame<'_> {
+		ExportedGame {
+			start: &self.start,
+			end: &self.end,
+			ports: std::array::from_fn(|i| self.ports[i].as_ref().map(|p| p.export(opts))),
+			metadata: &self.metadata,
+		}
+	}
+
+	/// Joins consecutive pieces of one game, such as a replay split across reconnects.
+	/// All pieces must have the same `start` and active ports, and each piece's frames
+	/// must pick up exactly where the previous piece's left off. The result's `end` is
+	/// taken from the last piece and its `metadata` from the first, except that
+	/// `lastFrame` (and so `duration`) is updated to cover the combined frames.
+	pub fn concat(games:&[Game]) -> std::io::Result<Game> {
+		let (first, rest) = games.split_first().ok_or_else(|| err!("no games to concatenate"))?;
+		let mut game = first.clone();
+
+		for next in rest {
+			if next.start != game.start {
+				Err(err!("mismatched game start"))?;
+			}
+
+			for (i, (port, next_port)) in game.ports.iter_mut().zip(next.ports.iter()).enumerate() {
+				match (port, next_port) {
+					(Some(port), Some(next_port)) => {
+						port.leader.append(&next_port.leader)
+							.map_err(|e| err!("port {} leader: {}", i, e))?;
+						match (&mut port.follower, &next_port.follower) {
+							(Some(f), Some(next_f)) => f.append(next_f)
+								.map_err(|e| err!("port {} follower: {}", i, e))?,
+							(None, None) => (),
+							_ => Err(err!("port {} has a follower in only some games", i))?,
+						}
+					},
+					(None, None) => (),
+					_ => Err(err!("port {} is active in only some games", i))?,
+				}
+			}
+
+			game.end = next.end.clone();
+		}
+
+		if !rest.is_empty() && game.metadata.json.contains_key("lastFrame") {
+			let last_frame = game.frame_indexes().end - 1;
+			game.metadata.json.insert("lastFrame".to_string(), ubjson::Object::Int(i64::from(last_frame)));
+			game.metadata = metadata::parse(&game.metadata.json);
+		}
+
+		Ok(game)
+	}
+
+	/// Index of the first frame of data. This is `FIRST_FRAME_INDEX` except for clips
+	/// cut from the middle of a game (see `slices_where`).
+	pub fn first_frame_index(&self) -> i32 {
+		self.ports.iter().flatten()
+			.filter_map(|p| p.leader.pre.first())
+			.map(|f| f.index)
+			.min()
+			.unwrap_or(FIRST_FRAME_INDEX)
+	}
+
+	/// Frame indexes for which there's data: from `first_frame_index` through the
+	/// longest port's last frame.
+	pub fn frame_indexes(&self) -> std::ops::Range<i32> {
+		let first = self.first_frame_index();
+		let len = self.ports.iter().flatten().map(|p| p.leader.pre.len()).max().unwrap_or(0);
+		first .. first + len as i32
+	}
+
+	/// Every stock lost by the leader on `port`, in order. Only drops between consecutive
+	/// frames count, so the initial spawn (whose stock count has no predecessor) doesn't.
+	pub fn deaths(&self, port:u8) -> Vec<Death> {
+		let post = match self.ports.get(port as usize) {
+			Some(Some(p)) => &p.leader.post,
+			_ => return vec![],
+		};
+		post.windows(2)
+			.filter(|w| w[1].stocks < w[0].stocks)
+			.map(|w| Death {
+				frame: w[1].index,
+				killed_by: w[0].last_hit_by_port().filter(|&k| k != port),
+				stock: self.stock_number(port, w[0].stocks),
+			})
+			.collect()
+	}
+
+	/// Which of `port`'s stocks (1 for the first) they're on with `stocks` remaining,
+	/// counted from `Player::starting_stocks`.
+	pub(crate) fn stock_number(&self, port:u8, stocks:u8) -> u8 {
+		let starting = self.start.players.get(port as usize)
+			.and_then(|p| p.as_ref())
+			.map_or(stocks, |p| p.starting_stocks());
+		starting.saturating_sub(stocks) + 1
+	}
+
+	/// All ports' data for frame `index` (an in-game frame index, so -123 is the first),
+	/// looked up in constant time. Its accessors return `None` if `index` is out of range.
+	pub fn frame(&self, index:i32) -> FrameRef<'_> {
+		FrameRef { index, game: self }
+	}
+
+	/// Pre-frame data for the leader on `port` at frame `index`. See `frame`.
+	pub fn pre(&self, port:u8, index:i32) -> Option<&frame::Pre> {
+		self.frame(index).pre(port)
+	}
+
+	/// Post-frame data for the leader on `port` at frame `index`. See `frame`.
+	pub fn post(&self, port:u8, index:i32) -> Option<&frame::Post> {
+		self.frame(index).post(port)
+	}
+
+	/// Every frame whose data differs between `self` and `other`, by port (leader, then
+	/// follower) and then index. A frame present in only one of the games is a difference.
+	/// Unless `include_seed`, Pre-frame data is compared with `frame::Pre::eq_ignoring_seed`,
+	/// since the random seed can differ between re-encodings of the same game.
+	pub fn diff_frames(&self, other:&Game, include_seed:bool) -> Vec<FrameDiff> {
+		let empty = Frames { pre: vec![], post: vec![] };
+		let mut diffs = Vec::new();
+		for (port, (a, b)) in (0u8 ..).zip(self.ports.iter().zip(other.ports.iter())) {
+			let leaders = (a.as_ref().map_or(&empty, |p| &p.leader), b.as_ref().map_or(&empty, |p| &p.leader));
+			diff_frames(leaders.0, leaders.1, port, false, include_seed, &mut diffs);
+			let followers = (
+				a.as_ref().and_then(|p| p.follower.as_ref()).unwrap_or(&empty),
+				b.as_ref().and_then(|p| p.follower.as_ref()).unwrap_or(&empty),
+			);
+			diff_frames(followers.0, followers.1, port, true, include_seed, &mut diffs);
+		}
+		diffs
+	}
+
+	/// Indexes of all frames for which `pred` holds, in order.
+	pub fn find_frames<F:Fn(&FrameRef) -> bool>(&self, pred:F) -> Vec<i32> {
+		self.frame_indexes()
+			.filter(|&index| pred(&self.frame(index)))
+			.collect()
+	}
+
+	/// Copy of this game containing only the frames in `range`. `start`, `end`, and
+	/// `metadata` are kept as-is.
+	pub fn slice(&self, range:std::ops::Range<i32>) -> Game {
+		let mut game = self.clone();
+		for port in game.ports.iter_mut().flatten() {
+			port.leader = port.leader.slice(&range);
+			port.follower = port.follower.as_ref().map(|f| f.slice(&range));
+		}
+		game
+	}
+
+	/// One `slice` per maximal run of consecutive frames matching `pred`, extended by
+	/// up to `before` frames before and `after` frames after (clamped to the game).
+	/// Runs whose padded ranges overlap are still returned as separate clips.
+	pub fn slices_where<F:Fn(&FrameRef) -> bool>(&self, pred:F, before:u32, after:u32) -> Vec<Game> {
+		let all = self.frame_indexes();
+		let mut runs:Vec<std::ops::Range<i32>> = Vec::new();
+		for index in self.find_frames(pred) {
+			match runs.last_mut() {
+				Some(run) if run.end == index => run.end += 1,
+				_ => runs.push(index .. index + 1),
+			}
+		}
+		let before = i32::try_from(before).unwrap_or(i32::MAX);
+		let after = i32::try_from(after).unwrap_or(i32::MAX);
+		runs.into_iter()
+			.map(|run| self.slice(run.start.saturating_sub(before).max(all.start) .. run.end.saturating_add(after).min(all.end)))
+			.collect()
+	}
+
+	/// Human-readable summary of every active port's state on frame `index`, for debugging.
+	/// Ports without data for that frame are omitted.
+	pub fn dump_frame(&self, index:i32) -> String {
+		let mut s = format!("frame {}\n", index);
+		for (port, p) in self.ports.iter().enumerate() {
+			if let Some(p) = p {
+				// writing to a `String` can't fail
+				dump_frames(&mut s, port, "leader", &p.leader, index).unwrap();
+				if let Some(follower) = &p.follower {
+					dump_frames(&mut s, port, "follower", follower, index).unwrap();
+				}
+			}
+		}
+		s
+	}
+}
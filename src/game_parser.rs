@@ -1,15 +1,53 @@
+use std::convert::TryFrom;
 use std::io::Result;
-use std::collections::HashMap;
+
+use log::{warn};
 
 use super::{frame, game, metadata, parse, ubjson};
-use super::frame::Indexed;
+use super::frame::{Indexed};
+use super::parse::{RollbackMode};
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct GameParser {
 	pub start: Option<game::Start>,
 	pub end: Option<game::End>,
 	pub ports: [Option<game::Port>; game::NUM_PORTS],
-	pub metadata: Option<HashMap<String, ubjson::Object>>,
+	pub metadata: Option<ubjson::Map>,
+	/// Index of the first frame event seen, which all frame arrays are relative to.
+	/// Normally `game::FIRST_FRAME_INDEX`, but later for trimmed replays.
+	first_index: Option<i32>,
+	pub rollback: RollbackMode,
+	/// Latest `frame::Bookend::latest_finalized_frame` seen.
+	finalized: Option<i32>,
+}
+
+fn relative_index(first_index:&mut Option<i32>, index:i32) -> Result<usize> {
+	let first = *first_index.get_or_insert(index);
+	usize::try_from(index - first).map_err(|_| err!("frame {} precedes first frame {}", index, first))
+}
+
+/// Adds `frame` (at relative index `idx`) to `frames`, replacing any earlier copy.
+fn add_frame<F:Copy + Indexed>(frames:&mut Vec<F>, idx:usize, frame:F, rollback:RollbackMode, finalized:Option<i32>) -> Result<()> {
+	if rollback == RollbackMode::KeepAll {
+		frames.push(frame);
+	} else if idx == frames.len() {
+		frames.push(frame)
+	} else if idx < frames.len() { // rollback
+		if finalized.is_some_and(|f| frame.index() <= f) {
+			warn!("ignoring rollback of finalized frame: {}", frame.index());
+		} else {
+			frames[idx] = frame
+		}
+	} else {
+		if let Some(&last) = frames.last() {
+			while frames.len() < idx {
+				frames.push(last);
+			}
+		} else {
+			Err(err!("missing frames: {:?} -> {:?}", frames.last().map(|f| f.index()), frame.index()))?
+		}
+	}
+	Ok(())
 }
 
 impl GameParser {
@@ -21,6 +59,17 @@ impl GameParser {
 			metadata: metadata::parse(&self.metadata.unwrap_or_default()),
 		})
 	}
+
+	/// Like `into_game`, but for a replay that was cut short (see `parse::parse_partial`):
+	/// a missing Game End is replaced by one with `EndMethod::UNRESOLVED` and no LRAS.
+	pub fn into_partial_game(mut self) -> Result<game::Game> {
+		self.end.get_or_insert(game::End {
+			method: game::EndMethod::UNRESOLVED,
+			#[cfg(v2_0)] v2_0: game::EndV2_0 { lras_initiator: -1 },
+			#[cfg(not(v2_0))] v2_0: None,
+		});
+		self.into_game()
+	}
 }
 
 impl parse::Handlers for GameParser {
@@ -36,6 +85,7 @@ impl parse::Handlers for GameParser {
 
 	fn frame_pre(&mut self, e:parse::FrameEvent<frame::Pre>) -> Result<()> {
 		let id = e.id;
+		let idx = relative_index(&mut self.first_index, e.event.index)?;
 
 		if self.ports[id.port as usize].is_none() {
 			self.ports[id.port as usize] = Some(game::Port {
@@ -55,26 +105,13 @@ impl parse::Handlers for GameParser {
 			&mut port.leader.pre
 		};
 
-		let idx = e.event.array_index();
-		if idx == frames.len() {
-			frames.push(e.event)
-		} else if idx < frames.len() { // rollback
-			frames[idx] = e.event
-		} else {
-			if let Some(&last) = frames.last() {
-				while frames.len() < idx {
-					frames.push(last);
-				}
-			} else {
-				Err(err!("missing frames: {:?} -> {:?}", frames.last().map(|f| f.index), e.event.index))?
-			}
-		}
-
+		add_frame(frames, idx, e.event, self.rollback, self.finalized)?;
 		Ok(())
 	}
 
 	fn frame_post(&mut self, e:parse::FrameEvent<frame::Post>) -> Result<()> {
 		let id = e.id;
+		let idx = relative_index(&mut self.first_index, e.event.index)?;
 
 		if self.ports[id.port as usize].is_none() {
 			self.ports[id.port as usize] = Some(
@@ -96,25 +133,18 @@ impl parse::Handlers for GameParser {
 			&mut port.leader.post
 		};
 
-		let idx = e.event.array_index();
-		if idx == frames.len() {
-			frames.push(e.event)
-		} else if idx < frames.len() { // rollback
-			frames[idx] = e.event
-		} else {
-			if let Some(&last) = frames.last() {
-				while frames.len() < idx {
-					frames.push(last);
-				}
-			} else {
-				Err(err!("missing frames: {:?} -> {:?}", frames.last().map(|f| f.index), e.event.index))?
-			}
-		}
+		add_frame(frames, idx, e.event, self.rollback, self.finalized)?;
+		Ok(())
+	}
 
+	fn frame_bookend(&mut self, b:parse::FrameEvent<frame::Bookend, i32>) -> Result<()> {
+		if let Some(f) = b.event.latest_finalized_frame {
+			self.finalized = Some(self.finalized.map_or(f, |prev| prev.max(f)));
+		}
 		Ok(())
 	}
 
-	fn metadata(&mut self, metadata:HashMap<String, ubjson::Object>) -> Result<()> {
+	fn metadata(&mut self, metadata:ubjson::Map) -> Result<()> {
 		self.metadata = Some(metadata);
 		Ok(())
 	}
@@ -0,0 +1,139 @@
+use std::io::{self, Write};
+use std::path;
+
+use clap::{App, Arg};
+
+use crossterm::{cursor, event, execute, queue, terminal};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+
+use log::{error};
+
+use peppi::game::{FIRST_FRAME_INDEX, Game};
+
+const HELP:&str = "←/→ step, PgUp/PgDn ±1 s, Home/End first/last, g go to frame, q quit";
+
+/// Frames skipped by PgUp/PgDn: one second.
+const PAGE:i32 = 60;
+
+/// Raw mode on the alternate screen, for as long as this lives. The terminal is
+/// restored on drop, including when `run` returns early with an error.
+struct Screen;
+
+impl Screen {
+	fn new() -> io::Result<Screen> {
+		terminal::enable_raw_mode()?;
+		if let Err(e) = execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide) {
+			let _ = terminal::disable_raw_mode();
+			return Err(e);
+		}
+		Ok(Screen)
+	}
+}
+
+impl Drop for Screen {
+	fn drop(&mut self) {
+		let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+		let _ = terminal::disable_raw_mode();
+	}
+}
+
+/// Redraws the whole screen: `dump_frame` for `index`, then a status line and either
+/// the help text or the go-to-frame prompt.
+fn draw<W:Write>(out:&mut W, game:&Game, index:i32, (first, last):(i32, i32), input:Option<&str>, message:&str) -> io::Result<()> {
+	queue!(out, terminal::Clear(terminal::ClearType::All), cursor::MoveTo(0, 0))?;
+	// raw mode doesn't translate "\n", so lines need an explicit carriage return
+	for line in game.dump_frame(index).lines() {
+		write!(out, "{}\r\n", line)?;
+	}
+	write!(out, "\r\nframe {} ({} to {}) {}\r\n", index, first, last, message)?;
+	match input {
+		Some(input) => write!(out, "go to frame: {}", input)?,
+		None => write!(out, "{}", HELP)?,
+	}
+	out.flush()
+}
+
+fn run(path:&str, start:i32) -> Result<(), String> {
+	let game = peppi::game(path::Path::new(path)).map_err(|e| format!("{:?}", e))?;
+	let (first, last) = (game.frame_indexes().start, game.frame_indexes().end - 1);
+	let mut index = start.max(first).min(last);
+	// the go-to-frame prompt's contents, while it's open
+	let mut input:Option<String> = None;
+	let mut message = String::new();
+
+	let _screen = Screen::new().map_err(|e| format!("{:?}", e))?;
+	let mut out = io::stdout();
+	loop {
+		draw(&mut out, &game, index, (first, last), input.as_deref(), &message).map_err(|e| format!("{:?}", e))?;
+
+		let key = match event::read().map_err(|e| format!("{:?}", e))? {
+			Event::Key(key) if key.kind != KeyEventKind::Release => key,
+			_ => continue,
+		};
+		if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+			return Ok(());
+		}
+		message.clear();
+
+		match input.as_mut() {
+			Some(s) => match key.code {
+				KeyCode::Char(c) if c.is_ascii_digit() || (c == '-' && s.is_empty()) => s.push(c),
+				KeyCode::Backspace => { s.pop(); },
+				KeyCode::Enter => {
+					match s.parse::<i32>() {
+						Ok(i) if (first ..= last).contains(&i) => index = i,
+						_ => message = format!("(frame out of range: {})", s),
+					}
+					input = None;
+				},
+				KeyCode::Esc => input = None,
+				_ => (),
+			},
+			None => match key.code {
+				KeyCode::Right | KeyCode::Down | KeyCode::Enter | KeyCode::Char('n') => index = (index + 1).min(last),
+				KeyCode::Left | KeyCode::Up | KeyCode::Char('p') => index = (index - 1).max(first),
+				KeyCode::PageDown => index = (index + PAGE).min(last),
+				KeyCode::PageUp => index = (index - PAGE).max(first),
+				KeyCode::Home => index = first,
+				KeyCode::End => index = last,
+				KeyCode::Char('g') => input = Some(String::new()),
+				// typing a frame number opens the prompt too
+				KeyCode::Char(c) if c.is_ascii_digit() || c == '-' => input = Some(c.to_string()),
+				KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+				_ => (),
+			},
+		}
+	}
+}
+
+fn main() {
+	pretty_env_logger::init();
+
+	let matches = App::new("Peppi inspector")
+		.version("0.1")
+		.about("Step through a Slippi SSBM replay frame by frame")
+		.arg(Arg::with_name("FRAME")
+			.help("Frame to start on")
+			.short("s")
+			.long("start")
+			.takes_value(true))
+		.arg(Arg::with_name("FILE")
+			.help("Replay file to inspect")
+			.required(true)
+			.index(1))
+		.get_matches();
+
+	let path = matches.value_of("FILE").unwrap();
+	let start = match matches.value_of("FRAME").map(|s| s.parse::<i32>()) {
+		Some(Ok(start)) => start,
+		Some(Err(e)) => {
+			error!("invalid start frame: {:?}", e);
+			return;
+		},
+		None => FIRST_FRAME_INDEX,
+	};
+
+	if let Err(e) = run(path, start) {
+		error!("{}", e);
+	}
+}
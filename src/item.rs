@@ -0,0 +1,37 @@
+pseudo_enum!(Item:u16 {
+	0 => CAPSULE,
+	1 => BOX,
+	2 => BARREL,
+	3 => EGG,
+	4 => PARTY_BALL,
+	5 => BARREL_CANNON,
+	6 => BOB_OMB,
+	7 => MR_SATURN,
+	8 => HEART_CONTAINER,
+	9 => MAXIM_TOMATO,
+	10 => STARMAN,
+	11 => HOME_RUN_BAT,
+	12 => BEAM_SWORD,
+	13 => PARASOL,
+	14 => GREEN_SHELL,
+	15 => RED_SHELL,
+	16 => RAY_GUN,
+	17 => FREEZIE,
+	18 => FOOD,
+	19 => MOTION_SENSOR_BOMB,
+	20 => FLIPPER,
+	21 => SUPER_SCOPE,
+	22 => STAR_ROD,
+	23 => LIPS_STICK,
+	24 => FAN,
+	25 => FIRE_FLOWER,
+	26 => SUPER_MUSHROOM,
+	27 => POISON_MUSHROOM,
+	28 => HAMMER,
+	29 => WARP_STAR,
+	30 => SCREW_ATTACK,
+	31 => BUNNY_HOOD,
+	32 => METAL_BOX,
+	33 => CLOAKING_DEVICE,
+	34 => POKE_BALL,
+});
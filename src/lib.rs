@@ -5,10 +5,26 @@ macro_rules! err {
 	}
 }
 
+/// What the exporters (JSON via `game::Game::export`, `query`, and `summary::csv_row_opts`)
+/// include in their output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExportOptions {
+	/// Whether to output follower (Nana) frames along with leader frames. When false,
+	/// followers are left out entirely, as if the port had none.
+	pub include_followers: bool,
+}
+
+impl Default for ExportOptions {
+	fn default() -> Self {
+		ExportOptions { include_followers: true }
+	}
+}
+
 #[derive(Clone, Debug)]
 pub struct Config {
 	pub json: bool,
 	pub frames: bool,
+	pub export: ExportOptions,
 	pub enum_names: bool,
 	pub query: Option<Vec<String>>,
 }
@@ -17,35 +33,54 @@ pub struct Config {
 pub static mut CONFIG:Config = Config {
 	json: false,
 	frames: false,
+	export: ExportOptions { include_followers: true },
 	enum_names: false,
 	query: None,
 };
 
 #[macro_use] pub mod pseudo_bitmask;
 #[macro_use] pub mod pseudo_enum;
+#[cfg(feature = "serde")]
 #[macro_use] pub mod query;
 
+/// Without serde there's no query support, so `query_impl!` expands to nothing.
+#[cfg(not(feature = "serde"))]
+macro_rules! query_impl {
+	($($t:tt)*) => {};
+}
+
 pub mod action_state;
 pub mod attack;
 pub mod buttons;
 pub mod character;
+pub mod event_counter;
 pub mod frame;
+pub mod frame_joiner;
 pub mod game;
 pub mod game_parser;
+pub mod item;
 pub mod metadata;
 pub mod parse;
 pub mod stage;
+pub mod stats;
+pub mod streaming_writer;
+pub mod summary;
+#[cfg(feature = "tar")]
+pub mod tar;
 pub mod triggers;
+pub mod trim;
 pub mod ubjson;
 
 #[cfg(test)] mod test;
 
-use std::{error, fmt, fs, io, path};
+use std::{error, fmt, fs, io, panic, path};
+#[cfg(feature = "rayon")]
+use std::{collections::BTreeMap, sync::mpsc};
 
 #[derive(Debug)]
 pub struct ParseError {
 	pub pos: Option<u64>,
-	pub error: io::Error,
+	pub error: parse::Error,
 }
 
 impl fmt::Display for ParseError {
@@ -65,8 +100,13 @@ impl error::Error for ParseError {
 }
 
 /// Parses a Slippi replay from `r`, passing events to the callbacks in `handlers` as they occur.
-pub fn parse<R:io::Read + io::Seek, H:parse::Handlers>(mut r:R, handlers:&mut H) -> std::result::Result<(), ParseError> {
-	parse::parse(r.by_ref(), handlers)
+pub fn parse<R:io::Read + io::Seek, H:parse::Handlers>(r:R, handlers:&mut H) -> std::result::Result<(), ParseError> {
+	parse_opts(r, handlers, &mut parse::ParseOptions::default())
+}
+
+/// Like `parse`, but with non-default `opts`.
+pub fn parse_opts<R:io::Read + io::Seek, H:parse::Handlers>(mut r:R, handlers:&mut H, opts:&mut parse::ParseOptions) -> std::result::Result<(), ParseError> {
+	parse::parse_opts(r.by_ref(), handlers, opts)
 		// Wrap with the approximate file position where the error occurred.
 		// This is why we require `R:Seek`.
 		.map_err(|e| ParseError { pos: r.seek(io::SeekFrom::Current(0)).ok(), error: e})?;
@@ -74,17 +114,361 @@ pub fn parse<R:io::Read + io::Seek, H:parse::Handlers>(mut r:R, handlers:&mut H)
 }
 
 /// Parses the Slippi replay file at `path`, returning a `game::Game` object.
+///
+/// Compressed replays are recognized by their contents rather than their names, and
+/// decompressed in memory before parsing: gzip (`.slp.gz`) with the `gzip` feature,
+/// and zstd (`.slp.zst`) with the `zstd` feature. Without the matching feature, they
+/// fail with an error naming it. Plain replays are read as before.
 pub fn game(path:&path::Path) -> std::result::Result<game::Game, ParseError> {
-	let f = fs::File::open(path).map_err(|e| ParseError { pos: None, error: e })?;
+	game_opts(path, &mut parse::ParseOptions::default())
+}
+
+/// Like `game`, but with non-default `opts`.
+pub fn game_opts(path:&path::Path, opts:&mut parse::ParseOptions) -> std::result::Result<game::Game, ParseError> {
+	let f = fs::File::open(path).map_err(|e| ParseError { pos: None, error: e.into() })?;
 	let mut r = io::BufReader::new(f);
+	match decompress(&mut r).map_err(|e| ParseError { pos: None, error: e.into() })? {
+		Some(data) => game_reader(io::Cursor::new(data), opts),
+		None => game_reader(r, opts),
+	}
+}
 
-	let mut game_parser = game_parser::GameParser {
-		start: None,
-		end: None,
-		ports: [None, None, None, None],
-		metadata: None,
+const GZIP_MAGIC:[u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC:[u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// If `r` starts with gzip or zstd magic, decompresses all of it (see `game` for the
+/// features involved). Returns `None`, without consuming anything, for anything else.
+fn decompress<R:io::BufRead>(r:&mut R) -> io::Result<Option<Vec<u8>>> {
+	let magic = r.fill_buf()?;
+	if magic.starts_with(&GZIP_MAGIC) {
+		gunzip(r).map(Some)
+	} else if magic.starts_with(&ZSTD_MAGIC) {
+		unzstd(r).map(Some)
+	} else {
+		Ok(None)
+	}
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip<R:io::BufRead>(r:R) -> io::Result<Vec<u8>> {
+	let mut data = Vec::new();
+	io::Read::read_to_end(&mut flate2::bufread::MultiGzDecoder::new(r), &mut data)?;
+	Ok(data)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip<R:io::BufRead>(_:R) -> io::Result<Vec<u8>> {
+	Err(err!("can't read a gzip-compressed replay without the `gzip` feature"))
+}
+
+#[cfg(feature = "zstd")]
+fn unzstd<R:io::BufRead>(r:R) -> io::Result<Vec<u8>> {
+	let mut data = Vec::new();
+	io::Read::read_to_end(&mut zstd::stream::read::Decoder::with_buffer(r)?, &mut data)?;
+	Ok(data)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn unzstd<R:io::BufRead>(_:R) -> io::Result<Vec<u8>> {
+	Err(err!("can't read a zstd-compressed replay without the `zstd` feature"))
+}
+
+/// Like `game_opts`, but for a replay that may have been cut short (e.g. by a crash). See
+/// `parse::parse_partial` for what's salvageable. Also returns whether the replay was
+/// truncated, in which case `end` is `game_parser::GameParser::into_partial_game`'s
+/// placeholder (unless Game End was read before the cut).
+pub fn game_partial(path:&path::Path, opts:&mut parse::ParseOptions) -> std::result::Result<(game::Game, bool), ParseError> {
+	let f = fs::File::open(path).map_err(|e| ParseError { pos: None, error: e.into() })?;
+	let mut r = io::BufReader::new(f);
+	let mut game_parser = game_parser::GameParser::default();
+	game_parser.rollback = opts.rollback;
+
+	let truncated = parse::parse_partial_opts(&mut r, &mut game_parser, opts)
+		.map_err(|e| ParseError { pos: io::Seek::stream_position(&mut r).ok(), error: e })?;
+	let game = game_parser.into_partial_game().map_err(|e| ParseError { pos: None, error: e.into() })?;
+	Ok((game, truncated))
+}
+
+fn game_reader<R:io::Read + io::Seek>(mut r:R, opts:&mut parse::ParseOptions) -> std::result::Result<game::Game, ParseError> {
+	let mut game_parser = game_parser::GameParser::default();
+	game_parser.rollback = opts.rollback;
+
+	parse_opts(&mut r, &mut game_parser, opts)
+		.and_then(|_| game_parser.into_game().map_err(|e| ParseError { pos: None, error: e.into() }))
+}
+
+#[cfg(feature = "tar")]
+fn tar_reader(path:&path::Path) -> std::result::Result<tar::Reader<io::BufReader<fs::File>>, ParseError> {
+	let f = fs::File::open(path).map_err(|e| ParseError { pos: None, error: e.into() })?;
+	Ok(tar::Reader::new(io::BufReader::new(f)))
+}
+
+/// Parses the replay named `member` inside the tar archive at `path`, without
+/// extracting it to disk. Stops at the first member with that name.
+#[cfg(feature = "tar")]
+pub fn read_tar(path:&path::Path, member:&str) -> std::result::Result<game::Game, ParseError> {
+	let mut archive = tar_reader(path)?;
+	loop {
+		match archive.next_member().map_err(|e| ParseError { pos: None, error: e.into() })? {
+			Some((name, _)) if name == member => {
+				let data = archive.read_member().map_err(|e| ParseError { pos: None, error: e.into() })?;
+				return game_reader(io::Cursor::new(data), &mut parse::ParseOptions::default());
+			},
+			Some(_) => (),
+			None => return Err(ParseError { pos: None, error: err!("no such archive member: {}", member).into() }),
+		}
+	}
+}
+
+/// An archive member's name, and the result of parsing it.
+#[cfg(any(feature = "tar", feature = "zip"))]
+pub type ArchiveGame = (String, std::result::Result<game::Game, ParseError>);
+
+/// Parses every `.slp` member of the tar archive at `path`, in archive order.
+/// Each member gets its own result, so one bad replay doesn't fail the rest.
+#[cfg(feature = "tar")]
+pub fn read_tar_all(path:&path::Path) -> std::result::Result<Vec<ArchiveGame>, ParseError> {
+	let mut archive = tar_reader(path)?;
+	let mut games = Vec::new();
+	while let Some((name, _)) = archive.next_member().map_err(|e| ParseError { pos: None, error: e.into() })? {
+		if name.ends_with(".slp") {
+			let data = archive.read_member().map_err(|e| ParseError { pos: None, error: e.into() })?;
+			let game = game_reader(io::Cursor::new(data), &mut parse::ParseOptions::default());
+			games.push((name, game));
+		}
+	}
+	Ok(games)
+}
+
+#[cfg(feature = "zip")]
+fn zip_archive(path:&path::Path) -> std::result::Result<zip::ZipArchive<io::BufReader<fs::File>>, ParseError> {
+	let f = fs::File::open(path).map_err(|e| ParseError { pos: None, error: e.into() })?;
+	zip::ZipArchive::new(io::BufReader::new(f)).map_err(|e| ParseError { pos: None, error: io::Error::from(e).into() })
+}
+
+/// Parses the replay named `member` inside the zip archive at `path`, without
+/// extracting it to disk.
+#[cfg(feature = "zip")]
+pub fn read_zip(path:&path::Path, member:&str) -> std::result::Result<game::Game, ParseError> {
+	use io::Read;
+	let mut archive = zip_archive(path)?;
+	let mut file = match archive.by_name(member) {
+		Ok(file) => file,
+		Err(zip::result::ZipError::FileNotFound) =>
+			return Err(ParseError { pos: None, error: err!("no such archive member: {}", member).into() }),
+		Err(e) => return Err(ParseError { pos: None, error: io::Error::from(e).into() }),
 	};
+	let mut data = Vec::new();
+	file.read_to_end(&mut data).map_err(|e| ParseError { pos: None, error: e.into() })?;
+	game_reader(io::Cursor::new(data), &mut parse::ParseOptions::default())
+}
+
+/// Parses every `.slp` member of the zip archive at `path`, in archive order.
+/// Each member gets its own result, so one bad replay doesn't fail the rest.
+#[cfg(feature = "zip")]
+pub fn read_zip_all(path:&path::Path) -> std::result::Result<Vec<ArchiveGame>, ParseError> {
+	use io::Read;
+	let mut archive = zip_archive(path)?;
+	let mut games = Vec::new();
+	for i in 0 .. archive.len() {
+		let mut file = archive.by_index(i).map_err(|e| ParseError { pos: None, error: io::Error::from(e).into() })?;
+		if file.is_file() && file.name().ends_with(".slp") {
+			let name = file.name().to_string();
+			let mut data = Vec::new();
+			let game = match file.read_to_end(&mut data) {
+				Ok(_) => game_reader(io::Cursor::new(data), &mut parse::ParseOptions::default()),
+				Err(e) => Err(ParseError { pos: None, error: e.into() }),
+			};
+			games.push((name, game));
+		}
+	}
+	Ok(games)
+}
+
+/// A replay's path, and the result of parsing it.
+pub type DirGame = (path::PathBuf, std::result::Result<game::Game, ParseError>);
+
+/// Results from `read_dir`'s rayon tasks, tagged with their position in path order.
+#[cfg(feature = "rayon")]
+struct DirGames {
+	rx: mpsc::Receiver<(usize, DirGame)>,
+	ordered: bool,
+	/// Finished results waiting for earlier ones, when `ordered`.
+	pending: BTreeMap<usize, DirGame>,
+	next: usize,
+}
+
+#[cfg(feature = "rayon")]
+impl Iterator for DirGames {
+	type Item = DirGame;
+
+	fn next(&mut self) -> Option<DirGame> {
+		if !self.ordered {
+			return self.rx.recv().ok().map(|(_, g)| g);
+		}
+		loop {
+			if let Some(g) = self.pending.remove(&self.next) {
+				self.next += 1;
+				return Some(g);
+			}
+			match self.rx.recv() {
+				Ok((i, g)) => { self.pending.insert(i, g); },
+				// every task is done, so nothing can fill the gap: yield what's left, in order
+				Err(_) => return self.pending.pop_first().map(|(i, g)| {
+					self.next = i + 1;
+					g
+				}),
+			}
+		}
+	}
+}
+
+/// Whether `path` is named like a replay: `.slp`, or `.slp.gz` or `.slp.zst` for
+/// compressed ones (see `game`).
+fn is_replay_path(path:&path::Path) -> bool {
+	path.file_name().map(|n| n.to_string_lossy())
+		.is_some_and(|n| [".slp", ".slp.gz", ".slp.zst"].iter().any(|ext| n.ends_with(ext)))
+}
+
+/// `game_opts` for one of `read_dir`'s files. A panic (e.g. from a malformed replay)
+/// becomes an error for this file alone.
+fn dir_game(path:path::PathBuf, opts:&mut parse::ParseOptions) -> DirGame {
+	let game = panic::catch_unwind(panic::AssertUnwindSafe(|| game_opts(&path, opts)))
+		.unwrap_or_else(|p| {
+			let msg = p.downcast_ref::<&str>().copied()
+				.or_else(|| p.downcast_ref::<String>().map(String::as_str))
+				.unwrap_or("unknown panic");
+			Err(ParseError { pos: None, error: err!("panicked while parsing: {}", msg).into() })
+		});
+	(path, game)
+}
+
+/// Parses every replay (see `is_replay_path`) in the directory `dir` (not recursively).
+/// Each file gets its own result, so one bad replay doesn't fail the rest (not even by
+/// panicking). `opts.progress` is ignored.
+///
+/// With the `rayon` feature, files are parsed in parallel on rayon's global thread
+/// pool, and results are yielded as they finish, or sorted by path if `ordered`.
+/// Without it, files are parsed one at a time as the iterator advances, always in
+/// path order.
+pub fn read_dir(dir:&path::Path, opts:&parse::ParseOptions, ordered:bool) -> std::result::Result<impl Iterator<Item = DirGame>, ParseError> {
+	let mut paths:Vec<path::PathBuf> = fs::read_dir(dir)
+		.map_err(|e| ParseError { pos: None, error: e.into() })?
+		.filter_map(|e| e.ok().map(|e| e.path()))
+		.filter(|p| is_replay_path(p))
+		.collect();
+	paths.sort();
+
+	// `ParseOptions` can't be sent to other threads (because of `progress`), but its other fields can
+	let (lenient_end, rollback, skip_frames) = (opts.lenient_end, opts.rollback, opts.skip_frames);
+	let opts = move || parse::ParseOptions { lenient_end, progress: None, rollback, skip_frames };
+
+	#[cfg(feature = "rayon")]
+	{
+		use rayon::prelude::*;
+		let (tx, rx) = mpsc::channel();
+		rayon::spawn(move || {
+			// stops early (with an error) if the iterator was dropped
+			let _ = paths.into_par_iter().enumerate()
+				.try_for_each_with(tx, |tx, (i, path)| tx.send((i, dir_game(path, &mut opts()))).map_err(|_| ()));
+		});
+		Ok(DirGames { rx, ordered, pending: BTreeMap::new(), next: 0 })
+	}
+
+	#[cfg(not(feature = "rayon"))]
+	{
+		let _ = ordered; // always in order
+		Ok(paths.into_iter().map(move |path| dir_game(path, &mut opts())))
+	}
+}
+
+/// Reads only the metadata of the Slippi replay in `r`, seeking past the frame data.
+pub fn metadata<R:io::Read + io::Seek>(mut r:R) -> std::result::Result<metadata::Metadata, ParseError> {
+	let json = parse::metadata(r.by_ref())
+		.map_err(|e| ParseError { pos: r.stream_position().ok(), error: e })?;
+	Ok(metadata::parse(&json))
+}
+
+struct MetadataCollector {
+	json: Option<ubjson::Map>,
+}
+
+impl parse::Handlers for MetadataCollector {
+	fn metadata(&mut self, json:ubjson::Map) -> io::Result<()> {
+		self.json = Some(json);
+		Ok(())
+	}
+}
+
+/// Reads the metadata of the Slippi replay in `r`, for readers that can't seek.
+/// This has to walk the entire event stream, so prefer `metadata` when possible.
+pub fn metadata_unseekable<R:io::Read>(r:R) -> std::result::Result<metadata::Metadata, ParseError> {
+	let mut collector = MetadataCollector { json: None };
+	parse::parse(r, &mut collector).map_err(|e| ParseError { pos: None, error: e })?;
+	Ok(metadata::parse(&collector.json.unwrap_or_default()))
+}
+
+#[derive(Default)]
+struct EventCollector {
+	events: Vec<parse::ParsedEvent>,
+}
+
+impl parse::Handlers for EventCollector {
+	fn unknown_event(&mut self, code:u8, payload:&[u8]) -> io::Result<()> {
+		self.events.push(parse::ParsedEvent::Unknown(code, payload.to_vec()));
+		Ok(())
+	}
+
+	fn game_start(&mut self, s:game::Start) -> io::Result<()> {
+		self.events.push(parse::ParsedEvent::GameStart(Box::new(s)));
+		Ok(())
+	}
+
+	fn game_end(&mut self, s:game::End) -> io::Result<()> {
+		self.events.push(parse::ParsedEvent::GameEnd(s));
+		Ok(())
+	}
+
+	fn frame_start(&mut self, s:parse::FrameEvent<frame::Start, i32>) -> io::Result<()> {
+		self.events.push(parse::ParsedEvent::FrameStart(s));
+		Ok(())
+	}
+
+	fn frame_pre(&mut self, f:parse::FrameEvent<frame::Pre>) -> io::Result<()> {
+		self.events.push(parse::ParsedEvent::FramePre(f));
+		Ok(())
+	}
+
+	fn frame_post(&mut self, f:parse::FrameEvent<frame::Post>) -> io::Result<()> {
+		self.events.push(parse::ParsedEvent::FramePost(f));
+		Ok(())
+	}
+
+	fn item(&mut self, i:parse::FrameEvent<frame::Item, parse::ItemId>) -> io::Result<()> {
+		self.events.push(parse::ParsedEvent::Item(i));
+		Ok(())
+	}
+
+	fn frame_bookend(&mut self, b:parse::FrameEvent<frame::Bookend, i32>) -> io::Result<()> {
+		self.events.push(parse::ParsedEvent::FrameBookend(b));
+		Ok(())
+	}
+
+	fn gecko_list(&mut self, codes:Vec<u8>) -> io::Result<()> {
+		self.events.push(parse::ParsedEvent::GeckoList(codes));
+		Ok(())
+	}
+
+	fn metadata(&mut self, json:ubjson::Map) -> io::Result<()> {
+		self.events.push(parse::ParsedEvent::Metadata(json));
+		Ok(())
+	}
+}
 
-	parse(&mut r, &mut game_parser)
-		.and_then(|_| game_parser.into_game().map_err(|e| ParseError { pos: None, error: e }))
+/// Reads every event of the Slippi replay in `r`, in stream order. Unlike `game`, nothing
+/// is merged or de-duplicated, so rollback re-sends of the same frame all appear.
+pub fn events<R:io::Read>(r:R) -> std::result::Result<Vec<parse::ParsedEvent>, ParseError> {
+	let mut collector = EventCollector::default();
+	parse::parse(r, &mut collector).map_err(|e| ParseError { pos: None, error: e })?;
+	Ok(collector.events)
 }
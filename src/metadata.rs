@@ -4,21 +4,31 @@ use std::convert::{TryFrom};
 
 use chrono::{DateTime, Utc};
 use log::{warn};
-use serde::{Serialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use super::character;
 use super::game::{NUM_PORTS, FIRST_FRAME_INDEX};
-use super::ubjson::{Object};
+use super::ubjson::{Map, Object};
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Metadata {
-	#[serde(flatten)] pub json: HashMap<String, Object>,
+	#[cfg_attr(feature = "serde", serde(flatten))] pub json: Map,
 
-	#[serde(skip)] pub date: Option<DateTime<Utc>>,
-	#[serde(skip)] pub duration: Option<u32>,
-	#[serde(skip)] pub platform: Option<String>,
-	#[serde(skip)] pub players: Option<[Option<MetadataPlayer>; NUM_PORTS]>,
-	#[serde(skip)] pub console_name: Option<String>,
+	#[cfg_attr(feature = "serde", serde(skip))] pub date: Option<DateTime<Utc>>,
+	#[cfg_attr(feature = "serde", serde(skip))] pub duration: Option<u32>,
+	#[cfg_attr(feature = "serde", serde(skip))] pub platform: Option<Platform>,
+	#[cfg_attr(feature = "serde", serde(skip))] pub players: Option<[Option<MetadataPlayer>; NUM_PORTS]>,
+	#[cfg_attr(feature = "serde", serde(skip))] pub console_name: Option<String>,
+}
+
+/// Only `json` is serialized, so the parsed fields are recomputed from it.
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Metadata {
+	fn deserialize<D:serde::de::Deserializer<'de>>(deserializer:D) -> std::result::Result<Self, D::Error> {
+		Map::deserialize(deserializer).map(|json| parse(&json))
+	}
 }
 
 query_impl!(Metadata, self, f, config, query {
@@ -32,16 +42,62 @@ query_impl!(Metadata, self, f, config, query {
 	}
 });
 
-#[derive(Debug, PartialEq, Serialize)]
+/// What the game was played on, from `playedOn`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Platform {
+	Dolphin,
+	Nintendont,
+	Network,
+	Other(String),
+}
+
+impl Platform {
+	/// The `playedOn` string this was parsed from.
+	pub fn as_str(&self) -> &str {
+		match self {
+			Platform::Dolphin => "dolphin",
+			Platform::Nintendont => "nintendont",
+			Platform::Network => "network",
+			Platform::Other(s) => s,
+		}
+	}
+}
+
+impl From<&str> for Platform {
+	fn from(s:&str) -> Self {
+		match s {
+			"dolphin" => Platform::Dolphin,
+			"nintendont" => Platform::Nintendont,
+			"network" => Platform::Network,
+			s => Platform::Other(s.to_string()),
+		}
+	}
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Platform {
+	fn serialize<S:serde::ser::Serializer>(&self, serializer:S) -> std::result::Result<S::Ok, S::Error> {
+		self.as_str().serialize(serializer)
+	}
+}
+
+query_impl!(Platform);
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct MetadataPlayer {
 	pub characters: Option<HashMap<character::Internal, u32>>,
+	/// Netplay display name, from `names.netplay`.
 	pub netplay_name: Option<String>,
+	/// Netplay connect code (e.g. "ABCD#123"), from `names.code`.
+	pub netplay_code: Option<String>,
 }
 
 query_impl!(MetadataPlayer, self, f, config, query {
 	match &*query[0] {
 		"characters" => self.characters.query(f, config, &query[1..]),
 		"netplay_name" => self.netplay_name.query(f, config, &query[1..]),
+		"netplay_code" => self.netplay_code.query(f, config, &query[1..]),
 		s => Err(err!("unknown field `buttons.{}`", s)),
 	}
 });
@@ -49,7 +105,9 @@ query_impl!(MetadataPlayer, self, f, config, query {
 query_impl!(HashMap<character::Internal, u32>);
 query_impl!(DateTime<Utc>);
 
-fn date(json:&HashMap<String, Object>) -> Option<DateTime<Utc>> {
+/// Parses the `startAt` field of the raw metadata (as in `Metadata::date`), or `None` if
+/// it's missing or malformed. Older replays omit the trailing `Z`; those times are UTC too.
+pub fn start_at(json:&Map) -> Option<DateTime<Utc>> {
 	let date_too_short = "2000-01-01T00:00:00".parse::<DateTime<Utc>>();
 	match json.get("startAt") {
 		None => None,
@@ -76,7 +134,7 @@ fn date(json:&HashMap<String, Object>) -> Option<DateTime<Utc>> {
 	}
 }
 
-fn duration(json:&HashMap<String, Object>) -> Option<u32> {
+fn duration(json:&Map) -> Option<u32> {
 	match json.get("lastFrame") {
 		None => None,
 		Some(Object::Int(last_frame)) => match u32::try_from(*last_frame - FIRST_FRAME_INDEX as i64 + 1) {
@@ -93,10 +151,10 @@ fn duration(json:&HashMap<String, Object>) -> Option<u32> {
 	}
 }
 
-fn platform(json:&HashMap<String, Object>) -> Option<String> {
+fn platform(json:&Map) -> Option<Platform> {
 	match json.get("playedOn") {
 		None => None,
-		Some(Object::Str(played_on)) => Some(played_on.clone()),
+		Some(Object::Str(played_on)) => Some(Platform::from(played_on.as_str())),
 		played_on => {
 			warn!("metadata.playedOn: expected str, but got: {:?}", played_on);
 			None
@@ -104,7 +162,7 @@ fn platform(json:&HashMap<String, Object>) -> Option<String> {
 	}
 }
 
-fn parse_characters(characters:&HashMap<String, Object>) -> Result<HashMap<character::Internal, u32>, Box<dyn Error>> {
+fn parse_characters(characters:&Map) -> Result<HashMap<character::Internal, u32>, Box<dyn Error>> {
 	characters.iter().map(|(k, v)|
 		match v {
 			Object::Int(v) => Ok((
@@ -116,7 +174,25 @@ fn parse_characters(characters:&HashMap<String, Object>) -> Result<HashMap<chara
 	).collect::<Result<HashMap<character::Internal, u32>, Box<dyn Error>>>()
 }
 
-fn metadata_player(player:&HashMap<String, Object>) -> Result<MetadataPlayer, Box<dyn Error>> {
+fn name(player:&Map, key:&str) -> Option<String> {
+	match player.get("names") {
+		None => None,
+		Some(Object::Map(names)) => match names.get(key) {
+			None => None,
+			Some(Object::Str(name)) => Some(name.clone()),
+			name => {
+				warn!("metadata.players.N.names.{}: expected str, but got: {:?}", key, name);
+				None
+			},
+		},
+		names => {
+			warn!("metadata.players.N.names: expected map, but got: {:?}", names);
+			None
+		},
+	}
+}
+
+fn metadata_player(player:&Map) -> Result<MetadataPlayer, Box<dyn Error>> {
 	Ok(MetadataPlayer {
 		characters: match player.get("characters") {
 			Some(Object::Map(characters)) => match parse_characters(&characters) {
@@ -131,25 +207,12 @@ fn metadata_player(player:&HashMap<String, Object>) -> Result<MetadataPlayer, Bo
 				None
 			},
 		},
-		netplay_name: match player.get("names") {
-			None => None,
-			Some(Object::Map(names)) => match names.get("netplay") {
-				None => None,
-				Some(Object::Str(netplay)) => Some(netplay.clone()),
-				netplay => {
-					warn!("metadata.players.N.names.netplay: expected str, but got: {:?}", netplay);
-					None
-				},
-			},
-			names => {
-				warn!("metadata.players.N.names: expected map, but got: {:?}", names);
-				None
-			},
-		},
+		netplay_name: name(player, "netplay"),
+		netplay_code: name(player, "code"),
 	})
 }
 
-fn players(json:&HashMap<String, Object>) -> Option<[Option<MetadataPlayer>; NUM_PORTS]> {
+fn players(json:&Map) -> Option<[Option<MetadataPlayer>; NUM_PORTS]> {
 	match json.get("players") {
 		None => None,
 		Some(Object::Map(players)) => {
@@ -180,7 +243,7 @@ fn players(json:&HashMap<String, Object>) -> Option<[Option<MetadataPlayer>; NUM
 	}
 }
 
-fn console_name(json:&HashMap<String, Object>) -> Option<String> {
+fn console_name(json:&Map) -> Option<String> {
 	match json.get("consoleNick") {
 		None => None,
 		Some(Object::Str(console_nick)) => Some(console_nick.clone()),
@@ -191,10 +254,10 @@ fn console_name(json:&HashMap<String, Object>) -> Option<String> {
 	}
 }
 
-pub fn parse(json:&HashMap<String, Object>) -> Metadata {
+pub fn parse(json:&Map) -> Metadata {
 	Metadata {
 		json: json.clone(),
-		date: date(json),
+		date: start_at(json),
 		duration: duration(json),
 		platform: platform(json),
 		players: players(json),
@@ -1,13 +1,14 @@
 use std::cmp::min;
 use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::io::{Read, Result};
+use std::{error, fmt, io};
+use std::io::{Read, Result, Seek, SeekFrom};
 
 use byteorder::{BigEndian, ReadBytesExt};
 use encoding_rs::SHIFT_JIS;
-use log::{debug, trace};
+use log::{debug, trace, warn};
 
-use super::{action_state, buttons, character, frame, game, stage, triggers, ubjson};
+use super::{action_state, buttons, character, frame, game, item, stage, triggers, ubjson};
 use super::action_state::{Common, State};
 use super::attack::Attack;
 use super::character::Internal;
@@ -25,6 +26,64 @@ const DEFAULT_CHAR_STATE: CharState = CharState {
 	age: 0
 };
 
+/// Why a replay couldn't be parsed.
+#[derive(Debug)]
+pub enum Error {
+	/// The input ended partway through the replay.
+	UnexpectedEof,
+	/// Fixed bytes (such as the `raw` or `metadata` headers) didn't match.
+	BadMagic { expected: Vec<u8>, got: Vec<u8> },
+	/// An event code that the Event Payloads event didn't declare a size for.
+	UnknownEvent(u8),
+	/// The Event Payloads event's own size, which must be one more than a multiple of 3.
+	InvalidPayloadSize(u8),
+	/// The `metadata` element isn't valid UBJSON.
+	Ubjson(io::Error),
+	/// Anything else, including errors returned by `Handlers` callbacks.
+	Io(io::Error),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::UnexpectedEof => write!(f, "unexpected end of input"),
+			Error::BadMagic { expected, got } => write!(f, "expected: {:?}, got: {:?}", expected, got),
+			Error::UnknownEvent(code) => write!(f, "unknown event: {:#x}", code),
+			Error::InvalidPayloadSize(size) => write!(f, "invalid payload size: {}", size),
+			Error::Ubjson(e) => write!(f, "invalid metadata: {}", e),
+			Error::Io(e) => e.fmt(f),
+		}
+	}
+}
+
+impl error::Error for Error {
+	fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+		match self {
+			Error::Ubjson(e) | Error::Io(e) => Some(e),
+			_ => None,
+		}
+	}
+}
+
+impl From<io::Error> for Error {
+	fn from(e: io::Error) -> Error {
+		match e.kind() {
+			io::ErrorKind::UnexpectedEof => Error::UnexpectedEof,
+			_ => Error::Io(e),
+		}
+	}
+}
+
+impl From<Error> for io::Error {
+	fn from(e: Error) -> io::Error {
+		match e {
+			Error::Io(e) => e,
+			Error::UnexpectedEof => io::Error::new(io::ErrorKind::UnexpectedEof, e.to_string()),
+			e => io::Error::new(io::ErrorKind::InvalidData, e),
+		}
+	}
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 struct CharState {
 	character: Internal,
@@ -32,7 +91,7 @@ struct CharState {
 	age: u32,
 }
 
-const PAYLOADS_EVENT_CODE: u8 = 0x35;
+pub(crate) const PAYLOADS_EVENT_CODE: u8 = 0x35;
 
 #[derive(Clone, Copy, Debug, PartialEq, num_enum::TryFromPrimitive)]
 #[repr(u8)]
@@ -41,6 +100,10 @@ pub enum Event {
 	FramePre = 0x37,
 	FramePost = 0x38,
 	GameEnd = 0x39,
+	FrameStart = 0x3A,
+	Item = 0x3B,
+	FrameBookend = 0x3C,
+	GeckoList = 0x3D,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -50,9 +113,17 @@ pub struct FrameId {
 	pub is_follower: bool,
 }
 
-#[derive(Debug)]
-pub struct FrameEvent<F> {
-	pub id: FrameId,
+/// Identifies an item on a particular frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ItemId {
+	pub index: i32,
+	/// See `frame::Item::id`.
+	pub id: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameEvent<F, Id = FrameId> {
+	pub id: Id,
 	pub event: F,
 }
 
@@ -62,10 +133,10 @@ pub struct FrameEvent<F> {
 /// codes to payload sizes. This map uses raw event codes as keys (as opposed
 /// to `Event` enum values) for forwards compatibility, as it allows us to
 /// skip unknown event types.
-fn payload_sizes<R: Read>(r: &mut R) -> Result<(usize, HashMap<u8, u16>)> {
+pub(crate) fn payload_sizes<R: Read>(r: &mut R) -> std::result::Result<(usize, HashMap<u8, u16>), Error> {
 	let code = r.read_u8()?;
 	if code != PAYLOADS_EVENT_CODE {
-		Err(err!("expected event payloads, but got: {}", code))?;
+		return Err(Error::BadMagic { expected: vec![PAYLOADS_EVENT_CODE], got: vec![code] });
 	}
 
 	// Size in bytes of the subsequent list of payload-size kv pairs.
@@ -73,7 +144,7 @@ fn payload_sizes<R: Read>(r: &mut R) -> Result<(usize, HashMap<u8, u16>)> {
 	// However the value includes this size byte itself, so it's off-by-one.
 	let size = r.read_u8()?;
 	if size % 3 != 1 {
-		Err(err!("invalid payload size: {}", size))?;
+		return Err(Error::InvalidPayloadSize(size));
 	}
 
 	let mut sizes = HashMap::new();
@@ -85,12 +156,20 @@ fn payload_sizes<R: Read>(r: &mut R) -> Result<(usize, HashMap<u8, u16>)> {
 	Ok((1 + size as usize, sizes)) // +1 byte for the event code
 }
 
+/// Decodes a null-terminated Shift-JIS name tag. Invalid sequences are replaced with
+/// U+FFFD, and the returned flag indicates whether any replacements were made.
+pub fn decode_name_tag(buf: &[u8]) -> (String, bool) {
+	let first_null = buf.iter().position(|&x| x == 0).unwrap_or(buf.len());
+	let (name_tag, had_errors) = SHIFT_JIS.decode_without_bom_handling(&buf[0..first_null]);
+	(name_tag.to_string(), had_errors)
+}
+
 fn player_v1_3(r: [u8; 16]) -> Result<game::PlayerV1_3> {
-	let first_null = r.iter().position(|&x| x == 0).unwrap_or(16);
-	let (name_tag, _) = SHIFT_JIS.decode_without_bom_handling(&r[0..first_null]);
-	Ok(game::PlayerV1_3 {
-		name_tag: name_tag.to_string(),
-	})
+	let (name_tag, had_errors) = decode_name_tag(&r);
+	if had_errors {
+		warn!("invalid Shift-JIS in name tag: {:?}", r);
+	}
+	Ok(game::PlayerV1_3 { name_tag })
 }
 
 fn player_v1_0(r: [u8; 8], v1_3: Option<[u8; 16]>) -> Result<game::PlayerV1_0> {
@@ -207,9 +286,9 @@ fn game_start_v1_5(r: &mut &[u8]) -> Result<game::StartV1_5> {
 fn game_start(mut r: &mut &[u8]) -> Result<Start> {
 	let slippi = game::Slippi {
 		version: game::SlippiVersion(r.read_u8()?, r.read_u8()?, r.read_u8()?),
+		unused_version_byte: r.read_u8()?,
 	};
 
-	r.read_u8()?; // unused (build number)
 	let bitfield = {
 		let mut buf = [0; 3];
 		buf[0] = r.read_u8()?; // bitfield 1
@@ -301,11 +380,11 @@ fn game_end(r: &mut &[u8]) -> Result<End> {
 	})
 }
 
-fn direction(value: f32) -> Result<Direction> {
+fn direction(value: f32) -> Direction {
 	match value {
-		v if v < 0.0 => Ok(Direction::LEFT),
-		v if v > 0.0 => Ok(Direction::RIGHT),
-		_ => Err(err!("direction == 0")),
+		v if v < 0.0 => Direction::LEFT,
+		v if v > 0.0 => Direction::RIGHT,
+		_ => Direction::NEUTRAL,
 	}
 }
 
@@ -360,7 +439,7 @@ fn frame_pre(r: &mut &[u8], last_char_states: &[CharState; NUM_PORTS]) -> Result
 		x: r.read_f32::<BigEndian>()?,
 		y: r.read_f32::<BigEndian>()?,
 	};
-	let direction = direction(r.read_f32::<BigEndian>()?)?;
+	let direction = direction(r.read_f32::<BigEndian>()?);
 	let joystick = Position {
 		x: r.read_f32::<BigEndian>()?,
 		y: r.read_f32::<BigEndian>()?,
@@ -474,13 +553,13 @@ fn frame_post_v2_0(r: &mut &[u8]) -> Result<frame::PostV2_0> {
 			flags(&buf)
 		},
 		misc_as: r.read_f32::<BigEndian>()?,
+		airborne: r.read_u8()? != 0,
 		ground: r.read_u16::<BigEndian>()?,
 		jumps: r.read_u8()?,
 		l_cancel: match r.read_u8()? {
 			0 => None,
 			l_cancel => Some(frame::LCancel(l_cancel)),
 		},
-		airborne: r.read_u8()? != 0,
 		#[cfg(v2_1)] v2_1: frame_post_v2_1(r)?,
 		#[cfg(not(v2_1))] v2_1: match r.is_empty() {
 			true => None,
@@ -514,7 +593,7 @@ fn frame_post(r: &mut &[u8], last_char_states: &mut [CharState; NUM_PORTS]) -> R
 		x: r.read_f32::<BigEndian>()?,
 		y: r.read_f32::<BigEndian>()?,
 	};
-	let direction = direction(r.read_f32::<BigEndian>()?)?;
+	let direction = direction(r.read_f32::<BigEndian>()?);
 	let damage = r.read_f32::<BigEndian>()?;
 	let shield = r.read_f32::<BigEndian>()?;
 	let last_attack_landed = {
@@ -555,81 +634,471 @@ fn frame_post(r: &mut &[u8], last_char_states: &mut [CharState; NUM_PORTS]) -> R
 	})
 }
 
+fn item_v3_6(r: &mut &[u8]) -> Result<frame::ItemV3_6> {
+	Ok(frame::ItemV3_6 {
+		owner: match r.read_i8()? {
+			owner if owner < 0 => None,
+			owner => Some(owner as u8),
+		},
+	})
+}
+
+fn item_v3_2(r: &mut &[u8]) -> Result<frame::ItemV3_2> {
+	Ok(frame::ItemV3_2 {
+		misc: {
+			let mut buf = [0; 4];
+			r.read_exact(&mut buf)?;
+			buf
+		},
+		#[cfg(v3_6)] v3_6: item_v3_6(r)?,
+		#[cfg(not(v3_6))] v3_6: match r.is_empty() {
+			true => None,
+			_ => Some(item_v3_6(r)?),
+		},
+	})
+}
+
+/// Parses an Item Update event. Any bytes past the fields we know about
+/// (from newer Slippi versions) are ignored.
+fn item(r: &mut &[u8]) -> Result<FrameEvent<frame::Item, ItemId>> {
+	let index = r.read_i32::<BigEndian>()?;
+	let r#type = item::Item(r.read_u16::<BigEndian>()?);
+	let state = r.read_u8()?;
+	let direction = Some(direction(r.read_f32::<BigEndian>()?)).filter(|&d| d != Direction::NEUTRAL);
+	let velocity = Position {
+		x: r.read_f32::<BigEndian>()?,
+		y: r.read_f32::<BigEndian>()?,
+	};
+	let position = Position {
+		x: r.read_f32::<BigEndian>()?,
+		y: r.read_f32::<BigEndian>()?,
+	};
+	let damage = r.read_u16::<BigEndian>()?;
+	let timer = r.read_f32::<BigEndian>()?;
+	let id = r.read_u32::<BigEndian>()?;
+	trace!("Item Update: {} {:?} ({})", index, r#type, id);
+
+	#[cfg(v3_2)] let v3_2 = item_v3_2(r)?;
+	#[cfg(not(v3_2))] let v3_2 = match r.is_empty() {
+		true => None,
+		_ => Some(item_v3_2(r)?),
+	};
+
+	Ok(FrameEvent {
+		id: ItemId { index, id },
+		event: frame::Item {
+			index,
+			r#type,
+			state,
+			direction,
+			velocity,
+			position,
+			damage,
+			timer,
+			id,
+			v3_2,
+		},
+	})
+}
+
+fn frame_start(r: &mut &[u8]) -> Result<FrameEvent<frame::Start, i32>> {
+	let index = r.read_i32::<BigEndian>()?;
+	trace!("Frame Start: {}", index);
+	let random_seed = r.read_u32::<BigEndian>()?;
+	let scene_frame_counter = match r.is_empty() {
+		true => None,
+		_ => Some(r.read_u32::<BigEndian>()?),
+	};
+	Ok(FrameEvent {
+		id: index,
+		event: frame::Start { index, random_seed, scene_frame_counter },
+	})
+}
+
+fn frame_bookend(r: &mut &[u8]) -> Result<FrameEvent<frame::Bookend, i32>> {
+	let index = r.read_i32::<BigEndian>()?;
+	trace!("Frame Bookend: {}", index);
+	let latest_finalized_frame = match r.is_empty() {
+		true => None,
+		_ => Some(r.read_i32::<BigEndian>()?),
+	};
+	Ok(FrameEvent {
+		id: index,
+		event: frame::Bookend { index, latest_finalized_frame },
+	})
+}
+
+/// One event from the raw stream, as passed to `Handlers`. See `events`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParsedEvent {
+	GameStart(Box<Start>),
+	FrameStart(FrameEvent<frame::Start, i32>),
+	FramePre(FrameEvent<Pre>),
+	FramePost(FrameEvent<Post>),
+	GameEnd(End),
+	Item(FrameEvent<frame::Item, ItemId>),
+	FrameBookend(FrameEvent<frame::Bookend, i32>),
+	GeckoList(Vec<u8>),
+	Metadata(ubjson::Map),
+	/// Any event this parser doesn't decode.
+	Unknown(u8, Vec<u8>),
+}
+
 pub trait Handlers {
+	/// Called once with the payload size (excluding the code byte) of every event code
+	/// declared in the Event Payloads event, before any other event is parsed.
+	fn payload_sizes(&mut self, _: &HashMap<u8, u16>) -> Result<()> { Ok(()) }
+	/// Called for events whose code is declared but not one we know how to parse.
+	fn unknown_event(&mut self, _code: u8, _payload: &[u8]) -> Result<()> { Ok(()) }
+	/// Called with the undecoded payload of every event after Event Payloads, known or
+	/// not, before the event's own callback.
+	fn raw_event(&mut self, _code: u8, _payload: &[u8]) -> Result<()> { Ok(()) }
 	fn game_start(&mut self, _: Start) -> Result<()> { Ok(()) }
 	fn game_end(&mut self, _: End) -> Result<()> { Ok(()) }
+	/// Called before any of a frame's other events. The id is the frame index.
+	fn frame_start(&mut self, _: FrameEvent<frame::Start, i32>) -> Result<()> { Ok(()) }
 	fn frame_pre(&mut self, _: FrameEvent<Pre>) -> Result<()> { Ok(()) }
 	fn frame_post(&mut self, _: FrameEvent<Post>) -> Result<()> { Ok(()) }
-	fn metadata(&mut self, _: HashMap<String, ubjson::Object>) -> Result<()> { Ok(()) }
+	fn item(&mut self, _: FrameEvent<frame::Item, ItemId>) -> Result<()> { Ok(()) }
+	/// Called once all of a frame's events have been sent. The id is the frame index.
+	fn frame_bookend(&mut self, _: FrameEvent<frame::Bookend, i32>) -> Result<()> { Ok(()) }
+	/// Called with the undecoded payload of the Gecko List event, which lists the
+	/// Gecko codes (mods) that were active. Sent once, right after Game Start.
+	fn gecko_list(&mut self, _codes: Vec<u8>) -> Result<()> { Ok(()) }
+	fn metadata(&mut self, _: ubjson::Map) -> Result<()> { Ok(()) }
 }
 
-fn expect_bytes<R: Read>(r: &mut R, expected: &[u8]) -> Result<()> {
+fn expect_bytes<R: Read>(r: &mut R, expected: &[u8]) -> std::result::Result<(), Error> {
 	let mut actual = vec![0; expected.len()];
 	r.read_exact(&mut actual)?;
 	if expected == actual.as_slice() {
 		Ok(())
 	} else {
-		Err(err!("expected: {:?}, got: {:?}", expected, actual))
+		Err(Error::BadMagic { expected: expected.to_vec(), got: actual })
 	}
 }
 
-/// Parses a single event from the raw stream. If the event is one of the
+/// Where event payloads come from: a reader (copying each payload into a buffer), or
+/// an in-memory slice (borrowing each payload in place).
+trait Source: Read {
+	/// Reads the next `size` bytes.
+	fn payload(&mut self, size: usize) -> Result<&[u8]>;
+}
+
+/// A `Source` for any reader. Payloads are read into `buf`, which is reused for every
+/// event, so once it's grown to the largest payload size there are no more allocations.
+struct ReadSource<R> {
+	r: R,
+	buf: Vec<u8>,
+}
+
+impl<R: Read> Read for ReadSource<R> {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		self.r.read(buf)
+	}
+}
+
+impl<R: Read> Source for ReadSource<R> {
+	fn payload(&mut self, size: usize) -> Result<&[u8]> {
+		self.buf.clear();
+		self.buf.resize(size, 0);
+		self.r.read_exact(&mut self.buf)?;
+		Ok(&self.buf)
+	}
+}
+
+impl Source for &[u8] {
+	fn payload(&mut self, size: usize) -> Result<&[u8]> {
+		if size > self.len() {
+			return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("event payload of {} bytes, but only {} left", size, self.len())));
+		}
+		let (payload, rest) = self.split_at(size);
+		*self = rest;
+		Ok(payload)
+	}
+}
+
+/// Parses the payload of a single event (whose code has already been read) from the raw
+/// stream. If the event is one of the
 /// supported `Event` types, calls the corresponding `Handler` callback with
 /// the parsed event.
 /// Returns the number of bytes read by this function.
-fn event<R: Read, H: Handlers>(mut r: R, payload_sizes: &HashMap<u8, u16>, last_char_states: &mut [CharState; NUM_PORTS], handlers: &mut H) -> Result<(usize, Option<Event>)> {
-	let code = r.read_u8()?;
+fn event<S: Source, H: Handlers>(r: &mut S, code: u8, payload_sizes: &HashMap<u8, u16>, last_char_states: &mut [CharState; NUM_PORTS], handlers: &mut H, skip_frames: bool) -> std::result::Result<(usize, Option<Event>), Error> {
 	debug!("Event: {:#x}", code);
 
-	let size = *payload_sizes.get(&code).ok_or_else(|| err!("unknown event: {}", code))? as usize;
-	let mut buf = vec![0; size];
-	r.read_exact(&mut *buf)?;
+	let size = *payload_sizes.get(&code).ok_or(Error::UnknownEvent(code))? as usize;
+	let buf = r.payload(size)?;
+	handlers.raw_event(code, buf)?;
 
 	let event = Event::try_from(code).ok();
 	if let Some(event) = event {
 		use Event::*;
 		match event {
+			FrameStart | FramePre | FramePost | Item | FrameBookend if skip_frames => (),
 			GameStart => handlers.game_start(game_start(&mut &*buf)?)?,
+			FrameStart => handlers.frame_start(frame_start(&mut &*buf)?)?,
 			FramePre => handlers.frame_pre(frame_pre(&mut &*buf, last_char_states)?)?,
 			FramePost => handlers.frame_post(frame_post(&mut &*buf, last_char_states)?)?,
 			GameEnd => handlers.game_end(game_end(&mut &*buf)?)?,
+			Item => handlers.item(item(&mut &*buf)?)?,
+			FrameBookend => handlers.frame_bookend(frame_bookend(&mut &*buf)?)?,
+			GeckoList => handlers.gecko_list(buf.to_vec())?,
 		}
+	} else {
+		handlers.unknown_event(code, buf)?;
 	}
 
 	Ok((1 + size as usize, event)) // +1 byte for the event code
 }
 
-/// Parses a Slippi replay from `r`, passing events to the callbacks in `handlers` as they occur.
-pub fn parse<R: Read, H: Handlers>(mut r: R, handlers: &mut H) -> Result<()> {
+// top-level opening brace, `raw` key & type ("{U\x03raw[$U#l")
+pub(crate) const RAW_HEADER: [u8; 11] = [0x7b, 0x55, 0x03, 0x72, 0x61, 0x77, 0x5b, 0x24, 0x55, 0x23, 0x6c];
+
+/// Reads the top-level opening brace and the `raw` element's key & type.
+/// Returns the length in bytes of the `raw` element's value.
+fn raw_header<R: Read>(r: &mut R) -> std::result::Result<usize, Error> {
 	// For speed, assume the `raw` element comes first and handle it manually.
 	// The official JS parser does this too, so it should be reliable.
-	expect_bytes(&mut r,
-		// top-level opening brace, `raw` key & type ("{U\x03raw[$U#l")
-		&[0x7b, 0x55, 0x03, 0x72, 0x61, 0x77, 0x5b, 0x24, 0x55, 0x23, 0x6c])?;
+	expect_bytes(r, &RAW_HEADER)?;
+
+	Ok(r.read_u32::<BigEndian>()? as usize)
+}
+
+// `metadata` key & type ("U\x08metadata{")
+pub(crate) const METADATA_KEY: [u8; 11] = [0x55, 0x08, 0x6d, 0x65, 0x74, 0x61, 0x64, 0x61, 0x74, 0x61, 0x7b];
+
+/// Reads the `metadata` element that follows `raw`, up to and including its closing brace.
+fn metadata_map<R: Read>(r: &mut R) -> std::result::Result<ubjson::Map, Error> {
+	expect_bytes(r, &METADATA_KEY)?;
+	// Since we already read the opening "{" from the `metadata` value,
+	// we know it's a map. `parse_map` will consume the corresponding "}".
+	ubjson::parse_map(r).map_err(|e| match e.kind() {
+		io::ErrorKind::UnexpectedEof => Error::UnexpectedEof,
+		_ => Error::Ubjson(e),
+	})
+}
+
+/// Like `metadata_map` followed by the top-level closing brace, but searches for the
+/// `metadata` key anywhere in the remaining bytes and tolerates a missing closing brace.
+/// Problems are logged rather than returned, and `handlers.metadata` is only called if a
+/// metadata map was successfully parsed.
+fn metadata_lenient<R: Read, H: Handlers>(r: &mut R, handlers: &mut H) -> Result<()> {
+	let mut rest = Vec::new();
+	r.read_to_end(&mut rest)?;
+
+	match rest.windows(METADATA_KEY.len()).position(|w| w == METADATA_KEY) {
+		Some(pos) => {
+			if pos > 0 {
+				warn!("skipped {} bytes before metadata", pos);
+			}
+			let mut rest = &rest[pos + METADATA_KEY.len() ..];
+			match ubjson::parse_map(&mut rest) {
+				Ok(metadata) => {
+					handlers.metadata(metadata)?;
+					match rest {
+						[0x7d] => (),
+						[] => warn!("missing top-level closing brace"),
+						_ => warn!("unexpected bytes after metadata: {:?}", rest),
+					}
+				},
+				Err(e) => warn!("failed to parse metadata: {:?}", e),
+			}
+		},
+		None => warn!("metadata not found"),
+	}
+
+	Ok(())
+}
+
+/// Number of events between calls to `ParseOptions::progress`.
+pub const PROGRESS_INTERVAL: usize = 1024;
+
+/// What `game_parser::GameParser` does with frames that are sent more than once
+/// (as happens with rollback netcode).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RollbackMode {
+	/// Keep only the last-sent copy of each frame, so frame indexes strictly increase.
+	/// Frames at or before a Frame Bookend's `latest_finalized_frame` are never replaced.
+	#[default]
+	Dedup,
+	/// Keep every copy in the order sent, including rolled-back ones. Frame arrays may
+	/// then repeat indexes, in which case index-based lookups on `Game` (`Game::frame`,
+	/// `Game::pre`, `Game::post`) return `None`.
+	KeepAll,
+}
+
+#[derive(Default)]
+pub struct ParseOptions<'a> {
+	/// Search for the `metadata` element instead of requiring it immediately after `raw`,
+	/// and tolerate a missing top-level closing brace. A missing or truncated `metadata`
+	/// element is skipped (leaving metadata empty), and a `raw` length that doesn't match
+	/// the events read is only a warning. This rescues otherwise-complete replays written
+	/// by third-party tools or cut short by a crash.
+	pub lenient_end: bool,
+
+	/// Called with `(bytes_read, raw_len)` every `PROGRESS_INTERVAL` events, and once more
+	/// after the last event unless that was just reported, so `bytes_read` strictly
+	/// increases. For in-progress replays the `raw` length isn't known, so `raw_len` is
+	/// passed as 0: callers can still report bytes read, just not a fraction.
+	pub progress: Option<&'a mut dyn FnMut(usize, usize)>,
+
+	/// How `game` and `game_opts` handle rolled-back frames.
+	pub rollback: RollbackMode,
+
+	/// Don't decode frame events (Frame Start, Pre, Post, Item and Frame Bookend), so their callbacks
+	/// are never called. Much faster when only `Start`, `End`, and metadata are needed.
+	/// `raw_event` still sees every event.
+	pub skip_frames: bool,
+}
+
+impl fmt::Debug for ParseOptions<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("ParseOptions")
+			.field("lenient_end", &self.lenient_end)
+			.field("progress", &self.progress.as_ref().map(|_| "FnMut"))
+			.field("rollback", &self.rollback)
+			.field("skip_frames", &self.skip_frames)
+			.finish()
+	}
+}
+
+/// Parses a Slippi replay from `r`, passing events to the callbacks in `handlers` as they occur.
+pub fn parse<R: Read, H: Handlers>(r: R, handlers: &mut H) -> std::result::Result<(), Error> {
+	parse_opts(r, handlers, &mut ParseOptions::default())
+}
+
+/// Like `parse`, but with non-default `opts`.
+pub fn parse_opts<R: Read, H: Handlers>(r: R, handlers: &mut H, opts: &mut ParseOptions) -> std::result::Result<(), Error> {
+	parse_source(ReadSource { r, buf: Vec::new() }, handlers, opts, &mut false)
+}
+
+/// Like `parse`, but a replay that ends early (e.g. because the console crashed) isn't an
+/// error as long as Game Start was read and the data stops between events (or in the
+/// metadata after them): `handlers` will have been sent everything up to the cut. Running
+/// out partway through an event is still `Error::UnexpectedEof`, since that's more likely
+/// corruption (such as a bad payload size) than a clean cut. Returns whether the replay
+/// was truncated.
+pub fn parse_partial<R: Read, H: Handlers>(r: R, handlers: &mut H) -> std::result::Result<bool, Error> {
+	parse_partial_opts(r, handlers, &mut ParseOptions::default())
+}
 
-	let raw_len = r.read_u32::<BigEndian>()? as usize;
+/// Like `parse_partial`, but with non-default `opts`.
+pub fn parse_partial_opts<R: Read, H: Handlers>(r: R, handlers: &mut H, opts: &mut ParseOptions) -> std::result::Result<bool, Error> {
+	let mut at_boundary = false;
+	match parse_source(ReadSource { r, buf: Vec::new() }, handlers, opts, &mut at_boundary) {
+		Ok(()) => Ok(false),
+		Err(Error::UnexpectedEof) if at_boundary => Ok(true),
+		Err(e) => Err(e),
+	}
+}
+
+/// Like `parse`, but for a replay that's already in memory. Event payloads are decoded
+/// straight from `data` instead of being copied out first.
+pub fn parse_slice<H: Handlers>(data: &[u8], handlers: &mut H) -> std::result::Result<(), Error> {
+	parse_slice_opts(data, handlers, &mut ParseOptions::default())
+}
+
+/// Like `parse_slice`, but with non-default `opts`.
+pub fn parse_slice_opts<H: Handlers>(data: &[u8], handlers: &mut H, opts: &mut ParseOptions) -> std::result::Result<(), Error> {
+	parse_source(data, handlers, opts, &mut false)
+}
+
+/// Does the actual parsing for `parse_opts` and friends. `at_boundary` is set whenever
+/// Game Start has been handled and no other event is partially read, i.e. wherever a
+/// truncated replay could cleanly end.
+fn parse_source<S: Source, H: Handlers>(mut r: S, handlers: &mut H, opts: &mut ParseOptions, at_boundary: &mut bool) -> std::result::Result<(), Error> {
+	let raw_len = raw_header(&mut r)?;
 	let (mut bytes_read, payload_sizes) = payload_sizes(&mut r)?;
+	handlers.payload_sizes(&payload_sizes)?;
 	let mut last_char_states = [DEFAULT_CHAR_STATE; NUM_PORTS];
 	let mut last_event: Option<Event> = None;
+	let mut started = false;
+
+	let mut progress = opts.progress.as_mut();
+	let mut event_count = 0;
+	let mut last_reported = None;
+
+	// The `metadata` key's first byte, if we read it while looking for another event.
+	let mut metadata_start = None;
 
 	// `raw_len` will be 0 for an in-progress replay
 	while (raw_len == 0 || bytes_read < raw_len) && last_event != Some(Event::GameEnd) {
-		let (bytes, event) = event(r.by_ref(), &payload_sizes, &mut last_char_states, handlers)?;
+		let code = r.read_u8()?;
+		// Without `raw_len` or Game End (e.g. if the game was cut short, so the last event
+		// is a Frame Bookend), the only way to tell that events are done is hitting metadata.
+		if raw_len == 0 && code == METADATA_KEY[0] && !payload_sizes.contains_key(&code) {
+			metadata_start = Some(code);
+			break;
+		}
+
+		*at_boundary = false;
+		let (bytes, event) = event(&mut r, code, &payload_sizes, &mut last_char_states, handlers, opts.skip_frames)?;
 		bytes_read += bytes;
 		last_event = event;
+		started |= event == Some(Event::GameStart);
+		*at_boundary = started;
+
+		if let Some(progress) = progress.as_mut() {
+			event_count += 1;
+			if event_count % PROGRESS_INTERVAL == 0 {
+				progress(bytes_read, raw_len);
+				last_reported = Some(bytes_read);
+			}
+		}
+	}
+
+	if let Some(progress) = progress {
+		if last_reported != Some(bytes_read) {
+			progress(bytes_read, raw_len);
+		}
 	}
 
 	if raw_len != 0 && bytes_read != raw_len {
-		Err(err!("failed to consume expected number of bytes: {}, {}", raw_len, bytes_read))?;
+		if opts.lenient_end {
+			warn!("failed to consume expected number of bytes: {}, {}", raw_len, bytes_read);
+		} else {
+			Err(err!("failed to consume expected number of bytes: {}, {}", raw_len, bytes_read))?;
+		}
 	}
 
-	expect_bytes(&mut r,
-		// `metadata` key & type ("U\x08metadata{")
-		&[0x55, 0x08, 0x6d, 0x65, 0x74, 0x61, 0x64, 0x61, 0x74, 0x61, 0x7b])?;
-	// Since we already read the opening "{" from the `metadata` value,
-	// we know it's a map. `parse_map` will consume the corresponding "}".
-	handlers.metadata(ubjson::parse_map(&mut r)?)?;
+	let metadata_start:Vec<u8> = metadata_start.into_iter().collect();
+	let mut r = (&*metadata_start).chain(r);
+
+	if opts.lenient_end {
+		return Ok(metadata_lenient(&mut r, handlers)?);
+	}
+
+	handlers.metadata(metadata_map(&mut r)?)?;
 
 	expect_bytes(&mut r, &[0x7d])?; // top-level closing brace ("}")
 	Ok(())
 }
+
+/// Reads only the metadata from a Slippi replay, seeking past the `raw` element without
+/// decoding any events. Fails for in-progress replays, whose `raw` length isn't known yet.
+pub fn metadata<R: Read + Seek>(mut r: R) -> std::result::Result<ubjson::Map, Error> {
+	let raw_len = raw_header(&mut r)?;
+	if raw_len == 0 {
+		Err(err!("can't skip `raw` element of unknown length"))?;
+	}
+
+	r.seek(SeekFrom::Current(raw_len as i64))?;
+	metadata_map(&mut r)
+}
+
+/// Reads the `raw` header and the Event Payloads event of a Slippi replay, returning the
+/// payload size (excluding the code byte) of every event code it declares. Afterwards `r`
+/// is positioned at the first event following Event Payloads (normally Game Start).
+pub fn event_sizes<R: Read>(r: &mut R) -> std::result::Result<HashMap<u8, u16>, Error> {
+	raw_header(r)?;
+	Ok(payload_sizes(r)?.1)
+}
+
+/// Reads just enough of a Slippi replay to return the version of Slippi that recorded it:
+/// the `raw` header, the Event Payloads event, and the version bytes at the start of Game
+/// Start. Doesn't require `r` to be seekable, and leaves it partway through Game Start.
+pub fn peek_version<R: Read>(r: &mut R) -> std::result::Result<game::SlippiVersion, Error> {
+	event_sizes(r)?;
+	expect_bytes(r, &[Event::GameStart as u8])?;
+	Ok(game::SlippiVersion(r.read_u8()?, r.read_u8()?, r.read_u8()?))
+}
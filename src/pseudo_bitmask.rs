@@ -1,27 +1,70 @@
 macro_rules! pseudo_bitmask {
 	($name:ident : $type:ty { $( $value:expr => $variant:ident ),* $(,)? }) => {
-		#[derive(PartialEq, Eq, Copy, Clone, serde::Serialize)]
+		#[derive(PartialEq, Eq, Copy, Clone)]
+		#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 		pub struct $name(pub $type);
 
 		impl $name {
 			$( pub const $variant:$name = $name($value); )*
+
+			/// Every named constant, in declaration order.
+			const NAMED:&'static [(&'static str, $name)] = &[ $( (stringify!($variant), $name($value)) ),* ];
+
+			/// The named constants (with their names) with bits set in this value.
+			fn named(&self) -> impl Iterator<Item = &'static (&'static str, $name)> {
+				let bits = self.0;
+				Self::NAMED.iter().filter(move |(_, flag)| (bits & flag.0) > 0)
+			}
+
+			/// Names of the constants with bits set in this value.
+			pub fn names(&self) -> Vec<&'static str> {
+				self.named().map(|(name, _)| *name).collect()
+			}
+
+			/// The named constants with bits set in this value.
+			pub fn flags(&self) -> Vec<$name> {
+				self.iter().collect()
+			}
+
+			/// Iterates over the named constants with bits set in this value.
+			pub fn iter(&self) -> impl Iterator<Item = $name> {
+				self.named().map(|(_, flag)| *flag)
+			}
+
+			/// Bits set in this value that aren't part of any named constant.
+			fn unnamed_bits(&self) -> $type {
+				self.0 & !(0 $( | $value )*)
+			}
+
+			/// Whether every bit set in `other` is also set in this value.
+			pub fn contains(&self, other:$name) -> bool {
+				self.0 & other.0 == other.0
+			}
 		}
 
 		impl std::fmt::Debug for $name {
 			fn fmt(&self, f:&mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 				match unsafe { super::CONFIG.enum_names } {
-					true => {
-						let mut named_values: Vec<&str> = Vec::new();
-						$( if (self.0 & $value) > 0 {
-							named_values.push(stringify!($variant));
-						} )*
-						write!(f, "{} {:?}", self.0, named_values)
-					},
+					true => write!(f, "{} {:?}", self.0, self.names()),
 					_ => write!(f, "{}", self.0),
 				}
 			}
 		}
 
+		/// Set flags joined by `|`, e.g. `SHIELD|HIT_STUN`. Unnamed bits are shown by
+		/// position (`0x1<<7`), and a value with no bits set as `0`.
+		impl std::fmt::Display for $name {
+			fn fmt(&self, f:&mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+				let unnamed = self.unnamed_bits();
+				let mut parts:Vec<String> = self.names().into_iter().map(String::from).collect();
+				parts.extend((0 .. <$type>::BITS).filter(|b| unnamed >> b & 1 == 1).map(|b| format!("0x1<<{}", b)));
+				match parts.is_empty() {
+					true => write!(f, "0"),
+					_ => write!(f, "{}", parts.join("|")),
+				}
+			}
+		}
+
 		impl std::ops::BitOr for $name {
 			type Output = Self;
 
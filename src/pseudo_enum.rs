@@ -12,6 +12,27 @@ impl std::fmt::Display for ConversionError {
 
 impl std::error::Error for ConversionError { }
 
+/// Reads a pseudo-enum's value in either form its `Serialize` impl writes: the bare
+/// value, or (with `enum_names`) a string such as `"20:FOX"` or `"400"`.
+#[cfg(feature = "serde")]
+pub fn deserialize<'de, D, T>(deserializer:D) -> std::result::Result<T, D::Error>
+		where D:serde::Deserializer<'de>, T:serde::Deserialize<'de> + std::str::FromStr, T::Err:std::fmt::Display {
+	#[derive(serde::Deserialize)]
+	#[serde(untagged)]
+	enum Repr<T> {
+		Value(T),
+		Named(String),
+	}
+
+	match <Repr<T> as serde::Deserialize>::deserialize(deserializer)? {
+		Repr::Value(v) => Ok(v),
+		Repr::Named(s) => {
+			let value = s.split(':').next().unwrap_or_default();
+			value.parse().map_err(|e| serde::de::Error::custom(format!("invalid value {:?}: {}", s, e)))
+		},
+	}
+}
+
 macro_rules! pseudo_enum {
 	($name:ident : $type:ty { $( $value:expr => $variant:ident ),* $(,)? }) => {
 		#[derive(Copy, Clone, PartialEq, Eq, Hash)]
@@ -19,6 +40,14 @@ macro_rules! pseudo_enum {
 
 		impl $name {
 			$( pub const $variant:$name = $name($value); )*
+
+			/// Name of the constant for this value, if there is one.
+			pub fn name(&self) -> Option<&'static str> {
+				match self.0 {
+					$( $value => Some(stringify!($variant)), )*
+					_ => None,
+				}
+			}
 		}
 
 		impl std::convert::TryFrom<&str> for $name {
@@ -37,8 +66,8 @@ macro_rules! pseudo_enum {
 		impl std::fmt::Debug for $name {
 			fn fmt(&self, f:&mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 				match unsafe { super::CONFIG.enum_names } {
-					true => match self.0 {
-						$( $value => write!(f, "{}:{}", self.0, stringify!($variant)), )*
+					true => match self.name() {
+						Some(name) => write!(f, "{}:{}", self.0, name),
 						_ => write!(f, "{}", self.0),
 					},
 					_ => write!(f, "{}", self.0),
@@ -46,6 +75,7 @@ macro_rules! pseudo_enum {
 			}
 		}
 
+		#[cfg(feature = "serde")]
 		impl serde::Serialize for $name {
 			fn serialize<S:serde::ser::Serializer>(&self, serializer:S) -> std::result::Result<S::Ok, S::Error> {
 				match unsafe { super::CONFIG.enum_names } {
@@ -55,6 +85,13 @@ macro_rules! pseudo_enum {
 			}
 		}
 
+		#[cfg(feature = "serde")]
+		impl<'de> serde::Deserialize<'de> for $name {
+			fn deserialize<D:serde::de::Deserializer<'de>>(deserializer:D) -> std::result::Result<Self, D::Error> {
+				super::pseudo_enum::deserialize(deserializer).map($name)
+			}
+		}
+
 		query_impl!($name);
 	}
 }
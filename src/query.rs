@@ -2,6 +2,28 @@ use std::io::{Write, Result};
 
 pub trait Query {
 	fn query(&self, f:&mut dyn Write, config:&super::Config, query:&[&str]) -> Result<()>;
+
+	/// Like `query`, but returns the value at `query` as JSON instead of writing it out,
+	/// with the default `ExportOptions`. Iterating over a collection (an empty path
+	/// segment) yields a JSON array.
+	fn query_json(&self, query:&[&str]) -> Result<serde_json::Value> {
+		let config = unsafe { super::Config {
+			json: true,
+			frames: super::CONFIG.frames,
+			export: super::ExportOptions::default(),
+			enum_names: super::CONFIG.enum_names,
+			query: None,
+		} };
+		let mut buf = Vec::new();
+		self.query(&mut buf, &config, query)?;
+		let mut values = serde_json::Deserializer::from_slice(&buf).into_iter::<serde_json::Value>()
+			.collect::<std::result::Result<Vec<_>, _>>()
+			.map_err(|e| err!("JSON deserialization error: {:?}", e))?;
+		match values.len() {
+			1 => Ok(values.remove(0)),
+			_ => Ok(serde_json::Value::Array(values)),
+		}
+	}
 }
 
 macro_rules! query_impl {
@@ -109,6 +131,7 @@ query_impl!(i32);
 query_impl!(u64);
 query_impl!(i64);
 query_impl!(f32);
+query_impl!(f64);
 
 query_impl!(bool);
 query_impl!(String);
@@ -1,3 +1,11 @@
+use std::fmt;
+
+use super::frame::{Position};
+
+/// How far below `y = 0` a character can be and still count as on stage,
+/// to allow for slopes and landing jitter.
+pub const STAGE_FLOOR_TOLERANCE:f32 = -5.0;
+
 pseudo_enum!(Stage:u16 {
 	02 => FOUNTAIN_OF_DREAMS,
 	03 => POKEMON_STADIUM,
@@ -30,3 +38,132 @@ pseudo_enum!(Stage:u16 {
 	31 => BATTLEFIELD,
 	32 => FINAL_DESTINATION,
 });
+
+impl Stage {
+	/// Human-readable name, as shown on the stage select screen.
+	pub fn display_name(&self) -> Option<&'static str> {
+		Some(match *self {
+			Stage::FOUNTAIN_OF_DREAMS => "Fountain of Dreams",
+			Stage::POKEMON_STADIUM => "Pokémon Stadium",
+			Stage::PRINCESS_PEACHS_CASTLE => "Princess Peach's Castle",
+			Stage::KONGO_JUNGLE => "Kongo Jungle",
+			Stage::BRINSTAR => "Brinstar",
+			Stage::CORNERIA => "Corneria",
+			Stage::YOSHIS_STORY => "Yoshi's Story",
+			Stage::ONETT => "Onett",
+			Stage::MUTE_CITY => "Mute City",
+			Stage::RAINBOW_CRUISE => "Rainbow Cruise",
+			Stage::JUNGLE_JAPES => "Jungle Japes",
+			Stage::GREAT_BAY => "Great Bay",
+			Stage::HYRULE_TEMPLE => "Hyrule Temple",
+			Stage::BRINSTAR_DEPTHS => "Brinstar Depths",
+			Stage::YOSHIS_ISLAND => "Yoshi's Island",
+			Stage::GREEN_GREENS => "Green Greens",
+			Stage::FOURSIDE => "Fourside",
+			Stage::MUSHROOM_KINGDOM_I => "Mushroom Kingdom I",
+			Stage::MUSHROOM_KINGDOM_II => "Mushroom Kingdom II",
+			Stage::VENOM => "Venom",
+			Stage::POKE_FLOATS => "Poké Floats",
+			Stage::BIG_BLUE => "Big Blue",
+			Stage::ICICLE_MOUNTAIN => "Icicle Mountain",
+			Stage::ICETOP => "Icetop",
+			Stage::FLAT_ZONE => "Flat Zone",
+			Stage::DREAM_LAND_N64 => "Dream Land N64",
+			Stage::YOSHIS_ISLAND_N64 => "Yoshi's Island N64",
+			Stage::KONGO_JUNGLE_N64 => "Kongo Jungle N64",
+			Stage::BATTLEFIELD => "Battlefield",
+			Stage::FINAL_DESTINATION => "Final Destination",
+			_ => return None,
+		})
+	}
+
+	/// Whether this is one of the six stages commonly legal in singles:
+	/// Final Destination, Battlefield, Yoshi's Story, Dream Land, Fountain of Dreams,
+	/// and Pokémon Stadium.
+	pub fn is_tournament_legal(&self) -> bool {
+		matches!(*self,
+			Stage::FINAL_DESTINATION |
+			Stage::BATTLEFIELD |
+			Stage::YOSHIS_STORY |
+			Stage::DREAM_LAND_N64 |
+			Stage::FOUNTAIN_OF_DREAMS |
+			Stage::POKEMON_STADIUM)
+	}
+
+	/// Horizontal distance from stage center to either ledge of the main platform,
+	/// for the tournament-legal stages. All of them are symmetric about `x = 0`, with
+	/// the top of the main platform at `y = 0`.
+	pub fn edge_x(&self) -> Option<f32> {
+		match *self {
+			Stage::FINAL_DESTINATION => Some(85.5657),
+			Stage::BATTLEFIELD => Some(68.4),
+			Stage::YOSHIS_STORY => Some(56.0),
+			Stage::DREAM_LAND_N64 => Some(77.2713),
+			Stage::FOUNTAIN_OF_DREAMS => Some(63.3475),
+			Stage::POKEMON_STADIUM => Some(87.75),
+			_ => None,
+		}
+	}
+
+	/// Whether `position` is beyond either ledge or below the main platform.
+	/// `None` for stages without known geometry (anything but the tournament-legal
+	/// stages; see `edge_x`).
+	pub fn is_offstage(&self, position:&Position) -> Option<bool> {
+		self.edge_x().map(|edge| position.x.abs() > edge || position.y < STAGE_FLOOR_TOLERANCE)
+	}
+}
+
+/// Name of the surface with ground ID `ground` (`frame::PostV2_0::ground`) on `stage`.
+/// `None` for unknown IDs, including 65535, which is reported before a character has
+/// first touched the ground, and for stages other than the tournament-legal ones.
+///
+/// The Battlefield and Yoshi's Story tables are checked against the replays in
+/// `test/replays`; the others follow the community-documented collision data, as
+/// there are no v2.0+ test replays on those stages yet.
+pub fn ground_name(stage:Stage, ground:u16) -> Option<&'static str> {
+	Some(match (stage, ground) {
+		(Stage::FOUNTAIN_OF_DREAMS, 0) => "Left Platform",
+		(Stage::FOUNTAIN_OF_DREAMS, 1) => "Right Platform",
+		(Stage::FOUNTAIN_OF_DREAMS, 2) => "Top Platform",
+		(Stage::FOUNTAIN_OF_DREAMS, 3) => "Left Edge",
+		(Stage::FOUNTAIN_OF_DREAMS, 4) => "Main Stage",
+		(Stage::FOUNTAIN_OF_DREAMS, 5) => "Right Edge",
+		(Stage::POKEMON_STADIUM, 34) => "Main Stage",
+		(Stage::POKEMON_STADIUM, 35) => "Left Platform",
+		(Stage::POKEMON_STADIUM, 36) => "Right Platform",
+		(Stage::POKEMON_STADIUM, 51) => "Left Edge",
+		(Stage::POKEMON_STADIUM, 52) => "Right Edge",
+		(Stage::BATTLEFIELD, 0) => "Left Edge",
+		(Stage::BATTLEFIELD, 1) => "Main Stage",
+		(Stage::BATTLEFIELD, 2) => "Left Platform",
+		(Stage::BATTLEFIELD, 3) => "Top Platform",
+		(Stage::BATTLEFIELD, 4) => "Right Platform",
+		(Stage::BATTLEFIELD, 5) => "Right Edge",
+		(Stage::YOSHIS_STORY, 0) => "Randall",
+		(Stage::YOSHIS_STORY, 1) => "Left Platform",
+		(Stage::YOSHIS_STORY, 2) => "Left Slant",
+		(Stage::YOSHIS_STORY, 3) => "Main Stage",
+		(Stage::YOSHIS_STORY, 4) => "Top Platform",
+		(Stage::YOSHIS_STORY, 5) => "Right Platform",
+		(Stage::YOSHIS_STORY, 6) => "Right Slant",
+		(Stage::DREAM_LAND_N64, 0) => "Left Platform",
+		(Stage::DREAM_LAND_N64, 1) => "Right Platform",
+		(Stage::DREAM_LAND_N64, 2) => "Top Platform",
+		(Stage::DREAM_LAND_N64, 3) => "Left Edge",
+		(Stage::DREAM_LAND_N64, 4) => "Main Stage",
+		(Stage::DREAM_LAND_N64, 5) => "Right Edge",
+		(Stage::FINAL_DESTINATION, 0) => "Left Edge",
+		(Stage::FINAL_DESTINATION, 1) => "Main Stage",
+		(Stage::FINAL_DESTINATION, 2) => "Right Edge",
+		_ => return None,
+	})
+}
+
+impl fmt::Display for Stage {
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+		match self.display_name() {
+			Some(name) => write!(f, "{}", name),
+			_ => write!(f, "Unknown({})", self.0),
+		}
+	}
+}
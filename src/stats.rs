@@ -0,0 +1,1068 @@
+use std::cmp::Ordering;
+use std::ops::AddAssign;
+
+use super::action_state::{Common, Falco, Fox, State};
+use super::attack::{Attack};
+use super::frame::{HurtboxState, LCancel, MiscAs, Position, Post, StateFlags, STICK_DEADZONE};
+use super::game::{Game, TeamColor, FIRST_PLAYABLE_FRAME_INDEX, NUM_PORTS};
+use super::stage::{Stage};
+
+/// Minimum number of crouches in quick succession to count as a teabag.
+pub const TEABAG_MIN_CROUCHES:u32 = 3;
+
+/// Maximum number of frames between the starts of consecutive crouches in a teabag.
+pub const TEABAG_MAX_GAP:i32 = 30;
+
+/// Fraction of frames spent shielding above which shielding is considered excessive.
+pub const EXCESSIVE_SHIELD_RATIO:f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InputPatterns {
+	/// Number of times the player started crouching.
+	pub crouches: u32,
+	/// Number of bursts of at least `TEABAG_MIN_CROUCHES` crouches.
+	pub teabags: u32,
+	/// Number of taunts started.
+	pub taunts: u32,
+	/// Number of frames spent holding shield.
+	pub shield_frames: u32,
+	/// Whether `shield_frames` exceeds `EXCESSIVE_SHIELD_RATIO` of all frames.
+	pub excessive_shield: bool,
+}
+
+/// Whether the character is dead or otherwise out of play (e.g. Nana after dying).
+fn is_dead(state:State) -> bool {
+	matches!(state, State::Common(Common(s)) if s <= Common::SLEEP.0)
+}
+
+fn is_shielding(state:State) -> bool {
+	matches!(state,
+		State::Common(Common::GUARD_ON) |
+		State::Common(Common::GUARD) |
+		State::Common(Common::GUARD_SET_OFF) |
+		State::Common(Common::GUARD_REFLECT))
+}
+
+/// Detects lighthearted input patterns (teabags, taunts, shielding) for the leader on `port`.
+/// Returns all zeroes if the port has no frame data.
+pub fn input_patterns(game:&Game, port:u8) -> InputPatterns {
+	let mut patterns = InputPatterns::default();
+	let frames = match game.ports.get(port as usize) {
+		Some(Some(p)) => &p.leader.post,
+		_ => return patterns,
+	};
+
+	let mut prev:Option<State> = None;
+	let mut last_crouch:Option<i32> = None;
+	let mut run = 0;
+
+	for post in frames {
+		let entered = |s:Common| post.state == State::Common(s) && prev != Some(State::Common(s));
+
+		if entered(Common::SQUAT) {
+			patterns.crouches += 1;
+			run = match last_crouch {
+				Some(last) if post.index - last <= TEABAG_MAX_GAP => run + 1,
+				_ => 1,
+			};
+			if run == TEABAG_MIN_CROUCHES {
+				patterns.teabags += 1;
+			}
+			last_crouch = Some(post.index);
+		}
+
+		if entered(Common::APPEAL_R) || entered(Common::APPEAL_L) {
+			patterns.taunts += 1;
+		}
+
+		if is_shielding(post.state) {
+			patterns.shield_frames += 1;
+		}
+
+		prev = Some(post.state);
+	}
+
+	patterns.excessive_shield = !frames.is_empty() &&
+		patterns.shield_frames as f32 / frames.len() as f32 > EXCESSIVE_SHIELD_RATIO;
+
+	patterns
+}
+
+fn add_damage_taken(matrix:&mut [[f32; NUM_PORTS]; NUM_PORTS], victim:usize, frames:&[Post]) {
+	for w in frames.windows(2) {
+		let delta = w[1].damage - w[0].damage;
+		if delta > 0.0 {
+			// Damage with no valid attacker (e.g. Bob-omb explosions) goes on the diagonal.
+			let attacker = w[1].last_hit_by_port().map_or(victim, |p| p as usize);
+			matrix[attacker][victim] += delta;
+		}
+	}
+}
+
+/// Total damage dealt by each port (first index) to each port (second index).
+/// Every increase in a character's percent is attributed to its `last_hit_by` port;
+/// damage without a valid attacker counts as self-damage, on the diagonal. Note that
+/// some projectiles (e.g. Fox's laser) don't set `last_hit_by`, so they end up there too.
+/// Damage taken by followers (Nana) counts towards their leader's port.
+pub fn interaction_matrix(game:&Game) -> [[f32; NUM_PORTS]; NUM_PORTS] {
+	damage_matrix(game, true)
+}
+
+/// `interaction_matrix`, optionally ignoring damage taken by followers.
+pub(crate) fn damage_matrix(game:&Game, include_followers:bool) -> [[f32; NUM_PORTS]; NUM_PORTS] {
+	let mut matrix = [[0.0; NUM_PORTS]; NUM_PORTS];
+	for (victim, port) in game.ports.iter().enumerate() {
+		if let Some(port) = port {
+			add_damage_taken(&mut matrix, victim, &port.leader.post);
+			if let Some(follower) = port.follower.as_ref().filter(|_| include_followers) {
+				add_damage_taken(&mut matrix, victim, &follower.post);
+			}
+		}
+	}
+	matrix
+}
+
+/// Number of frames between consecutive samples in `lead_timeline`.
+pub const LEAD_SAMPLE_INTERVAL:usize = 60;
+
+/// A port, or in teams games a whole team.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+	Port(u8),
+	Team(TeamColor),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LeadPoint {
+	pub index: i32,
+	/// Who's ahead, or `None` if the top score is tied.
+	pub leader: Option<Side>,
+	/// Whether `leader` differs from the previous sample's.
+	pub lead_change: bool,
+	/// Whether the game's first stock was lost since the previous sample.
+	pub first_blood: bool,
+}
+
+/// How far ahead a character (or a side, summed over its characters) is. More stocks
+/// always wins; percent only breaks ties between equal stock counts, with lower
+/// percent ahead. These are compared as a pair rather than a weighted sum, so a
+/// team's combined percent can never outweigh a stock.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LeadScore {
+	pub stocks: u32,
+	pub damage: f32,
+}
+
+impl LeadScore {
+	/// Orders scores from furthest behind to furthest ahead.
+	pub fn cmp_lead(&self, other:&LeadScore) -> Ordering {
+		self.stocks.cmp(&other.stocks).then(other.damage.total_cmp(&self.damage))
+	}
+}
+
+impl AddAssign for LeadScore {
+	fn add_assign(&mut self, other:LeadScore) {
+		self.stocks += other.stocks;
+		self.damage += other.damage;
+	}
+}
+
+/// A character's `LeadScore` on one frame.
+pub fn lead_score(post:&Post) -> LeadScore {
+	LeadScore { stocks: post.stocks as u32, damage: post.damage }
+}
+
+/// Adds `score` to `side`'s running total.
+fn add_score(scores:&mut Vec<(Side, LeadScore)>, side:Side, score:LeadScore) {
+	match scores.iter_mut().find(|(s, _)| *s == side) {
+		Some((_, total)) => *total += score,
+		None => scores.push((side, score)),
+	}
+}
+
+/// The side with the highest score, or `None` if the top score is tied.
+fn sole_leader(scores:&[(Side, LeadScore)]) -> Option<Side> {
+	let best = scores.iter().map(|(_, score)| *score).max_by(|a, b| a.cmp_lead(b))?;
+	let mut leaders = scores.iter().filter(|(_, score)| score.cmp_lead(&best) == Ordering::Equal);
+	match (leaders.next(), leaders.next()) {
+		(Some((side, _)), None) => Some(*side),
+		_ => None,
+	}
+}
+
+fn side(game:&Game, port:usize) -> Side {
+	match game.start.players[port].as_ref().and_then(|p| p.team.as_ref()) {
+		Some(team) if game.start.is_teams => Side::Team(team.color),
+		_ => Side::Port(port as u8),
+	}
+}
+
+/// Samples who's ahead every `LEAD_SAMPLE_INTERVAL` frames, according to `lead_score`
+/// summed over each side's leaders.
+pub fn lead_timeline(game:&Game) -> Vec<LeadPoint> {
+	let ports:Vec<(Side, &Vec<Post>)> = game.ports.iter().enumerate()
+		.filter_map(|(i, p)| p.as_ref().map(|p| (side(game, i), &p.leader.post)))
+		.collect();
+	let len = ports.iter().map(|(_, post)| post.len()).min().unwrap_or(0);
+
+	let first_death = (0 .. NUM_PORTS as u8)
+		.filter_map(|port| game.deaths(port).first().map(|d| d.frame))
+		.min();
+
+	let mut timeline = Vec::new();
+	let mut prev_leader:Option<Side> = None;
+	let mut blooded = false;
+
+	for idx in (0 .. len).step_by(LEAD_SAMPLE_INTERVAL) {
+		let mut scores:Vec<(Side, LeadScore)> = Vec::new();
+		for (side, post) in &ports {
+			add_score(&mut scores, *side, lead_score(&post[idx]));
+		}
+		let leader = sole_leader(&scores);
+
+		let index = ports[0].1[idx].index;
+		let first_blood = !blooded && first_death.is_some_and(|f| f <= index);
+		blooded |= first_blood;
+
+		timeline.push(LeadPoint {
+			index,
+			leader,
+			lead_change: !timeline.is_empty() && leader != prev_leader,
+			first_blood,
+		});
+
+		prev_leader = leader;
+	}
+
+	timeline
+}
+
+/// Distance below which two players are considered to be at close range.
+pub const SPACING_CLOSE_RANGE:f32 = 25.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpacingStats {
+	pub mean: f32,
+	pub median: f32,
+	pub max: f32,
+	/// Fraction of frames where the distance was below `SPACING_CLOSE_RANGE`.
+	pub close_fraction: f32,
+	/// Number of frames considered.
+	pub frames: usize,
+}
+
+/// Distance between the leaders on ports `a` and `b`, over playable frames where both are alive.
+/// Frames with a non-finite position are skipped. Returns `None` if there are no such frames.
+pub fn spacing(game:&Game, a:u8, b:u8) -> Option<SpacingStats> {
+	let a = &game.ports.get(a as usize)?.as_ref()?.leader.post;
+	let b = &game.ports.get(b as usize)?.as_ref()?.leader.post;
+
+	let mut distances:Vec<f32> = a.iter().zip(b.iter())
+		.filter(|(a, b)| a.index >= FIRST_PLAYABLE_FRAME_INDEX && !is_dead(a.state) && !is_dead(b.state))
+		.map(|(a, b)| a.position.distance(&b.position))
+		// positions come straight from the replay, so don't trust them to be finite
+		.filter(|d| d.is_finite())
+		.collect();
+	if distances.is_empty() {
+		return None;
+	}
+
+	distances.sort_by(f32::total_cmp);
+	let frames = distances.len();
+	Some(SpacingStats {
+		mean: distances.iter().sum::<f32>() / frames as f32,
+		median: distances[frames / 2],
+		max: distances[frames - 1],
+		close_fraction: distances.iter().filter(|&&d| d < SPACING_CLOSE_RANGE).count() as f32 / frames as f32,
+		frames,
+	})
+}
+
+/// Number of stocks the player on `port` lost over the game, counted from their
+/// `starting_stocks` rather than assuming a 4-stock ruleset.
+pub fn stocks_lost(game:&Game, port:u8) -> Option<u8> {
+	let player = game.start.players.get(port as usize)?.as_ref()?;
+	let last = game.ports[port as usize].as_ref()?.leader.post.last()?;
+	Some(player.starting_stocks().saturating_sub(last.stocks))
+}
+
+/// Number of consecutive frames a victim must spend in control before a conversion ends.
+pub const CONVERSION_RESET_FRAMES:u32 = 45;
+
+/// A punish: a series of hits by one port on another, ending once the victim has been
+/// back in control for `CONVERSION_RESET_FRAMES` or loses a stock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Conversion {
+	pub attacker: u8,
+	pub victim: u8,
+	pub start_index: i32,
+	pub end_index: i32,
+	pub start_percent: f32,
+	pub end_percent: f32,
+	/// Number of frames on which the victim took damage.
+	pub hits: u32,
+	/// Whether the conversion ended with the victim losing a stock.
+	pub did_kill: bool,
+	/// Which of the victim's stocks this happened on (1 for the first), counted from
+	/// `Player::starting_stocks`.
+	pub stock: u8,
+	/// Whether this was a true combo: at least two hits, each landing before the
+	/// previous one's hitstun ran out (see `hitstun`). Always false for replays before
+	/// v2.0, which don't record hitstun.
+	pub is_true: bool,
+}
+
+impl Conversion {
+	pub fn damage(&self) -> f32 {
+		self.end_percent - self.start_percent
+	}
+}
+
+/// Whether the character is being hit, held, or is otherwise not in control.
+fn is_vulnerable(state:State) -> bool {
+	let s = match state {
+		State::Common(Common(s)) => s,
+		_ => return false,
+	};
+	s == Common::DAMAGE_FALL.0 ||
+		(Common::DAMAGE_HI_1.0 ..= Common::DAMAGE_FLY_ROLL.0).contains(&s) ||
+		s == Common::DAMAGE_SCREW.0 || s == Common::DAMAGE_SCREW_AIR.0 ||
+		(Common::DOWN_BOUND_U.0 ..= Common::PASSIVE_CEIL.0).contains(&s) ||
+		(Common::CAPTURE_PULLED_HI.0 ..= Common::CAPTURE_FOOT.0).contains(&s) ||
+		(Common::SHOULDERED_WAIT.0 ..= Common::THROWN_MEWTWO_AIR.0).contains(&s)
+}
+
+fn port_conversions(game:&Game, victim:u8, reset_frames:u32, out:&mut Vec<Conversion>) {
+	let post = match game.ports.get(victim as usize) {
+		Some(Some(p)) => &p.leader.post,
+		_ => return,
+	};
+	let deaths = game.deaths(victim);
+	let mut deaths = deaths.iter().peekable();
+	let mut current:Option<Conversion> = None;
+	let mut in_control = 0;
+
+	for w in post.windows(2) {
+		let (prev, p) = (&w[0], &w[1]);
+
+		if p.damage > prev.damage {
+			// hits without a valid attacker (e.g. some projectiles) extend the current conversion
+			if let Some(attacker) = p.last_hit_by_port().filter(|&a| a != victim) {
+				if current.is_some_and(|c| c.attacker != attacker) {
+					out.extend(current.take());
+				}
+				let c = current.get_or_insert(Conversion {
+					attacker,
+					victim,
+					start_index: p.index,
+					end_index: p.index,
+					start_percent: prev.damage,
+					end_percent: p.damage,
+					hits: 0,
+					did_kill: false,
+					stock: game.stock_number(victim, prev.stocks),
+					is_true: false,
+				});
+				c.hits += 1;
+			} else if let Some(c) = current.as_mut() {
+				c.hits += 1;
+			}
+			in_control = 0;
+		} else if is_vulnerable(p.state) {
+			in_control = 0;
+		} else {
+			in_control += 1;
+		}
+
+		let died = deaths.next_if(|d| d.frame == p.index).is_some();
+		if let Some(c) = current.as_mut() {
+			c.end_index = p.index;
+			if p.damage > c.end_percent {
+				c.end_percent = p.damage;
+			}
+			if died {
+				c.did_kill = true;
+				out.extend(current.take());
+			} else if in_control >= reset_frames {
+				out.extend(current.take());
+			}
+		}
+	}
+
+	out.extend(current);
+}
+
+/// Whether the hits taken from `start_index` to `end_index` (inclusive) were a true
+/// combo: at least two hits, each landing during the previous one's hitstun.
+fn is_true_combo(windows:&[HitstunWindow], start_index:i32, end_index:i32) -> bool {
+	let hits:Vec<&HitstunWindow> = windows.iter()
+		.filter(|w| (start_index ..= end_index).contains(&w.start_index))
+		.collect();
+	// the last hit has nothing after it, so it doesn't matter if it was escapable
+	hits.len() >= 2 && hits[.. hits.len() - 1].iter().all(|w| w.comboed)
+}
+
+/// Every conversion in the game, in order of victim port and then time. Only leaders
+/// are considered. Compute this once and pass it to `ConversionStats::new` for each port.
+pub fn conversions(game:&Game) -> Vec<Conversion> {
+	let mut out = Vec::new();
+	for (victim, port) in game.ports.iter().enumerate() {
+		if port.is_some() {
+			let first = out.len();
+			port_conversions(game, victim as u8, CONVERSION_RESET_FRAMES, &mut out);
+
+			let windows = hitstun(game, victim as u8);
+			for c in &mut out[first ..] {
+				c.is_true = is_true_combo(&windows, c.start_index, c.end_index);
+			}
+		}
+	}
+	out
+}
+
+/// Slippi's default for `combos`' `reset_frames`.
+pub const COMBO_RESET_FRAMES:u32 = 45;
+
+/// A string of hits by one port on another, like a `Conversion` but with the moves
+/// that landed and a tunable reset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Combo {
+	pub attacker: u8,
+	pub victim: u8,
+	pub start_index: i32,
+	pub end_index: i32,
+	pub start_percent: f32,
+	pub end_percent: f32,
+	/// The attacker's `last_attack_landed` for each hit that had one, in order.
+	pub moves: Vec<Attack>,
+	/// Whether the combo ended with the victim losing a stock.
+	pub did_kill: bool,
+	/// Which of the victim's stocks this happened on (1 for the first), counted from
+	/// `Player::starting_stocks`.
+	pub stock: u8,
+	/// Whether this was a true combo, as for `Conversion::is_true`.
+	pub is_true: bool,
+}
+
+impl Combo {
+	pub fn damage(&self) -> f32 {
+		self.end_percent - self.start_percent
+	}
+}
+
+/// Every combo by `port` (the attacker), in order. A combo ends when the victim loses
+/// a stock, is hit by someone else, or has been out of hitstun (and not held) for
+/// `reset_frames` consecutive frames; `COMBO_RESET_FRAMES` matches Slippi.
+pub fn combos(game:&Game, port:u8, reset_frames:u32) -> Vec<Combo> {
+	let attacker_post = match game.ports.get(port as usize) {
+		Some(Some(p)) => &p.leader.post,
+		_ => return vec![],
+	};
+
+	let mut combos = Vec::new();
+	for (victim, p) in game.ports.iter().enumerate() {
+		let post = match p {
+			Some(p) if victim != port as usize => &p.leader.post,
+			_ => continue,
+		};
+		let mut conversions = Vec::new();
+		port_conversions(game, victim as u8, reset_frames, &mut conversions);
+		let windows = hitstun(game, victim as u8);
+		for c in conversions.into_iter().filter(|c| c.attacker == port) {
+			// all ports' frames start on the same index, so positions line up
+			let moves = (1 .. post.len())
+				.filter(|&i| (c.start_index ..= c.end_index).contains(&post[i].index) && post[i].damage > post[i - 1].damage)
+				.filter_map(|i| attacker_post.get(i).and_then(|a| a.last_attack_landed))
+				.collect();
+			combos.push(Combo {
+				attacker: c.attacker,
+				victim: c.victim,
+				start_index: c.start_index,
+				end_index: c.end_index,
+				start_percent: c.start_percent,
+				end_percent: c.end_percent,
+				moves,
+				did_kill: c.did_kill,
+				stock: c.stock,
+				is_true: is_true_combo(&windows, c.start_index, c.end_index),
+			});
+		}
+	}
+	combos.sort_by_key(|c| c.start_index);
+	combos
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConversionStats {
+	/// Number of conversions started (openings).
+	pub openings: u32,
+	pub kills: u32,
+	pub total_damage: f32,
+	/// Mean damage dealt per opening.
+	pub damage_per_opening: f32,
+	/// Fraction of openings that ended in a kill.
+	pub kills_per_opening: f32,
+	/// Number of conversions (value) by number of hits (key).
+	pub lengths: std::collections::BTreeMap<u32, u32>,
+}
+
+impl ConversionStats {
+	/// Summarizes the conversions in which `attacker` is the attacker.
+	pub fn new(conversions:&[Conversion], attacker:u8) -> ConversionStats {
+		let mut stats = ConversionStats::default();
+		for c in conversions.iter().filter(|c| c.attacker == attacker) {
+			stats.openings += 1;
+			stats.kills += c.did_kill as u32;
+			stats.total_damage += c.damage();
+			*stats.lengths.entry(c.hits).or_insert(0) += 1;
+		}
+		if stats.openings > 0 {
+			stats.damage_per_opening = stats.total_damage / stats.openings as f32;
+			stats.kills_per_opening = stats.kills as f32 / stats.openings as f32;
+		}
+		stats
+	}
+}
+
+/// Punish efficiency for `port`: how much damage and how many kills it gets per opening.
+pub fn conversion_rate(game:&Game, port:u8) -> ConversionStats {
+	ConversionStats::new(&conversions(game), port)
+}
+
+/// Share of `efficiency_score` that comes from `Efficiency::neutral_win_ratio`.
+pub const EFFICIENCY_NEUTRAL_WEIGHT:f64 = 0.4;
+
+/// Share of `efficiency_score` that comes from `ConversionStats::damage_per_opening`.
+pub const EFFICIENCY_DAMAGE_WEIGHT:f64 = 0.3;
+
+/// Share of `efficiency_score` that comes from `ConversionStats::kills_per_opening`.
+pub const EFFICIENCY_KILL_WEIGHT:f64 = 0.3;
+
+/// Damage per opening that earns the full `EFFICIENCY_DAMAGE_WEIGHT`.
+pub const EFFICIENCY_DAMAGE_SCALE:f64 = 60.0;
+
+/// The inputs to `efficiency_score`, so the score can be explained.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Efficiency {
+	pub conversions: ConversionStats,
+	/// Fraction of openings between this port and its opponents that this port won.
+	pub neutral_win_ratio: f32,
+	/// Openings needed per kill, or `None` if there were no kills.
+	pub openings_per_kill: Option<f32>,
+	/// The weighted score, between 0 and 100.
+	pub score: f64,
+}
+
+impl Efficiency {
+	/// Summarizes `port`'s efficiency from `conversions` (see `conversions`).
+	pub fn new(conversions:&[Conversion], port:u8) -> Efficiency {
+		let stats = ConversionStats::new(conversions, port);
+		let lost = conversions.iter().filter(|c| c.victim == port).count() as u32;
+		let neutral_win_ratio = match stats.openings + lost {
+			0 => 0.0,
+			total => stats.openings as f32 / total as f32,
+		};
+		let openings_per_kill = match stats.kills {
+			0 => None,
+			kills => Some(stats.openings as f32 / kills as f32),
+		};
+		let score = 100.0 * (
+			EFFICIENCY_NEUTRAL_WEIGHT * neutral_win_ratio as f64 +
+			EFFICIENCY_DAMAGE_WEIGHT * (stats.damage_per_opening as f64 / EFFICIENCY_DAMAGE_SCALE).min(1.0) +
+			EFFICIENCY_KILL_WEIGHT * stats.kills_per_opening as f64);
+		Efficiency {
+			conversions: stats,
+			neutral_win_ratio,
+			openings_per_kill,
+			score,
+		}
+	}
+}
+
+/// How well `port` wins neutral and converts, as one number between 0 and 100:
+/// `EFFICIENCY_NEUTRAL_WEIGHT` for neutral-win ratio, plus `EFFICIENCY_DAMAGE_WEIGHT`
+/// for damage per opening (capped at `EFFICIENCY_DAMAGE_SCALE`), plus
+/// `EFFICIENCY_KILL_WEIGHT` for kills per opening (the inverse of openings per kill).
+/// Use `Efficiency::new` for the components.
+pub fn efficiency_score(game:&Game, port:u8) -> f64 {
+	Efficiency::new(&conversions(game), port).score
+}
+
+/// A lost stock, attributed to the hit that caused it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KillMove {
+	/// Frame on which the victim's stock count went down.
+	pub index: i32,
+	/// Frame of the last hit the victim took before dying, if any.
+	pub hit_index: Option<i32>,
+	/// Port credited with the kill, or `None` for a self-destruct.
+	pub killer: Option<u8>,
+	/// The killer's `last_attack_landed` as of `hit_index`. For multi-hit moves this
+	/// is the final hit.
+	pub attack: Option<Attack>,
+	/// Where the killer was as of `hit_index`.
+	pub killer_position: Option<Position>,
+	/// Which of the victim's stocks was lost. See `game::Death::stock`.
+	pub stock: u8,
+}
+
+/// Every stock lost by `port`, with the move and player that took it.
+pub fn kill_moves(game:&Game, port:u8) -> Vec<KillMove> {
+	let post = match game.ports.get(port as usize) {
+		Some(Some(p)) => &p.leader.post,
+		_ => return vec![],
+	};
+
+	let mut kills = Vec::new();
+	let mut stock_start = 0; // first frame of the current stock
+	for death in game.deaths(port) {
+		let idx = (death.frame - post[0].index) as usize;
+
+		// last hit taken during this stock
+		let hit = (stock_start + 1 ..= idx).rev()
+			.find(|&i| post[i].damage > post[i - 1].damage);
+		let killer = hit
+			.and_then(|i| post[i].last_hit_by_port())
+			.filter(|&k| k != port);
+		let killer_post = match (hit, killer) {
+			(Some(i), Some(k)) => game.ports[k as usize].as_ref()
+				.and_then(|p| p.leader.post.get(i)),
+			_ => None,
+		};
+
+		kills.push(KillMove {
+			index: death.frame,
+			hit_index: hit.map(|i| post[i].index),
+			killer: killer_post.and(killer),
+			attack: killer_post.and_then(|p| p.last_attack_landed),
+			killer_position: killer_post.map(|p| p.position),
+			stock: death.stock,
+		});
+		stock_start = idx;
+	}
+	kills
+}
+
+/// Physical trigger value at which a press counts as an action in `apm`.
+pub const APM_TRIGGER_THRESHOLD:f32 = 0.3;
+
+/// Physical buttons that count as actions in `apm`: everything but Start.
+const APM_BUTTONS:u16 = 0x0fff;
+
+/// Which of the 8 directions (or the deadzone, `(0, 0)`) a stick is in, as the sign
+/// of each axis. An axis counts as pushed past `STICK_DEADZONE`.
+fn stick_region(p:&Position) -> (i8, i8) {
+	let axis = |v:f32| if v >= STICK_DEADZONE { 1 } else if v <= -STICK_DEADZONE { -1 } else { 0 };
+	(axis(p.x), axis(p.y))
+}
+
+/// Actions per minute for `port`, matching Slippi Launcher's definition. Over playable
+/// frames (from `FIRST_PLAYABLE_FRAME_INDEX`), each of the following is one action:
+///
+/// - a physical button (other than Start) going from released to pressed;
+/// - the joystick or C-stick moving into a different region (one of the 8 directions
+///   past `STICK_DEADZONE`), other than back to neutral;
+/// - a physical L or R trigger value crossing `APM_TRIGGER_THRESHOLD` upwards.
+///
+/// The total is divided by the number of playable frames, in minutes.
+pub fn apm(game:&Game, port:u8) -> Option<f32> {
+	let pre = &game.ports.get(port as usize)?.as_ref()?.leader.pre;
+	let first = pre.iter().position(|f| f.index >= FIRST_PLAYABLE_FRAME_INDEX)?;
+	let pre = &pre[first ..];
+	if pre.len() < 2 {
+		return None;
+	}
+
+	let stick = |prev:&Position, cur:&Position| {
+		let region = stick_region(cur);
+		(region != stick_region(prev) && region != (0, 0)) as u32
+	};
+	let trigger = |prev:f32, cur:f32| (prev < APM_TRIGGER_THRESHOLD && cur >= APM_TRIGGER_THRESHOLD) as u32;
+
+	let actions:u32 = pre.windows(2).map(|w| {
+		let (prev, cur) = (&w[0], &w[1]);
+		(cur.buttons.physical.0 & !prev.buttons.physical.0 & APM_BUTTONS).count_ones() +
+			stick(&prev.joystick, &cur.joystick) +
+			stick(&prev.cstick, &cur.cstick) +
+			trigger(prev.triggers.physical.l, cur.triggers.physical.l) +
+			trigger(prev.triggers.physical.r, cur.triggers.physical.r)
+	}).sum();
+	let minutes = pre.len() as f32 / (60.0 * 60.0);
+	Some(actions as f32 / minutes)
+}
+
+/// The winning side (port, or team in teams games), or `None` for ties and games with
+/// no clear result. If a player quit out (LRAS), the other side wins if there's only
+/// one; otherwise the winner is whoever has the highest `lead_score` (summed over each
+/// side's leaders) on the last frame, so stocks count first and then lower percent.
+pub fn winning_side(game:&Game) -> Option<Side> {
+	let mut scores:Vec<(Side, LeadScore)> = Vec::new();
+	for (i, p) in game.ports.iter().enumerate() {
+		if let Some(post) = p.as_ref().and_then(|p| p.leader.post.last()) {
+			add_score(&mut scores, side(game, i), lead_score(post));
+		}
+	}
+
+	if let Some(lras) = game.end.lras_port() {
+		let quitter = side(game, lras as usize);
+		let mut others = scores.iter().filter(|(s, _)| *s != quitter);
+		return match (others.next(), others.next()) {
+			(Some((s, _)), None) => Some(*s),
+			_ => None,
+		};
+	}
+
+	sole_leader(&scores)
+}
+
+/// The winning port of a singles game, or `None` for teams games and whenever
+/// `winning_side` is `None`.
+pub fn winner(game:&Game) -> Option<u8> {
+	match winning_side(game) {
+		Some(Side::Port(port)) => Some(port),
+		_ => None,
+	}
+}
+
+/// Default joystick threshold (downward) for a shield drop, matching UCF's.
+pub const SHIELD_DROP_STICK_THRESHOLD:f32 = 0.6625;
+
+/// A drop through a platform directly out of shield. (`game::ShieldDrop` is the UCF
+/// setting that affects these.)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShieldDropEvent {
+	/// First frame of the platform drop (`PASS`).
+	pub index: i32,
+	/// Ground ID of the platform dropped through, for v2.0+ replays.
+	pub platform: Option<u16>,
+}
+
+fn is_shielding_post(post:&Post) -> bool {
+	is_shielding(post.state) ||
+		post.v0_2.as_ref().and_then(|v| v.v2_0.as_ref())
+			.is_some_and(|v| (v.flags & StateFlags::SHIELD).0 != 0)
+}
+
+/// `(index, joystick)` for every frame on which the leader on `port` was in hitstun
+/// (`StateFlags::HIT_STUN`), for judging DI. Empty before v2.0, which has no flags.
+pub fn di_inputs(game:&Game, port:u8) -> Vec<(i32, Position)> {
+	let frames = match game.ports.get(port as usize) {
+		Some(Some(p)) => &p.leader,
+		_ => return vec![],
+	};
+	frames.pre.iter().zip(frames.post.iter())
+		.filter(|(pre, post)| pre.index == post.index)
+		.filter(|(_, post)| post.v0_2.as_ref().and_then(|v| v.v2_0.as_ref())
+			.is_some_and(|v| v.flags.contains(StateFlags::HIT_STUN)))
+		.map(|(pre, post)| (post.index, pre.joystick))
+		.collect()
+}
+
+/// Shield drops by `port` using `SHIELD_DROP_STICK_THRESHOLD`.
+pub fn shield_drops(game:&Game, port:u8) -> Vec<ShieldDropEvent> {
+	shield_drops_with_threshold(game, port, SHIELD_DROP_STICK_THRESHOLD)
+}
+
+/// Shield drops by `port`: frames where the character goes straight from shielding
+/// to dropping through a platform, with the joystick at least `threshold` down.
+pub fn shield_drops_with_threshold(game:&Game, port:u8, threshold:f32) -> Vec<ShieldDropEvent> {
+	let frames = match game.ports.get(port as usize) {
+		Some(Some(p)) => &p.leader,
+		_ => return vec![],
+	};
+
+	frames.post.windows(2).zip(frames.pre.iter().skip(1))
+		.filter(|(post, pre)|
+			post[1].state == State::Common(Common::PASS) &&
+			is_shielding_post(&post[0]) &&
+			pre.joystick.y <= -threshold)
+		.map(|(post, _)| ShieldDropEvent {
+			index: post[1].index,
+			platform: post[0].v0_2.as_ref().and_then(|v| v.v2_0.as_ref()).map(|v| v.ground),
+		})
+		.collect()
+}
+
+/// Multishine execution for Fox and Falco.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ExecutionStats {
+	/// Number of multishine sequences (at least two shines linked by jump cancels).
+	pub multishines: u32,
+	/// Total shines across all multishine sequences.
+	pub multishine_shines: u32,
+	/// Fewest frames between consecutive shines in a multishine.
+	pub fastest_cadence: Option<u32>,
+	/// Mean frames between consecutive shines in a multishine.
+	pub average_cadence: Option<f32>,
+}
+
+fn is_shine_start(state:State) -> bool {
+	matches!(state,
+		State::Fox(Fox::REFLECTOR_GROUND_STARTUP) | State::Fox(Fox::REFLECTOR_AIR_STARTUP) |
+		State::Falco(Falco::REFLECTOR_GROUND_STARTUP) | State::Falco(Falco::REFLECTOR_AIR_STARTUP))
+}
+
+fn is_shine(state:State) -> bool {
+	match state {
+		State::Fox(Fox(s)) => (Fox::REFLECTOR_GROUND_STARTUP.0 ..= Fox::REFLECTOR_AIR_CHANGE_DIRECTION.0).contains(&s),
+		State::Falco(Falco(s)) => (Falco::REFLECTOR_GROUND_STARTUP.0 ..= Falco::REFLECTOR_AIR_CHANGE_DIRECTION.0).contains(&s),
+		_ => false,
+	}
+}
+
+/// States allowed between two shines for them to count as one multishine.
+fn is_multishine_link(state:State) -> bool {
+	is_shine(state) || matches!(state,
+		State::Common(Common::KNEE_BEND) | State::Common(Common::JUMP_F) | State::Common(Common::JUMP_B))
+}
+
+fn end_multishine(stats:&mut ExecutionStats, sequence:&mut u32) {
+	if *sequence >= 2 {
+		stats.multishines += 1;
+		stats.multishine_shines += *sequence;
+	}
+	*sequence = 0;
+}
+
+/// Multishine cadence for `port`'s leader. A multishine is a run of shines where each
+/// one is jump-canceled (`KNEE_BEND`) straight into the next, with nothing but shine
+/// and jump states in between. Cadence is the number of frames from one shine's first
+/// frame to the next's.
+pub fn execution(game:&Game, port:u8) -> ExecutionStats {
+	let post = match game.ports.get(port as usize) {
+		Some(Some(p)) => &p.leader.post,
+		_ => return ExecutionStats::default(),
+	};
+
+	let mut stats = ExecutionStats::default();
+	let mut cadences:Vec<u32> = Vec::new();
+	let mut last_shine:Option<usize> = None; // start of the previous shine
+	let mut jumped = false; // whether we've been in jumpsquat since `last_shine`
+	let mut sequence = 0; // shines in the current sequence
+
+	for idx in 0 .. post.len() {
+		let state = post[idx].state;
+		let starts = is_shine_start(state) && (idx == 0 || post[idx - 1].state != state);
+		if starts {
+			match last_shine {
+				Some(last) if jumped => {
+					cadences.push((idx - last) as u32);
+					sequence += 1;
+				},
+				_ => {
+					end_multishine(&mut stats, &mut sequence);
+					sequence = 1;
+				},
+			}
+			last_shine = Some(idx);
+			jumped = false;
+		} else if last_shine.is_some() {
+			if state == State::Common(Common::KNEE_BEND) {
+				jumped = true;
+			} else if !is_multishine_link(state) {
+				end_multishine(&mut stats, &mut sequence);
+				last_shine = None;
+			}
+		}
+	}
+	end_multishine(&mut stats, &mut sequence);
+
+	if !cadences.is_empty() {
+		stats.fastest_cadence = cadences.iter().min().copied();
+		stats.average_cadence = Some(cadences.iter().sum::<u32>() as f32 / cadences.len() as f32);
+	}
+	stats
+}
+
+/// Fraction of `port`'s L-cancel attempts that succeeded, counting every frame where
+/// `l_cancel` is set. `None` before v2.0 (which has no L-cancel data), or if there
+/// were no aerial landings to L-cancel.
+pub fn l_cancel_rate(game:&Game, port:u8) -> Option<f64> {
+	let post = &game.ports.get(port as usize)?.as_ref()?.leader.post;
+	let mut attempts = 0;
+	let mut successes = 0;
+	for p in post {
+		let v2_0 = p.v0_2.as_ref()?.v2_0.as_ref()?;
+		if let Some(l_cancel) = v2_0.l_cancel {
+			attempts += 1;
+			if l_cancel == LCancel::SUCCESSFUL {
+				successes += 1;
+			}
+		}
+	}
+	match attempts {
+		0 => None,
+		_ => Some(successes as f64 / attempts as f64),
+	}
+}
+
+/// Number of frames in `range` (by frame index) on which the leader on `port` had
+/// hurtbox state `state`. `None` before v2.1, which doesn't record hurtbox state.
+pub fn hurtbox_frames_in(game:&Game, port:u8, state:HurtboxState, range:std::ops::Range<i32>) -> Option<u32> {
+	if !game.capabilities().has_hurtbox {
+		return None;
+	}
+	let post = &game.ports.get(port as usize)?.as_ref()?.leader.post;
+	Some(post.iter()
+		.filter(|p| range.contains(&p.index))
+		.filter(|p| p.v0_2.as_ref()
+			.and_then(|v| v.v2_0.as_ref())
+			.and_then(|v| v.v2_1.as_ref())
+			.is_some_and(|v| v.hurtbox_state == state))
+		.count() as u32)
+}
+
+/// Number of frames `port` spent intangible (e.g. during a ledgedash's GALINT).
+/// See `hurtbox_frames_in` to count within a frame range.
+pub fn intangible_frames(game:&Game, port:u8) -> Option<u32> {
+	hurtbox_frames_in(game, port, HurtboxState::INTANGIBLE, game.frame_indexes())
+}
+
+/// Number of frames `port` spent invulnerable. See `hurtbox_frames_in` to count
+/// within a frame range.
+pub fn invulnerable_frames(game:&Game, port:u8) -> Option<u32> {
+	hurtbox_frames_in(game, port, HurtboxState::INVULNERABLE, game.frame_indexes())
+}
+
+/// How much of a character's per-frame stage control comes from being near center,
+/// as opposed to simply being on stage. Between 0 and 1.
+pub const STAGE_CONTROL_CENTER_WEIGHT:f32 = 0.5;
+
+/// Number of trailing frames averaged into each `ControlPoint`.
+pub const STAGE_CONTROL_WINDOW:usize = 30;
+
+/// How far a port's control must exceed everyone else's for it to be the `leader`.
+pub const STAGE_CONTROL_MARGIN:f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ControlPoint {
+	pub index: i32,
+	/// Each port's stage control between 0 and 1, averaged over the last
+	/// `STAGE_CONTROL_WINDOW` frames. `None` for empty ports.
+	pub control: [Option<f32>; NUM_PORTS],
+	/// Port with the most control, if it leads by at least `STAGE_CONTROL_MARGIN`.
+	pub leader: Option<u8>,
+}
+
+/// Per-frame stage control for one character: 0 when dead or off stage, otherwise
+/// `1 - STAGE_CONTROL_CENTER_WEIGHT` for being on stage plus up to
+/// `STAGE_CONTROL_CENTER_WEIGHT` for closeness to center.
+fn frame_control(post:&Post, stage:Stage, edge:f32) -> f32 {
+	if is_dead(post.state) || stage.is_offstage(&post.position) != Some(false) {
+		0.0
+	} else {
+		let centrality = 1.0 - post.position.x.abs() / edge;
+		(1.0 - STAGE_CONTROL_CENTER_WEIGHT) + STAGE_CONTROL_CENTER_WEIGHT * centrality
+	}
+}
+
+/// Which leader holds center stage, for every frame. A rough neutral-control signal:
+/// see `frame_control` and the `STAGE_CONTROL_*` constants for the heuristic.
+/// Empty if the stage has no known geometry (see `Stage::edge_x`).
+pub fn stage_control(game:&Game) -> Vec<ControlPoint> {
+	let edge = match game.start.stage.edge_x() {
+		Some(edge) => edge,
+		None => return Vec::new(),
+	};
+
+	let posts:Vec<Option<&Vec<Post>>> = game.ports.iter().map(|p| p.as_ref().map(|p| &p.leader.post)).collect();
+	let len = posts.iter().flatten().map(|post| post.len()).min().unwrap_or(0);
+
+	// prefix sums of per-frame control, for the rolling average
+	let sums:Vec<Option<Vec<f32>>> = posts.iter().map(|post| post.map(|post| {
+		let mut sums = vec![0.0];
+		for p in &post[.. len] {
+			sums.push(sums[sums.len() - 1] + frame_control(p, game.start.stage, edge));
+		}
+		sums
+	})).collect();
+
+	let first = match posts.iter().flatten().next() {
+		Some(post) => post,
+		None => return Vec::new(),
+	};
+
+	(0 .. len).map(|idx| {
+		let start = (idx + 1).saturating_sub(STAGE_CONTROL_WINDOW);
+		let mut control = [None; NUM_PORTS];
+		for (port, sums) in sums.iter().enumerate() {
+			control[port] = sums.as_ref().map(|s| (s[idx + 1] - s[start]) / (idx + 1 - start) as f32);
+		}
+
+		let leader = control.iter().enumerate()
+			.filter_map(|(port, c)| c.map(|c| (port, c)))
+			.fold(None, |best:Option<(usize, f32)>, (port, c)| match best {
+				Some((_, b)) if b >= c => best,
+				_ => Some((port, c)),
+			})
+			.filter(|&(port, c)| control.iter().enumerate()
+				.all(|(p, other)| p == port || other.is_none_or(|o| c - o >= STAGE_CONTROL_MARGIN)))
+			.map(|(port, _)| port as u8);
+
+		ControlPoint {
+			index: first[idx].index,
+			control,
+			leader,
+		}
+	}).collect()
+}
+
+/// The hitstun from one hit taken.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HitstunWindow {
+	/// Frame on which the hit landed (the victim's damage went up).
+	pub start_index: i32,
+	/// Last frame of hitstun, or of the window if the next hit cut it short.
+	/// Equal to `start_index` when hitstun isn't known.
+	pub end_index: i32,
+	/// Hitstun the hit caused, in frames (not counting hitlag).
+	/// `None` for replays before v2.0, which don't record it.
+	pub frames: Option<u32>,
+	/// Whether the next hit landed while the victim was still in hitstun, i.e. the
+	/// two hits were a true combo (DI and SDI aside).
+	pub comboed: bool,
+}
+
+fn hitstun_remaining(post:&Post) -> Option<f32> {
+	match post.misc_as_interpreted() {
+		Some(MiscAs::HitstunRemaining(h)) if h > 0.0 => Some(h),
+		_ => None,
+	}
+}
+
+/// Every hit taken by `port`, with the hitstun it caused. Hitstun is read from
+/// `misc_as`, so on replays before v2.0 only the hits themselves are reported.
+/// Knockback velocities aren't recorded in any version this parser supports.
+pub fn hitstun(game:&Game, port:u8) -> Vec<HitstunWindow> {
+	let post = match game.ports.get(port as usize) {
+		Some(Some(p)) => &p.leader.post,
+		_ => return vec![],
+	};
+
+	let hits:Vec<usize> = (1 .. post.len()).filter(|&i| post[i].damage > post[i - 1].damage).collect();
+	hits.iter().enumerate().map(|(n, &start)| {
+		let next_hit = hits.get(n + 1).copied().unwrap_or(post.len());
+		let mut window = HitstunWindow {
+			start_index: post[start].index,
+			end_index: post[start].index,
+			frames: None,
+			comboed: false,
+		};
+
+		let mut end = start;
+		while end < next_hit {
+			match hitstun_remaining(&post[end]) {
+				Some(h) => {
+					let h = h.ceil() as u32;
+					window.frames = Some(window.frames.map_or(h, |f| f.max(h)));
+					window.end_index = post[end].index;
+				},
+				None if end > start => break,
+				None => (),
+			}
+			end += 1;
+		}
+		window.comboed = end == next_hit && next_hit < post.len() && window.frames.is_some();
+		window
+	}).collect()
+}
@@ -0,0 +1,107 @@
+//! Writes a replay back out as it's being parsed, without building a `Game`.
+
+use std::collections::HashMap;
+use std::io::{Result, Seek, SeekFrom, Write};
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use super::parse::{self, Handlers, METADATA_KEY, PAYLOADS_EVENT_CODE, RAW_HEADER};
+use super::ubjson;
+
+/// A `Handlers` implementation that re-encodes every event to `w` as it arrives, so
+/// replays can be copied or filtered in constant memory. Wrap it (forwarding only
+/// the events you want to keep) to filter.
+///
+/// The `raw` element's length comes before the events, so it's written as 0, which
+/// readers treat as an in-progress replay. Call `finalize` on a seekable output to
+/// fill in the real length. With nothing filtered, the output is byte-identical to
+/// replays written by Slippi, but not necessarily to other producers': Event Payloads
+/// entries are written in event code order, and metadata values are re-encoded as
+/// described in `ubjson::write_map` (only key order is kept).
+#[derive(Debug)]
+pub struct StreamingWriter<W:Write> {
+	w: W,
+	/// Bytes written so far, in total and within the `raw` element.
+	written: u64,
+	raw_len: u32,
+}
+
+impl<W:Write> StreamingWriter<W> {
+	pub fn new(w:W) -> StreamingWriter<W> {
+		StreamingWriter { w, written: 0, raw_len: 0 }
+	}
+
+	fn write(&mut self, bytes:&[u8]) -> Result<()> {
+		self.w.write_all(bytes)?;
+		self.written += bytes.len() as u64;
+		Ok(())
+	}
+
+	fn write_raw(&mut self, bytes:&[u8]) -> Result<()> {
+		self.write(bytes)?;
+		self.raw_len += bytes.len() as u32;
+		Ok(())
+	}
+
+	/// Length in bytes of the `raw` element written so far.
+	pub fn raw_len(&self) -> u32 {
+		self.raw_len
+	}
+
+	/// Returns the output, leaving the `raw` length as 0.
+	pub fn into_inner(self) -> W {
+		self.w
+	}
+}
+
+impl<W:Write + Seek> StreamingWriter<W> {
+	/// Patches the real `raw` length into the header and returns the output,
+	/// positioned at the end of the replay.
+	pub fn finalize(mut self) -> Result<W> {
+		self.w.flush()?;
+		let end = self.w.stream_position()?;
+		let start = end.checked_sub(self.written)
+			.ok_or_else(|| err!("output is shorter than what was written"))?;
+		self.w.seek(SeekFrom::Start(start + RAW_HEADER.len() as u64))?;
+		self.w.write_u32::<BigEndian>(self.raw_len)?;
+		self.w.seek(SeekFrom::Start(end))?;
+		Ok(self.w)
+	}
+}
+
+impl<W:Write> Handlers for StreamingWriter<W> {
+	fn payload_sizes(&mut self, sizes:&HashMap<u8, u16>) -> Result<()> {
+		self.write(&RAW_HEADER)?;
+		self.write(&[0; 4])?; // `raw` length, unknown until the end
+
+		let mut sizes:Vec<(u8, u16)> = sizes.iter().map(|(&code, &size)| (code, size)).collect();
+		sizes.sort();
+		// the size includes itself, and each entry is a code and a u16
+		let mut payloads = vec![PAYLOADS_EVENT_CODE, (1 + 3 * sizes.len()) as u8];
+		for (code, size) in sizes {
+			payloads.push(code);
+			payloads.extend_from_slice(&size.to_be_bytes());
+		}
+		self.write_raw(&payloads)
+	}
+
+	fn raw_event(&mut self, code:u8, payload:&[u8]) -> Result<()> {
+		self.write_raw(&[code])?;
+		self.write_raw(payload)
+	}
+
+	fn metadata(&mut self, metadata:ubjson::Map) -> Result<()> {
+		let mut buf = METADATA_KEY.to_vec();
+		ubjson::write_map(&mut buf, &metadata)?;
+		buf.push(0x7d); // top-level closing brace ("}")
+		self.write(&buf)
+	}
+}
+
+/// Parses the replay in `r` and writes it straight back out to `w` with a
+/// `StreamingWriter`, patching in the `raw` length at the end.
+pub fn copy<R:std::io::Read, W:Write + Seek>(r:R, w:W) -> Result<W> {
+	let mut writer = StreamingWriter::new(w);
+	parse::parse(r, &mut writer)?;
+	writer.finalize()
+}
@@ -0,0 +1,43 @@
+use std::path;
+
+use clap::{App, Arg};
+
+use log::{error, warn};
+
+use peppi::summary;
+
+/// Prints a CSV row for each replay in `dir`, in path order. Replays are parsed in
+/// parallel if the `rayon` feature is on.
+fn summarize(dir:&path::Path, opts:&peppi::ExportOptions) -> Result<(), String> {
+	let games = peppi::read_dir(dir, &Default::default(), true).map_err(|e| format!("{:?}", e))?;
+
+	println!("{}", summary::csv_header());
+	for (path, game) in games {
+		match game {
+			Ok(game) => println!("{}", summary::csv_row_opts(&path.to_string_lossy(), &game, opts)),
+			Err(e) => warn!("skipping {}: {:?}", path.display(), e),
+		}
+	}
+	Ok(())
+}
+
+fn main() {
+	pretty_env_logger::init();
+
+	let matches = App::new("Peppi summarizer")
+		.version("0.1")
+		.about("Writes a CSV summary of every Slippi replay in a directory")
+		.arg(Arg::with_name("DIR")
+			.help("Directory of replays")
+			.required(true)
+			.index(1))
+		.arg(Arg::with_name("no-followers")
+			.help("Don't count follower (Nana) data")
+			.long("no-followers"))
+		.get_matches();
+
+	let opts = peppi::ExportOptions { include_followers: !matches.is_present("no-followers") };
+	if let Err(e) = summarize(path::Path::new(matches.value_of("DIR").unwrap()), &opts) {
+		error!("{}", e);
+	}
+}
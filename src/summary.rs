@@ -0,0 +1,68 @@
+//! One-line-per-replay CSV summaries, for spreadsheets and ladder/tournament analysis.
+
+use std::fmt::Write;
+
+use super::game::{Game, NUM_PORTS};
+use super::stats;
+use super::ExportOptions;
+
+/// `name_tag` is the in-game tag from local play; `netplay_name` is the netplay
+/// display name from the metadata. They're separate fields, and either may be blank.
+const PLAYER_COLUMNS:[&str; 6] = ["character", "name_tag", "netplay_name", "apm", "damage", "deaths"];
+
+/// Header row matching `csv_row`. Ports are numbered from 1, as in-game.
+pub fn csv_header() -> String {
+	let mut s = String::from("file,date,stage,winner");
+	for port in 1 ..= NUM_PORTS {
+		for col in &PLAYER_COLUMNS {
+			write!(s, ",p{}_{}", port, col).unwrap();
+		}
+	}
+	s
+}
+
+fn escape(s:&str) -> String {
+	match s.contains(&[',', '"', '\n'][..]) {
+		true => format!("\"{}\"", s.replace('"', "\"\"")),
+		_ => s.to_string(),
+	}
+}
+
+/// Summary of `game` (read from `file`) as a CSV row. Columns for empty ports are blank.
+pub fn csv_row(file:&str, game:&Game) -> String {
+	csv_row_opts(file, game, &ExportOptions::default())
+}
+
+/// Like `csv_row`, but with non-default `opts`. Without followers, damage that Nana
+/// takes isn't counted towards the `damage` column. The columns are the same either way.
+pub fn csv_row_opts(file:&str, game:&Game, opts:&ExportOptions) -> String {
+	let matrix = stats::damage_matrix(game, opts.include_followers);
+	let mut s = String::new();
+
+	// writing to a `String` can't fail
+	write!(s, "{},{},{},{}",
+		escape(file),
+		game.metadata.date.map_or_else(String::new, |d| d.to_rfc3339()),
+		game.start.stage.name().map_or_else(|| game.start.stage.0.to_string(), |n| n.to_string()),
+		stats::winner(game).map_or_else(String::new, |w| (w + 1).to_string())).unwrap();
+
+	for (port, (player, dealt)) in game.start.players.iter().zip(matrix.iter()).enumerate() {
+		match player {
+			Some(player) => {
+				let damage:f32 = dealt.iter().enumerate().filter(|(v, _)| *v != port).map(|(_, d)| d).sum();
+				let netplay_name = game.metadata.players.as_ref()
+					.and_then(|p| p[port].as_ref())
+					.and_then(|p| p.netplay_name.as_deref());
+				write!(s, ",{},{},{},{},{:.1},{}",
+					player.character.name().map_or_else(|| player.character.0.to_string(), |n| n.to_string()),
+					escape(player.name_tag().unwrap_or("")),
+					escape(netplay_name.unwrap_or("")),
+					stats::apm(game, port as u8).map_or_else(String::new, |a| format!("{:.1}", a)),
+					damage,
+					stats::stocks_lost(game, port as u8).map_or_else(String::new, |d| d.to_string())).unwrap();
+			},
+			None => s.push_str(&",".repeat(PLAYER_COLUMNS.len())),
+		}
+	}
+	s
+}
@@ -0,0 +1,185 @@
+//! Minimal reader for (ustar/GNU/pax) tar archives, enough to pull replays out of one
+//! without extracting to disk. Only regular files are returned; links and directories
+//! are skipped. Long names from GNU `L` entries and pax `path` records are applied
+//! to the member that follows them; other extended header fields are ignored.
+
+use std::io::{self, Read, Result};
+
+const BLOCK_SIZE:usize = 512;
+
+/// A regular file in a tar archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Member {
+	/// Path within the archive, including any ustar prefix.
+	pub name: String,
+	pub data: Vec<u8>,
+}
+
+fn field_str(bytes:&[u8]) -> String {
+	let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+	String::from_utf8_lossy(&bytes[.. end]).into_owned()
+}
+
+fn field_octal(bytes:&[u8]) -> Result<u64> {
+	let s = field_str(bytes);
+	let s = s.trim_matches(|c:char| c == ' ' || c == '\0');
+	match s {
+		"" => Ok(0),
+		s => u64::from_str_radix(s, 8).map_err(|e| err!("tar: bad octal field {:?}: {}", s, e)),
+	}
+}
+
+/// The `path` record from pax extended header data, if any. Records are
+/// `"<length> <key>=<value>\n"`, where the length counts the whole record.
+fn pax_path(data:&[u8]) -> Result<Option<String>> {
+	let mut path = None;
+	let mut rest = data;
+	while !rest.is_empty() {
+		let space = rest.iter().position(|&b| b == b' ')
+			.ok_or_else(|| err!("tar: bad pax record: {:?}", String::from_utf8_lossy(rest)))?;
+		let len:usize = std::str::from_utf8(&rest[.. space]).ok()
+			.and_then(|s| s.parse().ok())
+			.filter(|&len| len > space + 1 && len <= rest.len())
+			.ok_or_else(|| err!("tar: bad pax record length: {:?}", String::from_utf8_lossy(&rest[.. space])))?;
+		let record = &rest[space + 1 .. len];
+		let record = record.strip_suffix(b"\n").unwrap_or(record);
+		if let Some(value) = record.strip_prefix(b"path=") {
+			path = Some(String::from_utf8_lossy(value).into_owned());
+		}
+		rest = &rest[len ..];
+	}
+	Ok(path)
+}
+
+/// Checks the header checksum: the sum of all header bytes, with the checksum
+/// field itself counted as spaces. Some old writers summed signed bytes.
+fn check_checksum(header:&[u8; BLOCK_SIZE]) -> Result<()> {
+	let expected = field_octal(&header[148 .. 156])?;
+	let (unsigned, signed) = header.iter().enumerate().fold((0u64, 0i64), |(u, s), (i, &b)| {
+		let b = if (148 .. 156).contains(&i) { b' ' } else { b };
+		(u + b as u64, s + b as i8 as i64)
+	});
+	match expected == unsigned || expected as i64 == signed {
+		true => Ok(()),
+		false => Err(err!("tar: bad header checksum: expected {}, got {}", expected, unsigned)),
+	}
+}
+
+/// Streams the regular files in a tar archive, reading one header at a time.
+/// Each member's data is only read (or skipped) on demand, so archives can be
+/// searched without buffering members that aren't wanted.
+pub struct Reader<R> {
+	r: R,
+	/// Unread bytes of the current member's data.
+	remaining: u64,
+	/// Padding after the current member's data, up to the next block.
+	padding: u64,
+	done: bool,
+}
+
+impl<R:Read> Reader<R> {
+	pub fn new(r:R) -> Self {
+		Reader { r, remaining: 0, padding: 0, done: false }
+	}
+
+	fn skip(&mut self, n:u64) -> Result<()> {
+		let skipped = io::copy(&mut self.r.by_ref().take(n), &mut io::sink())?;
+		match skipped == n {
+			true => Ok(()),
+			false => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "tar: truncated member")),
+		}
+	}
+
+	/// Advances to the next regular file, skipping whatever is left of the
+	/// current one and any other entry types. Returns its name and declared size,
+	/// or `None` at the end of the archive.
+	pub fn next_member(&mut self) -> Result<Option<(String, u64)>> {
+		self.skip(self.remaining + self.padding)?;
+		self.remaining = 0;
+		self.padding = 0;
+
+		let mut header = [0; BLOCK_SIZE];
+		// from a preceding GNU long name or pax extended header
+		let mut long_name = None;
+		while !self.done {
+			match self.r.read_exact(&mut header) {
+				Ok(()) => (),
+				// some writers omit the trailing zero blocks
+				Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => { self.done = true; break; },
+				Err(e) => return Err(e),
+			}
+			if header.iter().all(|&b| b == 0) {
+				self.done = true;
+				break;
+			}
+			check_checksum(&header)?;
+
+			let size = field_octal(&header[124 .. 136])?;
+			let padding = (BLOCK_SIZE as u64 - size % BLOCK_SIZE as u64) % BLOCK_SIZE as u64;
+
+			match header[156] {
+				// '0' (or NUL, pre-POSIX) is a regular file
+				b'0' | 0 => {
+					let name = long_name.take().unwrap_or_else(|| {
+						let name = field_str(&header[0 .. 100]);
+						match &header[257 .. 262] {
+							b"ustar" => match field_str(&header[345 .. 500]) {
+								prefix if prefix.is_empty() => name,
+								prefix => format!("{}/{}", prefix, name),
+							},
+							_ => name,
+						}
+					});
+					self.remaining = size;
+					self.padding = padding;
+					return Ok(Some((name, size)));
+				},
+				// GNU long name: the data is the next entry's name
+				b'L' => {
+					self.remaining = size;
+					let data = self.read_member()?;
+					long_name = Some(field_str(&data));
+					self.skip(padding)?;
+				},
+				// pax extended header for the next entry
+				b'x' => {
+					self.remaining = size;
+					let data = self.read_member()?;
+					if let Some(path) = pax_path(&data)? {
+						long_name = Some(path);
+					}
+					self.skip(padding)?;
+				},
+				_ => {
+					long_name = None;
+					self.skip(size + padding)?;
+				},
+			}
+		}
+		Ok(None)
+	}
+
+	/// Reads the data of the member returned by the last `next_member`. The
+	/// buffer grows as data arrives, so a corrupt size can't force a huge
+	/// allocation up front.
+	pub fn read_member(&mut self) -> Result<Vec<u8>> {
+		let size = self.remaining;
+		let mut data = Vec::new();
+		self.r.by_ref().take(size).read_to_end(&mut data)?;
+		self.remaining = 0;
+		match data.len() as u64 == size {
+			true => Ok(data),
+			false => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "tar: truncated member")),
+		}
+	}
+}
+
+/// Reads every regular file in the tar archive `r`, in archive order.
+pub fn members<R:Read>(r:R) -> Result<Vec<Member>> {
+	let mut reader = Reader::new(r);
+	let mut members = Vec::new();
+	while let Some((name, _)) = reader.next_member()? {
+		members.push(Member { name, data: reader.read_member()? });
+	}
+	Ok(members)
+}
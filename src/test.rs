@@ -1,20 +1,20 @@
 use std::collections::{HashMap};
-use std::path;
+use std::{fs, io, path};
 
 use chrono::{DateTime, Utc};
 
-use super::action_state::{State, Zelda};
+use super::action_state::{Common, State, Zelda};
 use super::buttons::{Logical, Physical};
 use super::character::{Internal, External};
-use super::frame::{Buttons};
-use super::game::{DashBack, Game, End, EndMethod, Start, Player, PlayerType, PlayerV1_0, ShieldDrop, Slippi, SlippiVersion, Ucf};
-use super::metadata::{Metadata, MetadataPlayer};
+use super::frame::{Buttons, Position};
+use super::game::{DashBack, Game, End, EndMethod, Start, Player, PlayerType, PlayerV1_0, PortState, ShieldDrop, Slippi, SlippiVersion, Ucf};
+use super::metadata::{Metadata, MetadataPlayer, Platform};
 use super::stage::{Stage};
 use super::ubjson::{ToObject};
 
 macro_rules! map {
 	{ $($key:expr => $value:expr),* $(,)? } => {{
-		let mut m = std::collections::HashMap::new();
+		let mut m = super::ubjson::Map::new();
 		$( m.insert($key.to_string(), $value.to_object()); )+
 		m
 	}}
@@ -60,7 +60,7 @@ fn basic_game() -> Result<(), String> {
 	assert_eq!(game.metadata, Metadata {
 		date: "2018-06-22T07:52:59Z".parse::<DateTime<Utc>>().ok(),
 		duration: Some(5209),
-		platform: Some("dolphin".to_string()),
+		platform: Some(Platform::Dolphin),
 		players: Some([
 			Some(MetadataPlayer {
 				characters: {
@@ -69,6 +69,7 @@ fn basic_game() -> Result<(), String> {
 					Some(m)
 				},
 				netplay_name: None,
+				netplay_code: None,
 			}),
 			Some(MetadataPlayer {
 				characters: {
@@ -77,6 +78,7 @@ fn basic_game() -> Result<(), String> {
 					Some(m)
 				},
 				netplay_name: None,
+				netplay_code: None,
 			}),
 			None,
 			None,
@@ -102,7 +104,7 @@ fn basic_game() -> Result<(), String> {
 	});
 
 	assert_eq!(game.start, Start {
-		slippi: Slippi { version: SlippiVersion(1, 0, 0) },
+		slippi: Slippi { version: SlippiVersion(1, 0, 0), unused_version_byte: 0 },
 		bitfield: [50, 1, 76],
 		is_teams: false,
 		item_spawn_frequency: -1,
@@ -183,6 +185,7 @@ fn ics() -> Result<(), String> {
 				m
 			}),
 			netplay_name: None,
+			netplay_code: None,
 		}),
 		Some(MetadataPlayer {
 			characters: Some({
@@ -191,6 +194,7 @@ fn ics() -> Result<(), String> {
 				m
 			}),
 			netplay_name: None,
+			netplay_code: None,
 		}),
 		None,
 		None,
@@ -241,6 +245,42 @@ fn buttons_lzrs() -> Result<(), String> {
 	Ok(())
 }
 
+#[test]
+fn buttons_pressed() -> Result<(), String> {
+	let game = game("buttons_lrzs")?;
+	let az = button_seq(&game)?[4].logical;
+	assert_eq!(az.pressed(), vec![Logical::TRIGGER_ANALOG, Logical::A, Logical::Z]);
+	assert!(az.contains(Logical::A));
+	assert!(az.contains(Logical::A | Logical::Z));
+	assert!(!az.contains(Logical::A | Logical::B));
+	assert!(Logical::NONE.pressed().is_empty());
+	Ok(())
+}
+
+#[test]
+fn state_flags() {
+	use super::frame::StateFlags;
+	let flags = StateFlags::SHIELD | StateFlags::HIT_STUN;
+	assert_eq!(flags.iter().collect::<Vec<_>>(), vec![StateFlags::SHIELD, StateFlags::HIT_STUN]);
+	assert_eq!(flags.flags(), flags.iter().collect::<Vec<_>>());
+	assert_eq!(flags.names(), vec!["SHIELD", "HIT_STUN"]);
+	assert_eq!(flags.to_string(), "SHIELD|HIT_STUN");
+	assert_eq!((flags | StateFlags(1 << 7)).to_string(), "SHIELD|HIT_STUN|0x1<<7");
+	assert_eq!(StateFlags(1 << 7).iter().count(), 0);
+	assert_eq!(StateFlags(0).to_string(), "0");
+	assert_eq!((Logical::A | Logical::Z).to_string(), "A|Z");
+}
+
+#[test]
+fn buttons_just_pressed() {
+	use super::buttons::{just_pressed, just_released};
+	let prev = Logical::A;
+	let cur = Logical::A | Logical::Z;
+	assert_eq!(just_pressed(prev, cur), Logical::Z);
+	assert_eq!(just_released(prev, cur), Logical::NONE);
+	assert_eq!(just_released(cur, prev), Logical::Z);
+}
+
 #[test]
 fn buttons_abxy() -> Result<(), String> {
 	let game = game("buttons_abxy")?;
@@ -340,7 +380,7 @@ fn joystick_udlr() -> Result<(), String> {
 #[test]
 fn nintendont() -> Result<(), String> {
 	let game = game("nintendont")?;
-	assert_eq!(game.metadata.platform, Some("nintendont".to_string()));
+	assert_eq!(game.metadata.platform, Some(Platform::Nintendont));
 	Ok(())
 }
 
@@ -367,10 +407,47 @@ fn v2() -> Result<(), String> {
 	Ok(())
 }
 
+#[test]
+fn slippi_version() {
+	let v = SlippiVersion(2, 0, 1);
+	assert!(v.at_least(2, 0, 1));
+	assert!(v.at_least(1, 14, 0));
+	assert!(!v.at_least(2, 1, 0));
+	assert!(SlippiVersion(0, 1, 0) < SlippiVersion(0, 1, 1));
+	assert!(SlippiVersion(1, 0, 0) > SlippiVersion(0, 255, 255));
+	assert_eq!(v.to_string(), "2.0.1");
+}
+
 #[test]
 fn unknown_event() -> Result<(), String> {
+	use std::convert::TryFrom;
+	use super::parse::{self, Handlers};
 	game("unknown_event")?;
-	// TODO: check for warning
+
+	#[derive(Default)]
+	struct Unknown {
+		sizes: HashMap<u8, u16>,
+		events: Vec<(u8, usize)>,
+	}
+	impl Handlers for Unknown {
+		fn payload_sizes(&mut self, sizes:&HashMap<u8, u16>) -> io::Result<()> {
+			self.sizes = sizes.clone();
+			Ok(())
+		}
+		fn unknown_event(&mut self, code:u8, payload:&[u8]) -> io::Result<()> {
+			self.events.push((code, payload.len()));
+			Ok(())
+		}
+	}
+
+	let mut unknown = Unknown::default();
+	let bytes = fs::read("test/replays/unknown_event.slp").map_err(|e| format!("{:?}", e))?;
+	parse::parse(&*bytes, &mut unknown).map_err(|e| format!("{:?}", e))?;
+	assert!(!unknown.events.is_empty());
+	for (code, len) in unknown.events {
+		assert!(parse::Event::try_from(code).is_err());
+		assert_eq!(len, unknown.sizes[&code] as usize);
+	}
 	Ok(())
 }
 
@@ -381,3 +458,2471 @@ fn zelda_sheik_transformation() -> Result<(), String> {
 		State::Zelda(Zelda::TRANSFORM_GROUND));
 	Ok(())
 }
+
+#[test]
+fn metadata_only() -> Result<(), String> {
+	let expected = game("game")?.metadata;
+
+	let f = fs::File::open("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	let metadata = super::metadata(io::BufReader::new(f)).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(metadata, expected);
+
+	let f = fs::File::open("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	let metadata = super::metadata_unseekable(io::BufReader::new(f)).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(metadata, expected);
+
+	Ok(())
+}
+
+#[test]
+fn dump_frame() -> Result<(), String> {
+	let game = game("ics")?;
+	assert_eq!(game.dump_frame(127), "frame 127
+port 0 (leader): POPO, DAMAGE_N_2, (-17.138386, -0.8447896), 10%, 4 stocks, buttons [\"A\"]
+port 0 (follower): NANA, DAMAGE_LW_2, (-17.783054, 0.62154835), 10%, 4 stocks, buttons []
+port 1 (leader): JIGGLYPUFF, ATTACK_LW_3, (9.089375, 0.0001), 0%, 4 stocks, buttons []
+");
+	assert_eq!(game.dump_frame(-124), "frame -124\n");
+	Ok(())
+}
+
+#[test]
+fn input_patterns() -> Result<(), String> {
+	// real replay: lots of ordinary crouching but no teabags, and two taunts
+	let console = game("console_name")?;
+	assert_eq!(super::stats::input_patterns(&console, 1), super::stats::InputPatterns {
+		crouches: 30,
+		teabags: 0,
+		taunts: 2,
+		shield_frames: 403,
+		excessive_shield: false,
+	});
+	assert_eq!(super::stats::input_patterns(&console, 0).taunts, 0);
+
+	// None of the test replays have a teabag, so make one.
+	let mut game = game("game")?;
+	{
+		let post = &mut game.ports[0].as_mut().ok_or("missing port 0")?.leader.post;
+		for p in post.iter_mut() {
+			p.state = State::Common(Common::WAIT);
+		}
+		// four quick crouches, then a lone crouch much later
+		for start in &[1000, 1010, 1020, 1030, 2000] {
+			for p in &mut post[*start .. *start + 5] {
+				p.state = State::Common(Common::SQUAT);
+			}
+		}
+		for p in &mut post[3000 .. 3050] {
+			p.state = State::Common(Common::APPEAL_R);
+		}
+		for p in &mut post[4000 .. 4100] {
+			p.state = State::Common(Common::GUARD);
+		}
+	}
+
+	assert_eq!(super::stats::input_patterns(&game, 0), super::stats::InputPatterns {
+		crouches: 5,
+		teabags: 1,
+		taunts: 1,
+		shield_frames: 100,
+		excessive_shield: false,
+	});
+	assert_eq!(super::stats::input_patterns(&game, 2), Default::default());
+
+	Ok(())
+}
+
+fn game_from_bytes(bytes:&[u8], opts:&mut super::parse::ParseOptions) -> Result<Game, String> {
+	let mut game_parser = super::game_parser::GameParser::default();
+	game_parser.rollback = opts.rollback;
+	super::parse_opts(io::Cursor::new(bytes), &mut game_parser, opts).map_err(|e| format!("{:?}", e))?;
+	game_parser.into_game().map_err(|e| format!("{:?}", e))
+}
+
+#[test]
+fn lenient_end() -> Result<(), String> {
+	let expected = game("game")?;
+	let bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	let truncated = &bytes[.. bytes.len() - 1]; // missing the final "}"
+	let mut lenient = super::parse::ParseOptions { lenient_end: true, ..Default::default() };
+
+	assert!(game_from_bytes(truncated, &mut Default::default()).is_err());
+	assert_eq!(game_from_bytes(truncated, &mut lenient)?, expected);
+	assert_eq!(game_from_bytes(&bytes, &mut lenient)?, expected);
+
+	// metadata cut short: frames survive, metadata is empty
+	let raw_len = u32::from_be_bytes([bytes[11], bytes[12], bytes[13], bytes[14]]) as usize;
+	let metadata_start = 15 + raw_len;
+	let truncated = &bytes[.. metadata_start + 20];
+	assert!(game_from_bytes(truncated, &mut Default::default()).is_err());
+	let game = game_from_bytes(truncated, &mut lenient)?;
+	assert_eq!(game.ports, expected.ports);
+	assert!(game.metadata.json.is_empty());
+	assert_eq!(game.metadata.date, None);
+
+	// `raw` length too long (Game End comes before it), with leftover bytes before metadata
+	let mut padded = bytes.clone();
+	padded[11 .. 15].copy_from_slice(&(raw_len as u32 + 2).to_be_bytes());
+	padded.splice(metadata_start .. metadata_start, vec![0, 0]);
+	assert!(game_from_bytes(&padded, &mut Default::default()).is_err());
+	assert_eq!(game_from_bytes(&padded, &mut lenient)?, expected);
+
+	Ok(())
+}
+
+#[test]
+fn interaction_matrix() -> Result<(), String> {
+	// real replays: all damage taken, including Nana's, lands in exactly one cell
+	for name in &["v2.0", "console_name", "nintendont", "ics"] {
+		let real = game(name)?;
+		let matrix = super::stats::interaction_matrix(&real);
+		for (victim, port) in real.ports.iter().enumerate() {
+			let taken:f32 = port.iter()
+				.flat_map(|p| std::iter::once(&p.leader).chain(p.follower.as_ref()))
+				.flat_map(|f| f.post.windows(2))
+				.map(|w| (w[1].damage - w[0].damage).max(0.0))
+				.sum();
+			let attributed:f32 = matrix.iter().map(|row| row[victim]).sum();
+			assert!((taken - attributed).abs() < 0.01, "{}: port {} took {}, attributed {}", name, victim, taken, attributed);
+		}
+	}
+	// nintendont.slp has real damage with no valid attacker
+	assert!(super::stats::interaction_matrix(&game("nintendont")?)[3][3] > 0.0);
+
+	let mut game = game("game")?;
+	let matrix = super::stats::interaction_matrix(&game);
+	assert!(matrix[0][1] > 0.0);
+	assert!(matrix[1][0] > 0.0);
+	assert_eq!(matrix[2], [0.0; 4]);
+
+	// simulate a self-inflicted explosion
+	{
+		let post = &mut game.ports[0].as_mut().ok_or("missing port 0")?.leader.post;
+		let base = post[100].damage;
+		post[101].damage = base + 8.0;
+		post[101].last_hit_by = 6;
+		post[102].damage = base + 8.0;
+	}
+	assert_eq!(super::stats::interaction_matrix(&game)[0][0], matrix[0][0] + 8.0);
+
+	Ok(())
+}
+
+#[test]
+fn lead_timeline() -> Result<(), String> {
+	use super::stats::Side;
+
+	// A real comeback: port 0 loses the first stock and trails by a stock,
+	// then wins with one stock left.
+	let comeback = game("console_name")?;
+	let timeline = super::stats::lead_timeline(&comeback);
+	let first_death = comeback.deaths(0).first().ok_or("no deaths")?.frame;
+	let blood:Vec<&super::stats::LeadPoint> = timeline.iter().filter(|p| p.first_blood).collect();
+	assert_eq!(blood.len(), 1);
+	assert!(blood[0].index >= first_death && blood[0].index - first_death < super::stats::LEAD_SAMPLE_INTERVAL as i32);
+	assert_eq!(blood[0].leader, Some(Side::Port(1)));
+	assert_eq!(timeline.last().ok_or("empty timeline")?.leader, Some(Side::Port(0)));
+	assert_eq!(timeline.iter().filter(|p| p.lead_change).count(), 13);
+	// stocks always outweigh percent
+	let post = |port:usize, idx:usize| &comeback.ports[port].as_ref().unwrap().leader.post[idx];
+	for (i, p) in timeline.iter().enumerate() {
+		let idx = i * super::stats::LEAD_SAMPLE_INTERVAL;
+		match post(0, idx).stocks.cmp(&post(1, idx).stocks) {
+			std::cmp::Ordering::Greater => assert_eq!(p.leader, Some(Side::Port(0))),
+			std::cmp::Ordering::Less => assert_eq!(p.leader, Some(Side::Port(1))),
+			_ => (),
+		}
+	}
+
+	let mut game = game("game")?;
+	// Fake a temporary deficit for port 0, so port 1 takes the lead and then loses it.
+	for p in &mut game.ports[0].as_mut().ok_or("missing port 0")?.leader.post[2500 .. 3500] {
+		p.stocks = 1;
+	}
+
+	let timeline = super::stats::lead_timeline(&game);
+	let changes:Vec<Option<Side>> = timeline.iter().filter(|p| p.lead_change).map(|p| p.leader).collect();
+	assert_eq!(timeline[0].leader, None);
+	assert_eq!(changes, vec![Some(Side::Port(0)), Some(Side::Port(1)), Some(Side::Port(0))]);
+
+	let first_blood:Vec<i32> = timeline.iter().filter(|p| p.first_blood).map(|p| p.index).collect();
+	assert_eq!(first_blood, vec![1917]);
+
+	// 2v2: red has more stocks, even though its combined percent is over 1000
+	use super::game::{Team, TeamColor, TeamShade};
+	game.start.is_teams = true;
+	game.start.players[2] = game.start.players[0].clone();
+	game.start.players[3] = game.start.players[1].clone();
+	game.ports[2] = game.ports[0].clone();
+	game.ports[3] = game.ports[1].clone();
+	for (port, color, stocks, damage) in [(0, TeamColor::RED, 2, 600.0), (2, TeamColor::RED, 2, 600.0), (1, TeamColor::BLUE, 2, 0.0), (3, TeamColor::BLUE, 1, 0.0)] {
+		game.start.players[port].as_mut().ok_or("missing player")?.team = Some(Team { color, shade: TeamShade::NORMAL });
+		for p in &mut game.ports[port].as_mut().ok_or("missing port")?.leader.post {
+			p.stocks = stocks;
+			p.damage = damage;
+		}
+	}
+	let timeline = super::stats::lead_timeline(&game);
+	assert!(timeline.iter().all(|p| p.leader == Some(Side::Team(TeamColor::RED))));
+	assert_eq!(super::stats::winning_side(&game), Some(Side::Team(TeamColor::RED)));
+
+	Ok(())
+}
+
+#[test]
+fn platform() {
+	assert_eq!(Platform::from("dolphin"), Platform::Dolphin);
+	assert_eq!(Platform::from("network"), Platform::Network);
+	assert_eq!(Platform::from("wii-u"), Platform::Other("wii-u".to_string()));
+	assert_eq!(Platform::from("wii-u").as_str(), "wii-u");
+}
+
+#[test]
+fn name_tag_decoding() {
+	use super::parse::decode_name_tag;
+	assert_eq!(decode_name_tag(&[0x41, 0x42, 0, 0]), ("AB".to_string(), false));
+	assert_eq!(decode_name_tag(&[0x82, 0xa0, 0, 0x41]), ("あ".to_string(), false));
+	assert_eq!(decode_name_tag(&[0x41, 0x82, 0x20, 0]), ("A\u{fffd} ".to_string(), true));
+}
+
+#[test]
+fn spacing() -> Result<(), String> {
+	let mut game = game("game")?;
+	for p in game.ports[0].as_mut().ok_or("missing port 0")?.leader.post.iter_mut() {
+		p.position = Position { x: 0.0, y: 0.0 };
+		p.state = State::Common(Common::WAIT);
+	}
+	for (i, p) in game.ports[1].as_mut().ok_or("missing port 1")?.leader.post.iter_mut().enumerate() {
+		p.position = Position { x: [10.0, 20.0, 60.0][i % 3], y: 0.0 };
+		p.state = match i {
+			1000 ..= 1099 => State::Common(Common::DEAD_DOWN),
+			_ => State::Common(Common::WAIT),
+		};
+	}
+
+	let stats = super::stats::spacing(&game, 0, 1).ok_or("no spacing stats")?;
+	assert_eq!(stats.frames, 5209 - 84 - 100);
+	assert!((stats.mean - 30.0).abs() < 0.1);
+	assert_eq!(stats.median, 20.0);
+	assert_eq!(stats.max, 60.0);
+	assert!((stats.close_fraction - 2.0 / 3.0).abs() < 0.01);
+
+	assert_eq!(super::stats::spacing(&game, 0, 2), None);
+
+	// a corrupt position is skipped rather than poisoning the stats
+	game.ports[1].as_mut().ok_or("missing port 1")?.leader.post[2000].position.x = f32::NAN;
+	let stats = super::stats::spacing(&game, 0, 1).ok_or("no spacing stats")?;
+	assert_eq!(stats.frames, 5209 - 84 - 100 - 1);
+	assert_eq!(stats.max, 60.0);
+	Ok(())
+}
+
+#[test]
+fn progress() -> Result<(), String> {
+	let bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	let mut calls = Vec::new();
+	let mut record = |bytes_read, raw_len| calls.push((bytes_read, raw_len));
+	game_from_bytes(&bytes, &mut super::parse::ParseOptions { progress: Some(&mut record), ..Default::default() })?;
+
+	assert!(calls.len() > 1);
+	assert!(calls.windows(2).all(|w| w[0].0 < w[1].0));
+	let (bytes_read, raw_len) = calls[calls.len() - 1];
+	assert_eq!(bytes_read, raw_len);
+
+	// a whole number of intervals: the final report would repeat the last periodic one
+	let events = raw_event_offsets(&bytes);
+	let raw_len = u32::from_be_bytes([bytes[11], bytes[12], bytes[13], bytes[14]]) as usize;
+	let end = events[super::parse::PROGRESS_INTERVAL * 2 - 1].1;
+	let mut cut = bytes[.. end].to_vec();
+	cut[11 .. 15].copy_from_slice(&(end as u32 - 15).to_be_bytes());
+	cut.extend_from_slice(&bytes[15 + raw_len ..]);
+	let mut calls = Vec::new();
+	let mut record = |bytes_read, raw_len| calls.push((bytes_read, raw_len));
+	struct Discard;
+	impl super::parse::Handlers for Discard { }
+	super::parse::parse_opts(&*cut, &mut Discard, &mut super::parse::ParseOptions { progress: Some(&mut record), ..Default::default() })
+		.map_err(|e| format!("{:?}", e))?;
+	assert_eq!(calls.len(), 2);
+	assert_eq!(calls[1], (end - 15, end - 15));
+
+	// in-progress: same reports, but the total is unknown
+	let mut in_progress = bytes.clone();
+	in_progress[11 .. 15].copy_from_slice(&[0; 4]);
+	let mut calls = Vec::new();
+	let mut record = |bytes_read, raw_len| calls.push((bytes_read, raw_len));
+	super::parse::parse_opts(&*in_progress, &mut Discard, &mut super::parse::ParseOptions { progress: Some(&mut record), ..Default::default() })
+		.map_err(|e| format!("{:?}", e))?;
+	assert!(calls.len() > 1);
+	assert!(calls.windows(2).all(|w| w[0].0 < w[1].0));
+	assert!(calls.iter().all(|&(_, raw_len)| raw_len == 0));
+	assert_eq!(calls[calls.len() - 1].0, raw_len);
+	Ok(())
+}
+
+#[test]
+fn eq_ignoring_seed() -> Result<(), String> {
+	let game = game("game")?;
+	let a = game.ports[0].as_ref().ok_or("missing port 0")?.leader.pre[200];
+	let b = super::frame::Pre { random_seed: a.random_seed.wrapping_add(1), ..a };
+	assert_ne!(a, b);
+	assert!(a.eq_ignoring_seed(&b));
+	let c = super::frame::Pre { index: a.index + 1, ..b };
+	assert!(!a.eq_ignoring_seed(&c));
+	Ok(())
+}
+
+#[test]
+fn diff_frames() -> Result<(), String> {
+	use super::game::FrameDiff;
+	let a = game("ics")?;
+	assert_eq!(a.diff_frames(&a, true), vec![]);
+
+	let mut b = a.clone();
+	{
+		let port = b.ports[0].as_mut().ok_or("missing port 0")?;
+		port.leader.pre[10].random_seed ^= 1;
+		port.follower.as_mut().ok_or("missing follower")?.post[20].damage += 1.0;
+		port.leader.post.pop();
+	}
+	let last = a.ports[0].as_ref().unwrap().leader.post.last().unwrap().index;
+	let first = a.ports[0].as_ref().unwrap().leader.pre[0].index;
+	let follower = FrameDiff { port: 0, is_follower: true, index: first + 20 };
+	let missing = FrameDiff { port: 0, is_follower: false, index: last };
+	assert_eq!(a.diff_frames(&b, false), vec![missing, follower]);
+	assert_eq!(a.diff_frames(&b, true), vec![FrameDiff { port: 0, is_follower: false, index: first + 10 }, missing, follower]);
+	Ok(())
+}
+
+#[test]
+fn item_rules() -> Result<(), String> {
+	use super::game::{ItemFrequency, ItemRules};
+	use super::item::Item;
+
+	let rules = game("game")?.start.item_rules();
+	assert_eq!(rules.frequency, ItemFrequency::OFF);
+	assert!(rules.is_enabled(Item::BOB_OMB));
+
+	let rules = ItemRules { frequency: ItemFrequency::HIGH, bitfield: [0, 0, 0, 0, 0b0100_0001] };
+	assert!(rules.is_enabled(Item::CAPSULE));
+	assert!(rules.is_enabled(Item::BOB_OMB));
+	assert!(!rules.is_enabled(Item::BOX));
+	assert!(!rules.is_enabled(Item::POKE_BALL));
+	assert!(!rules.is_enabled(Item(1000)));
+	assert_eq!(rules.enabled(), vec![Item::CAPSULE, Item::BOB_OMB]);
+
+	let mut start = game("game")?.start;
+	assert!(start.items_enabled());
+	assert!(start.enabled_items().contains(&Item::BOB_OMB));
+	start.item_spawn_bitfield = [0; 5];
+	assert!(!start.items_enabled());
+	assert!(start.enabled_items().is_empty());
+
+	assert_eq!(ItemFrequency::VERY_HIGH.name(), Some("VERY_HIGH"));
+	Ok(())
+}
+
+#[test]
+fn capabilities() -> Result<(), String> {
+	use super::game::Capabilities;
+
+	assert_eq!(game("v0.1")?.capabilities(), Capabilities::default());
+
+	let c = game("game")?.capabilities();
+	assert!(c.has_state_age && c.has_ucf);
+	assert!(!c.has_raw_analog && !c.has_flags);
+
+	assert_eq!(game("v2.0")?.capabilities(), Capabilities {
+		has_state_age: true,
+		has_ucf: true,
+		has_raw_analog: true,
+		has_name_tags: true,
+		has_pre_damage: true,
+		has_pal: true,
+		has_flags: true,
+		has_hurtbox: false,
+		..Default::default()
+	});
+
+	// no v3.x replays in the test set, so relabel one
+	let mut game = game("v2.0")?;
+	game.start.slippi.version = SlippiVersion(3, 6, 0);
+	let c = game.capabilities();
+	assert!(c.has_hurtbox && c.has_items && c.has_frame_bookend && c.has_item_misc && c.has_item_owner);
+	assert!(!c.has_latest_finalized_frame);
+	game.start.slippi.version = SlippiVersion(3, 0, 0);
+	let c = game.capabilities();
+	assert!(c.has_items && c.has_frame_bookend && !c.has_item_misc && !c.has_item_owner);
+
+	Ok(())
+}
+
+fn split_game(game:&Game, at:usize) -> (Game, Game) {
+	let mut first = game.clone();
+	let mut second = game.clone();
+	for (a, b) in first.ports.iter_mut().zip(second.ports.iter_mut()) {
+		if let (Some(a), Some(b)) = (a, b) {
+			a.leader.pre.truncate(at);
+			a.leader.post.truncate(at);
+			b.leader.pre.drain(.. at);
+			b.leader.post.drain(.. at);
+		}
+	}
+	first.end = End { method: EndMethod::UNRESOLVED, v2_0: None };
+	(first, second)
+}
+
+#[test]
+fn concat() -> Result<(), String> {
+	let game = game("game")?;
+	let (mut first, second) = split_game(&game, 2000);
+	// as recorded when the first piece's replay was written
+	first.metadata.json.insert("lastFrame".to_string(), super::ubjson::Object::Int(i64::from(first.frame_indexes().end - 1)));
+	first.metadata = super::metadata::parse(&first.metadata.json);
+	assert_eq!(first.metadata.duration, Some(2000));
+
+	let joined = Game::concat(&[first.clone(), second.clone()]).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(joined.metadata.duration, game.metadata.duration);
+	assert_eq!(joined, game);
+	assert!(Game::concat(&[second.clone(), first.clone()]).is_err());
+	assert!(Game::concat(&[first.clone(), first.clone()]).is_err());
+	assert!(Game::concat(&[]).is_err());
+
+	Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn export_followers() -> Result<(), String> {
+	use super::query::Query;
+	use super::{Config, ExportOptions};
+	let game = game("ics")?;
+	let with = ExportOptions::default();
+	let without = ExportOptions { include_followers: false };
+	assert!(with.include_followers);
+
+	// JSON
+	let json = |opts| serde_json::to_string(&game.export(&opts)).map_err(|e| format!("{:?}", e));
+	assert_eq!(json(with)?, serde_json::to_string(&game).map_err(|e| format!("{:?}", e))?);
+	assert!(json(with)?.contains("follower"));
+	assert!(!json(without)?.contains("follower"));
+
+	// query
+	let query = |export, query:&[&str]| -> Result<String, String> {
+		let config = Config { json: true, frames: false, export, enum_names: false, query: None };
+		let mut buf = Vec::new();
+		game.query(&mut buf, &config, query).map_err(|e| format!("{:?}", e))?;
+		String::from_utf8(buf).map_err(|e| format!("{:?}", e))
+	};
+	assert!(query(with, &["ports", "0"])?.contains("follower"));
+	assert!(!query(without, &["ports", "0"])?.contains("follower"));
+	assert!(!query(without, &[])?.contains("follower"));
+	assert_eq!(query(without, &["ports", "0", "follower"])?, "null");
+	assert_ne!(query(with, &["ports", "0", "follower"])?, "null");
+
+	// CSV: same columns, but Nana's damage taken only counts with followers
+	let (row_with, row_without) = (super::summary::csv_row_opts("ics.slp", &game, &with),
+		super::summary::csv_row_opts("ics.slp", &game, &without));
+	assert_eq!(row_with, super::summary::csv_row("ics.slp", &game));
+	assert_eq!(row_with.split(',').count(), row_without.split(',').count());
+	assert_ne!(row_with, row_without);
+	Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize() -> Result<(), String> {
+	use super::character::Internal;
+	use super::frame::StateFlags;
+	use super::game::Frames;
+	fn round_trip<T:serde::Serialize + serde::de::DeserializeOwned>(t:&T) -> Result<T, String> {
+		let json = serde_json::to_string(t).map_err(|e| format!("{:?}", e))?;
+		serde_json::from_str(&json).map_err(|e| format!("{:?}: {}", e, json))
+	}
+
+	for name in ["game", "v0.1", "v2.0", "ics", "transform"].iter() {
+		let game = game(name)?;
+		assert_eq!(round_trip(&game.start)?, game.start);
+		assert_eq!(round_trip(&game.end)?, game.end);
+		assert_eq!(round_trip(&game.metadata)?, game.metadata);
+		for port in game.ports.iter().flatten() {
+			for frames in std::iter::once(&port.leader).chain(port.follower.as_ref()) {
+				// character-specific states, like Sheik's and Zelda's, are resolved against `character`
+				for post in &frames.post {
+					assert_eq!(round_trip(post)?, *post);
+				}
+				let json = format!("{{\"pre\":{},\"post\":{}}}",
+					serde_json::to_string(&frames.pre).map_err(|e| format!("{:?}", e))?,
+					serde_json::to_string(&frames.post).map_err(|e| format!("{:?}", e))?);
+				let de:Frames = serde_json::from_str(&json).map_err(|e| format!("{:?}", e))?;
+				assert!(de == *frames, "{}: frames differ", name);
+			}
+		}
+	}
+
+	// the `enum_names` forms
+	assert_eq!(serde_json::from_str::<Internal>("\"1:FOX\"").map_err(|e| format!("{:?}", e))?, Internal::FOX);
+	assert_eq!(serde_json::from_str::<Internal>("\"99\"").map_err(|e| format!("{:?}", e))?, Internal(99));
+	assert!(serde_json::from_str::<Internal>("\"FOX\"").is_err());
+	assert_eq!(serde_json::from_str::<StateFlags>("8388608").map_err(|e| format!("{:?}", e))?, StateFlags::SHIELD);
+	Ok(())
+}
+
+#[test]
+fn competitive() -> Result<(), String> {
+	let cpu = game("game")?;
+	assert!(cpu.has_cpu());
+	assert!(!cpu.is_competitive());
+
+	let mut netplay = game("netplay_name")?;
+	assert!(!netplay.has_cpu());
+	assert!(netplay.is_competitive());
+
+	// the same game under time rules, or with a non-standard timer or stock count
+	netplay.start.bitfield[0] = (netplay.start.bitfield[0] & 0b0001_1111) | (super::game::GameMode::TIME.0 << 5);
+	assert!(netplay.start.is_time_match());
+	assert!(!netplay.is_competitive());
+	let mut netplay = game("netplay_name")?;
+	netplay.start.timer = 6 * 60;
+	assert!(!netplay.is_competitive());
+	let mut netplay = game("netplay_name")?;
+	netplay.start.players[0].as_mut().ok_or("missing player 0")?.stocks = 3;
+	assert!(!netplay.is_competitive());
+
+	assert!(Stage::BATTLEFIELD.is_tournament_legal());
+	assert!(!Stage::BIG_BLUE.is_tournament_legal());
+	Ok(())
+}
+
+#[test]
+fn event_counter() -> Result<(), String> {
+	use super::event_counter::{EventCount, EventCounter};
+	let game = game("game")?;
+	let frames = game.ports[0].as_ref().ok_or("port 0 missing")?.leader.pre.len();
+
+	let mut bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	// declare an extra event (0x10, 2-byte payload) and insert one instance before Game Start
+	bytes.splice(29 .. 29, vec![0x10, 0x00, 0x02, 0x10, 0xaa, 0xbb]);
+	bytes[16] += 3;
+	let raw_len = u32::from_be_bytes([bytes[11], bytes[12], bytes[13], bytes[14]]) + 6;
+	bytes[11 .. 15].copy_from_slice(&raw_len.to_be_bytes());
+
+	let mut counter = EventCounter::default();
+	super::parse(io::Cursor::new(&bytes), &mut counter).map_err(|e| format!("{:?}", e))?;
+
+	assert_eq!(counter.counts[&0x10], EventCount { count: 1, bytes: 3 });
+	assert_eq!(counter.counts[&0x36], EventCount { count: 1, bytes: 353 });
+	assert_eq!(counter.counts[&0x37], EventCount { count: frames * 2, bytes: frames * 2 * 59 });
+	assert_eq!(counter.counts[&0x38], EventCount { count: frames * 2, bytes: frames * 2 * 38 });
+	assert_eq!(counter.counts[&0x39], EventCount { count: 1, bytes: 2 });
+	assert_eq!(counter.total_bytes() + 17, raw_len as usize); // + Event Payloads
+	assert!(counter.fraction(0x37) > 0.5);
+
+	Ok(())
+}
+
+#[test]
+fn starting_stocks() -> Result<(), String> {
+	let mut game = game("game")?;
+	assert_eq!(super::stats::stocks_lost(&game, 1), Some(4));
+	assert_eq!(game.start.players[1].as_ref().ok_or("player 1 missing")?.starting_stocks(), 4);
+
+	// turn it into a 2-stock game
+	game.start.players[1].as_mut().ok_or("player 1 missing")?.stocks = 2;
+	for p in game.ports[1].as_mut().ok_or("port 1 missing")?.leader.post.iter_mut() {
+		p.stocks = p.stocks.saturating_sub(2);
+	}
+	assert_eq!(super::stats::stocks_lost(&game, 1), Some(2));
+	assert_eq!(super::stats::stocks_lost(&game, 2), None);
+
+	// death & combo stats count stocks from the starting stocks, too
+	assert_eq!(game.deaths(1).iter().map(|d| d.stock).collect::<Vec<_>>(), vec![1, 2]);
+	let kills = super::stats::kill_moves(&game, 1);
+	assert_eq!(kills.iter().map(|k| (k.index, k.stock)).collect::<Vec<_>>(), vec![(1876, 1), (2578, 2)]);
+	let combos = super::stats::combos(&game, 0, super::stats::COMBO_RESET_FRAMES);
+	assert_eq!(combos[0].stock, 1);
+	assert_eq!(combos.iter().filter(|c| c.did_kill).map(|c| c.stock).collect::<Vec<_>>(), vec![1, 2]);
+	Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn query_json() -> Result<(), String> {
+	use super::query::Query;
+	let game = game("game")?;
+	let pre = &game.ports[0].as_ref().ok_or("port 0 missing")?.leader.pre[0];
+
+	let buttons = pre.query_json(&["buttons"]).map_err(|e| format!("{:?}", e))?;
+	assert!(buttons.is_object());
+	assert_eq!(buttons["logical"], pre.buttons.logical.0);
+
+	assert_eq!(game.query_json(&["start", "stage"]).map_err(|e| format!("{:?}", e))?, Stage::YOSHIS_STORY.0);
+	assert_eq!(game.query_json(&["start", "players", "", "stocks"]).map_err(|e| format!("{:?}", e))?,
+		serde_json::json!([4, 4, null, null]));
+	Ok(())
+}
+
+#[test]
+fn port_state() -> Result<(), String> {
+	let mut game = game("nintendont")?;
+	assert_eq!(game.port_state(0), Some(PortState::Empty));
+	assert!(matches!(game.port_state(1), Some(PortState::Active(_, _))));
+	assert_eq!(game.port_state(2), Some(PortState::Empty));
+	assert!(matches!(game.port_state(3), Some(PortState::Active(_, _))));
+	assert_eq!(game.port_state(4), None);
+	assert_eq!(game.port_state(u8::MAX), None);
+
+	game.ports[3] = None;
+	assert!(matches!(game.port_state(3), Some(PortState::MissingFrames(_))));
+	game.start.players.swap(1, 2);
+	assert!(matches!(game.port_state(1), Some(PortState::MissingPlayer(_))));
+	Ok(())
+}
+
+#[test]
+fn conversion_rate() -> Result<(), String> {
+	let game = game("game")?;
+	let stats = super::stats::conversion_rate(&game, 0);
+	assert_eq!(stats.openings, 13);
+	assert_eq!(stats.kills, 4); // the CPU lost all of its stocks
+	assert_eq!(stats.kills_per_opening, 4.0 / 13.0);
+	assert_eq!(stats.lengths.values().sum::<u32>(), stats.openings);
+	assert!((stats.damage_per_opening - stats.total_damage / 13.0).abs() < 0.001);
+	// includes unattributed damage (Fox's laser) taken mid-conversion
+	let matrix = super::stats::interaction_matrix(&game);
+	assert!(stats.total_damage <= matrix[0][1] + matrix[1][1] + 0.001);
+
+	let conversions = super::stats::conversions(&game);
+	assert_eq!(super::stats::ConversionStats::new(&conversions, 0), stats);
+	assert_eq!(super::stats::ConversionStats::new(&conversions, 1).openings, 1);
+	Ok(())
+}
+
+#[test]
+fn combos() -> Result<(), String> {
+	use super::stats::{combos, conversions, COMBO_RESET_FRAMES, CONVERSION_RESET_FRAMES};
+	let game = game("game")?;
+	let c = combos(&game, 0, COMBO_RESET_FRAMES);
+	assert_eq!(c.len(), 13);
+	assert_eq!((c[0].start_index, c[0].end_index, c[0].moves.len()), (81, 355, 5));
+	assert_eq!(c[0].damage(), 39.0);
+	assert_eq!(c.iter().filter(|c| c.did_kill).map(|c| c.end_index).collect::<Vec<_>>(),
+		game.deaths(1).iter().map(|d| d.frame).collect::<Vec<_>>());
+
+	// same grouping as conversions with the same reset
+	assert_eq!(COMBO_RESET_FRAMES, CONVERSION_RESET_FRAMES);
+	let conversions:Vec<_> = conversions(&game).into_iter().filter(|c| c.attacker == 0).collect();
+	assert_eq!(c.iter().map(|c| (c.start_index, c.end_index)).collect::<Vec<_>>(),
+		conversions.iter().map(|c| (c.start_index, c.end_index)).collect::<Vec<_>>());
+
+	// a shorter reset splits combos up, a longer one merges them
+	assert_eq!(combos(&game, 0, 0).len(), 42);
+	assert_eq!(combos(&game, 0, 300).len(), 6);
+	assert_eq!(combos(&game, 2, COMBO_RESET_FRAMES), vec![]);
+	Ok(())
+}
+
+#[test]
+fn hurtbox_frames() -> Result<(), String> {
+	use super::frame::{HurtboxState, PostV2_1};
+	use super::stats::{hurtbox_frames_in, intangible_frames, invulnerable_frames};
+	let mut game = game("v2.0")?;
+	assert_eq!(intangible_frames(&game, 0), None);
+
+	game.start.slippi.version = SlippiVersion(2, 1, 0);
+	let post = &mut game.ports[0].as_mut().ok_or("missing port 0")?.leader.post;
+	for (i, p) in post.iter_mut().enumerate() {
+		let hurtbox_state = match i {
+			200 .. 210 => HurtboxState::INTANGIBLE,
+			300 .. 303 => HurtboxState::INVULNERABLE,
+			_ => HurtboxState::VULNERABLE,
+		};
+		p.v0_2.as_mut().unwrap().v2_0.as_mut().unwrap().v2_1 = Some(PostV2_1 { hurtbox_state });
+	}
+	assert_eq!(intangible_frames(&game, 0), Some(10));
+	assert_eq!(invulnerable_frames(&game, 0), Some(3));
+	let first = game.first_frame_index();
+	assert_eq!(hurtbox_frames_in(&game, 0, HurtboxState::INTANGIBLE, first + 205 .. first + 250), Some(5));
+	assert_eq!(hurtbox_frames_in(&game, 0, HurtboxState::INTANGIBLE, 0 .. 0), Some(0));
+	assert_eq!(intangible_frames(&game, 3), None);
+	Ok(())
+}
+
+#[test]
+fn di_inputs() -> Result<(), String> {
+	use super::frame::StateFlags;
+	use super::stats::di_inputs;
+	assert_eq!(di_inputs(&game("v0.1")?, 0), vec![]);
+
+	let game = game("v2.0")?;
+	let port = game.ports[0].as_ref().ok_or("missing port 0")?;
+	let di = di_inputs(&game, 0);
+	assert!(!di.is_empty());
+	for (index, joystick) in di {
+		let i = (index - game.first_frame_index()) as usize;
+		assert_eq!(port.leader.pre[i].joystick, joystick);
+		assert!(port.leader.post[i].v0_2.unwrap().v2_0.unwrap().flags.contains(StateFlags::HIT_STUN));
+	}
+	Ok(())
+}
+
+#[test]
+fn joystick_analog() -> Result<(), String> {
+	let nintendont = game("nintendont")?;
+	let pre = &nintendont.ports.iter().flatten().next().ok_or("missing port")?.leader.pre;
+	let mut raw_xs = Vec::new();
+	for p in pre {
+		let analog = p.joystick_analog();
+		assert_eq!(analog.processed, p.joystick);
+		raw_xs.push(analog.raw_x.ok_or("missing raw x")?);
+	}
+	assert!(raw_xs.iter().any(|&x| x < -90) && raw_xs.iter().any(|&x| x > 90));
+	// processing can zero the raw value (deadzone), but never flips its sign
+	assert!(pre.iter().all(|p| p.joystick.x * p.raw_joystick_x().unwrap() as f32 >= 0.0));
+
+	let v1_0 = game("joystick_udlr")?;
+	assert_eq!(v1_0.ports[0].as_ref().unwrap().leader.pre[0].joystick_analog().raw_x, None);
+	Ok(())
+}
+
+#[test]
+fn kill_moves() -> Result<(), String> {
+	use super::attack::Attack;
+	let mut game = game("game")?;
+	let kills = super::stats::kill_moves(&game, 1);
+	assert_eq!(kills.iter().map(|k| (k.index, k.attack)).collect::<Vec<_>>(), vec![
+		(1876, Some(Attack::SIDE_TILT)),
+		(2578, Some(Attack::DOWN_TILT)),
+		(3715, Some(Attack::UP_TILT)),
+		(5085, Some(Attack::SIDE_TILT)),
+	]);
+	assert_eq!(kills[0].hit_index, Some(1843));
+	assert_eq!(kills[0].killer, Some(0));
+	assert!(kills[0].killer_position.is_some());
+
+	// no attacker: treated as a self-destruct
+	for p in game.ports[1].as_mut().ok_or("port 1 missing")?.leader.post.iter_mut() {
+		p.last_hit_by = 6;
+	}
+	let kills = super::stats::kill_moves(&game, 1);
+	assert_eq!(kills.len(), 4);
+	assert!(kills.iter().all(|k| k.killer.is_none() && k.attack.is_none() && k.killer_position.is_none()));
+
+	assert_eq!(super::stats::kill_moves(&game, 2), vec![]);
+	Ok(())
+}
+
+#[test]
+fn deaths() -> Result<(), String> {
+	use super::game::Death;
+	let game = game("game")?;
+	let deaths = game.deaths(1);
+	assert_eq!(deaths.iter().map(|d| d.frame).collect::<Vec<_>>(), vec![1876, 2578, 3715, 5085]);
+	assert!(deaths.iter().all(|d| d.killed_by == Some(0)));
+	assert_eq!(deaths.iter().map(|d| d.stock).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+	assert_eq!(game.deaths(1).len(), super::stats::stocks_lost(&game, 1).ok_or("missing port 1")? as usize);
+	assert_eq!(game.deaths(2), vec![]);
+
+	// the spawn on the first frame isn't a death
+	let mut game = game.slice(1876 .. 2000);
+	assert_eq!(game.deaths(1), vec![]);
+	for p in game.ports[1].as_mut().unwrap().leader.post.iter_mut() {
+		p.last_hit_by = 1;
+	}
+	game.ports[1].as_mut().unwrap().leader.post[50].stocks -= 1;
+	assert_eq!(game.deaths(1), vec![Death { frame: 1926, killed_by: None, stock: 2 }]);
+	Ok(())
+}
+
+#[test]
+fn misc_as() -> Result<(), String> {
+	use super::frame::MiscAs;
+	let v2_0 = game("v2.0")?;
+	let post = &v2_0.ports[0].as_ref().ok_or("port 0 missing")?.leader.post;
+	let frame = |index:i32| &post[(index - super::game::FIRST_FRAME_INDEX) as usize];
+
+	// hitstun counts down while in DAMAGE_HI_2
+	assert_eq!(frame(170).state, State::Common(Common::DAMAGE_HI_2));
+	assert_eq!(frame(170).misc_as_interpreted(), Some(MiscAs::HitstunRemaining(13.0)));
+	assert_eq!(frame(171).misc_as_interpreted(), Some(MiscAs::HitstunRemaining(12.0)));
+
+	// shield time counts up from GUARD_ON, through shieldstun (GUARD_REFLECT) and into GUARD
+	assert_eq!(frame(126).state, State::Common(Common::GUARD_REFLECT));
+	assert_eq!(frame(126).misc_as_interpreted(), Some(MiscAs::AirtimeOrCharge(7.0)));
+	assert_eq!(frame(127).state, State::Common(Common::GUARD));
+	assert_eq!(frame(127).misc_as_interpreted(), Some(MiscAs::AirtimeOrCharge(8.0)));
+	assert_eq!(frame(128).misc_as_interpreted(), Some(MiscAs::AirtimeOrCharge(9.0)));
+
+	// airborne
+	assert!(matches!(frame(0).misc_as_interpreted(), Some(MiscAs::AirtimeOrCharge(_))));
+	// grounded, not shielding
+	assert_eq!(frame(3).state, State::Common(Common::LANDING));
+	assert!(matches!(frame(3).misc_as_interpreted(), Some(MiscAs::Unknown(_))));
+
+	let v0_1 = game("v0.1")?;
+	assert_eq!(v0_1.ports[0].as_ref().ok_or("port 0 missing")?.leader.post[0].misc_as_interpreted(), None);
+	Ok(())
+}
+
+#[test]
+fn csv_summary() -> Result<(), String> {
+	assert_eq!(super::summary::csv_header(), "file,date,stage,winner,\
+		p1_character,p1_name_tag,p1_netplay_name,p1_apm,p1_damage,p1_deaths,\
+		p2_character,p2_name_tag,p2_netplay_name,p2_apm,p2_damage,p2_deaths,\
+		p3_character,p3_name_tag,p3_netplay_name,p3_apm,p3_damage,p3_deaths,\
+		p4_character,p4_name_tag,p4_netplay_name,p4_apm,p4_damage,p4_deaths");
+
+	let local = game("game")?;
+	let netplay = game("netplay_name")?;
+	assert_eq!(super::stats::winner(&local), Some(0));
+	assert_eq!(super::summary::csv_row("a,b.slp", &local),
+		"\"a,b.slp\",2018-06-22T07:52:59+00:00,YOSHIS_STORY,1,\
+		MARTH,,,265.5,358.8,0,FOX,,,94.8,4.0,4,,,,,,,,,,,,");
+
+	let row = super::summary::csv_row("n.slp", &netplay);
+	assert!(row.contains(",,Player1,"), "{}", row);
+	assert!(row.contains(",,metonym,"), "{}", row);
+	Ok(())
+}
+
+#[test]
+fn without_countdown() -> Result<(), String> {
+	let expected = game("game")?;
+	let bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	let mut trimmed = Vec::new();
+	super::trim::without_countdown(&*bytes, &mut trimmed).map_err(|e| format!("{:?}", e))?;
+	assert!(trimmed.len() < bytes.len());
+
+	let game = game_from_bytes(&trimmed, &mut Default::default())?;
+	let first = super::game::FIRST_FRAME_INDEX;
+	assert_eq!(game.first_frame_index(), first);
+	assert_eq!(game.start, expected.start);
+
+	// frames after "Go!" are kept, renumbered to start at the usual index
+	let frames = expected.frame_indexes().end as usize;
+	for (port, expected_port) in game.ports.iter().zip(expected.ports.iter()) {
+		match (port, expected_port) {
+			(Some(p), Some(e)) => {
+				let skip = -first as usize;
+				assert_eq!(p.leader.pre.len(), frames);
+				for (pre, e) in p.leader.pre.iter().zip(&e.leader.pre[skip ..]) {
+					assert_eq!(pre.index, e.index + first);
+					assert_eq!(*pre, super::frame::Pre { index: pre.index, ..*e });
+				}
+				for (post, e) in p.leader.post.iter().zip(&e.leader.post[skip ..]) {
+					assert_eq!(*post, super::frame::Post { index: post.index, ..*e });
+				}
+			},
+			(None, None) => (),
+			_ => Err("mismatched ports")?,
+		}
+	}
+
+	// metadata is rewritten to match
+	assert_eq!(game.metadata.duration, Some(frames as u32));
+	assert_eq!(expected.metadata.duration, Some((frames as i32 - first) as u32));
+	let mut json = expected.metadata.json.clone();
+	json.insert("lastFrame".to_string(), super::ubjson::Object::Int(i64::from(expected.frame_indexes().end - 1 + first)));
+	assert_eq!(game.metadata.json, json);
+	assert_eq!(game.metadata.date, expected.metadata.date);
+	Ok(())
+}
+
+#[test]
+fn without_countdown_all_frame_events() -> Result<(), String> {
+	// Frame Start, Pre, Post, Item & Bookend, each with just a frame index
+	let codes = [0x3Au8, 0x37, 0x38, 0x3B, 0x3C];
+	let payloads = |raw:&mut Vec<u8>| {
+		raw.extend_from_slice(&[0x35, 1 + 3 * codes.len() as u8]);
+		for &c in &codes {
+			raw.extend_from_slice(&[c, 0, 4]);
+		}
+	};
+	let events = |raw:&mut Vec<u8>, indexes:&[i32]| {
+		for &index in indexes {
+			for &c in &codes {
+				raw.push(c);
+				raw.extend_from_slice(&index.to_be_bytes());
+			}
+		}
+	};
+	let replay = |raw:Vec<u8>| {
+		let mut bytes = vec![0x7b, 0x55, 0x03, 0x72, 0x61, 0x77, 0x5b, 0x24, 0x55, 0x23, 0x6c];
+		bytes.extend_from_slice(&(raw.len() as u32).to_be_bytes());
+		bytes.extend_from_slice(&raw);
+		bytes.push(0x7d);
+		bytes
+	};
+
+	let mut raw = Vec::new();
+	payloads(&mut raw);
+	events(&mut raw, &[-2, -1, 0, 1]);
+	let mut expected = Vec::new();
+	payloads(&mut expected);
+	events(&mut expected, &[super::game::FIRST_FRAME_INDEX, super::game::FIRST_FRAME_INDEX + 1]);
+
+	let mut trimmed = Vec::new();
+	super::trim::without_countdown(&*replay(raw.clone()), &mut trimmed).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(trimmed, replay(expected));
+
+	// Event Payloads are validated the same way `parse` does
+	let mut bad_code = raw.clone();
+	bad_code[0] = 0x36;
+	assert!(super::trim::without_countdown(&*replay(bad_code), &mut Vec::new()).is_err());
+	let mut bad_size = raw;
+	bad_size[1] += 1;
+	assert!(super::trim::without_countdown(&*replay(bad_size), &mut Vec::new()).is_err());
+	Ok(())
+}
+
+#[test]
+fn unused_version_byte() -> Result<(), String> {
+	for name in ["game", "v0.1", "v2.0", "ics", "console_name", "netplay_name", "nintendont", "transform"].iter() {
+		assert_eq!(game(name)?.start.slippi.unused_version_byte, 0, "{}", name);
+	}
+
+	// it's read from the byte after the version, not skipped
+	let mut bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	assert_eq!(bytes[29 .. 34], [0x36, 1, 0, 0, 0]); // Game Start: version 1.0.0, then the unused byte
+	bytes[33] = 7;
+	let game = game_from_bytes(&bytes, &mut Default::default())?;
+	assert_eq!(game.start.slippi, Slippi { version: SlippiVersion(1, 0, 0), unused_version_byte: 7 });
+	Ok(())
+}
+
+#[test]
+fn slices_where() -> Result<(), String> {
+	let game = game("game")?;
+	// frames where port 1 (the CPU) is above 80%: three separate stretches
+	let pred = |f:&super::game::FrameRef| f.post(1).is_some_and(|p| p.damage > 80.0);
+
+	let ranges = |clips:&[Game]| clips.iter().map(|c| {
+		let pre = &c.ports[1].as_ref().unwrap().leader.pre;
+		(pre[0].index, pre[pre.len() - 1].index)
+	}).collect::<Vec<_>>();
+
+	let clips = game.slices_where(pred, 0, 0);
+	assert_eq!(ranges(&clips), vec![(737, 1935), (3480, 3774), (4832, 5085)]);
+
+	// padding is clamped to the end of the game
+	let clips = game.slices_where(pred, 10, 20);
+	assert_eq!(ranges(&clips), vec![(727, 1955), (3470, 3794), (4822, 5085)]);
+	assert_eq!(clips[0].first_frame_index(), 727);
+	assert_eq!(clips[0].dump_frame(1000), game.dump_frame(1000));
+	assert_eq!(clips[0].dump_frame(2000), "frame 2000\n");
+
+	assert!(game.slices_where(|_| false, 10, 10).is_empty());
+
+	// huge padding clamps to the whole game instead of wrapping around
+	let clips = game.slices_where(pred, u32::MAX, u32::MAX);
+	let whole = game.frame_indexes();
+	assert_eq!(ranges(&clips)[0], (whole.start, whole.end - 1));
+	Ok(())
+}
+
+#[test]
+fn post_v2_0_fields() -> Result<(), String> {
+	let game = game("v2.0")?;
+	let stage = Stage::YOSHIS_STORY;
+	assert_eq!(game.start.stage, stage);
+	for port in game.ports.iter().flatten() {
+		for p in &port.leader.post {
+			let v = p.v0_2.as_ref().and_then(|v| v.v2_0.as_ref()).ok_or("missing v2.0 fields")?;
+			// Yoshi's Story has 7 surfaces; 65535 means not grounded since spawning
+			assert!(v.ground <= 6 || v.ground == u16::MAX, "ground {}", v.ground);
+			assert!(v.jumps <= 6, "jumps {}", v.jumps); // up to 6 for Kirby and Jigglypuff
+			// only set on the frame of an aerial landing
+			if v.l_cancel.is_some() {
+				assert!(matches!(p.state, State::Common(s) if (Common::LANDING_AIR_N.0 ..= Common::LANDING_AIR_LW.0).contains(&s.0)));
+				assert!(!v.airborne);
+			}
+		}
+	}
+
+	// known frames from port 2: spawning, shielding on the right side
+	// platform just before a shield drop, and falling through it
+	let post = &game.ports[1].as_ref().ok_or("port 1 missing")?.leader.post;
+	let fields = |index:i32| post[(index + 123) as usize].v0_2.as_ref()
+		.and_then(|v| v.v2_0.as_ref()).map(|v| (v.airborne, v.ground, v.jumps));
+	assert_eq!(fields(-123), Some((true, u16::MAX, 1)));
+	assert_eq!(fields(6776), Some((false, 5, 2)));
+	assert_eq!(fields(6786), Some((true, 5, 1)));
+
+	Ok(())
+}
+
+#[test]
+fn shield_drops() -> Result<(), String> {
+	let v2_0 = game("v2.0")?;
+	// from Yoshi's Story's right and left side platforms
+	assert_eq!(super::stats::shield_drops(&v2_0, 1), vec![
+		super::stats::ShieldDropEvent { index: 6777, platform: Some(5) },
+		super::stats::ShieldDropEvent { index: 8029, platform: Some(1) },
+	]);
+	assert_eq!(super::stats::shield_drops_with_threshold(&v2_0, 1, 1.1), vec![]);
+
+	// pre-v2.0: no ground IDs
+	let console = game("console_name")?;
+	assert_eq!(super::stats::shield_drops(&console, 0).iter().map(|d| (d.index, d.platform)).collect::<Vec<_>>(),
+		vec![(2424, None), (8825, None)]);
+
+	// Final Destination has no platforms
+	let fd = game("shield_drop")?;
+	assert_eq!(super::stats::shield_drops(&fd, 0), vec![]);
+	Ok(())
+}
+
+#[test]
+fn controller() -> Result<(), String> {
+	use super::frame::process_stick;
+	assert_eq!(process_stick(0, 0), Position { x: 0.0, y: 0.0 });
+	assert_eq!(process_stick(22, -22), Position { x: 0.0, y: 0.0 }); // deadzone
+	assert_eq!(process_stick(23, 0), Position { x: 0.2875, y: 0.0 });
+	assert_eq!(process_stick(-80, 0), Position { x: -1.0, y: 0.0 });
+	assert_eq!(process_stick(0, 127), Position { x: 0.0, y: 1.0 }); // clamped to the circle
+	let diagonal = process_stick(80, 80);
+	assert!((diagonal.x - 0.5f32.sqrt()).abs() < 1e-6 && diagonal.x == diagonal.y);
+
+	let game = game("game")?;
+	for pre in &game.ports[0].as_ref().ok_or("port 0 missing")?.leader.pre {
+		let c = pre.controller();
+		assert_eq!(c.joystick, pre.joystick);
+		assert_eq!(c.buttons, pre.buttons);
+		// stored values are already processed, so re-processing them is a no-op
+		let raw = |v:f32| (v * 80.0).round() as i8;
+		let p = process_stick(raw(c.joystick.x), raw(c.joystick.y));
+		assert!((p.x - c.joystick.x).abs() < 1e-6 && (p.y - c.joystick.y).abs() < 1e-6);
+	}
+	Ok(())
+}
+
+#[test]
+fn execution() -> Result<(), String> {
+	use super::action_state::Fox;
+	use super::stats::ExecutionStats;
+
+	let netplay = game("netplay_name")?;
+	assert_eq!(super::stats::execution(&netplay, 1), ExecutionStats {
+		multishines: 2,
+		multishine_shines: 4,
+		fastest_cadence: Some(12),
+		average_cadence: Some(12.0),
+	});
+	assert_eq!(super::stats::execution(&netplay, 0), Default::default());
+
+	// a synthetic 3-shine multishine at a 7-frame cadence, then a lone shine
+	let mut v2_0 = game("v2.0")?;
+	let post = &mut v2_0.ports[1].as_mut().ok_or("port 1 missing")?.leader.post;
+	let states = [State::Fox(Fox::REFLECTOR_GROUND_STARTUP); 4].iter()
+		.chain(&[State::Common(Common::KNEE_BEND); 3])
+		.cycle().take(3 * 7).copied()
+		.chain(vec![State::Common(Common::WAIT); 10])
+		.chain(vec![State::Fox(Fox::REFLECTOR_GROUND_STARTUP)])
+		.collect::<Vec<_>>();
+	for p in post.iter_mut() {
+		p.state = State::Common(Common::WAIT);
+	}
+	for (p, s) in post[1000 ..].iter_mut().zip(states) {
+		p.state = s;
+	}
+	assert_eq!(super::stats::execution(&v2_0, 1), ExecutionStats {
+		multishines: 1,
+		multishine_shines: 3,
+		fastest_cadence: Some(7),
+		average_cadence: Some(7.0),
+	});
+	Ok(())
+}
+
+#[test]
+fn contains_pii() -> Result<(), String> {
+	use super::game::PiiField;
+	use super::ubjson::Object;
+
+	assert_eq!(game("game")?.contains_pii(), vec![]);
+	assert_eq!(game("console_name")?.contains_pii(), vec![PiiField::ConsoleName]);
+
+	let mut game = game("netplay_name")?;
+	game.start.players[1].as_mut().and_then(|p| p.v1_0.as_mut()).and_then(|v| v.v1_3.as_mut())
+		.ok_or("no v1.3 data for player 1")?.name_tag = "ABC".to_string();
+	assert_eq!(game.contains_pii(), vec![PiiField::NetplayName(0), PiiField::NameTag(1), PiiField::NetplayName(1)]);
+
+	// anonymize
+	game.start.players[1].as_mut().and_then(|p| p.v1_0.as_mut()).and_then(|v| v.v1_3.as_mut())
+		.ok_or("no v1.3 data for player 1")?.name_tag.clear();
+	if let Some(Object::Map(players)) = game.metadata.json.get_mut("players") {
+		for player in players.values_mut() {
+			if let Object::Map(player) = player {
+				player.remove("names");
+			}
+		}
+	}
+	assert_eq!(game.contains_pii(), vec![]);
+	Ok(())
+}
+
+#[test]
+fn stage_control() -> Result<(), String> {
+	let mut game = game("game")?;
+	let edge = game.start.stage.edge_x().ok_or("no stage geometry")?;
+	// Port 0 holds center while port 1 is off stage, then they swap.
+	for (i, p) in game.ports[0].as_mut().ok_or("missing port 0")?.leader.post.iter_mut().enumerate() {
+		p.position = Position { x: if i < 2000 { 0.0 } else { edge + 30.0 }, y: 0.0 };
+		p.state = State::Common(Common::WAIT);
+	}
+	for (i, p) in game.ports[1].as_mut().ok_or("missing port 1")?.leader.post.iter_mut().enumerate() {
+		p.position = Position { x: if i < 2000 { -edge - 30.0 } else { edge / 2.0 }, y: 0.0 };
+		p.state = State::Common(Common::WAIT);
+	}
+
+	let control = super::stats::stage_control(&game);
+	assert_eq!(control.len(), game.ports[0].as_ref().unwrap().leader.post.len());
+	assert_eq!(control[1000].control[0], Some(1.0));
+	assert_eq!(control[1000].control[1], Some(0.0));
+	assert_eq!(control[1000].control[2], None);
+	assert_eq!(control[1000].leader, Some(0));
+	assert_eq!(control[2016].leader, None); // the rolling averages cross over
+	assert_eq!(control[3000].control[1], Some(0.75));
+	assert_eq!(control[3000].leader, Some(1));
+
+	game.start.stage = Stage::CORNERIA;
+	assert!(super::stats::stage_control(&game).is_empty());
+	Ok(())
+}
+
+#[cfg(feature = "tar")]
+#[test]
+fn read_tar() -> Result<(), String> {
+	let archive = path::Path::new("test/replays.tar");
+	let ics = super::read_tar(archive, "sets/ics.slp").map_err(|e| format!("{:?}", e))?;
+	assert_eq!(ics, game("ics")?);
+	assert!(super::read_tar(archive, "sets/missing.slp").is_err());
+
+	let all = super::read_tar_all(archive).map_err(|e| format!("{:?}", e))?;
+	let names:Vec<&str> = all.iter().map(|(name, _)| name.as_str()).collect();
+	assert_eq!(names, vec!["sets/ics.slp", "sets/broken.slp", "sets/dash_back.slp"]);
+	assert!(all[0].1.is_ok());
+	assert!(all[1].1.is_err());
+	assert_eq!(all[2].1.as_ref().map_err(|e| format!("{:?}", e))?, &game("dash_back")?);
+	Ok(())
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn read_zip() -> Result<(), String> {
+	let archive = path::Path::new("test/replays.zip");
+	let ics = super::read_zip(archive, "sets/ics.slp").map_err(|e| format!("{:?}", e))?;
+	assert_eq!(ics, game("ics")?);
+	assert!(super::read_zip(archive, "sets/missing.slp").is_err());
+
+	let all = super::read_zip_all(archive).map_err(|e| format!("{:?}", e))?;
+	let names:Vec<&str> = all.iter().map(|(name, _)| name.as_str()).collect();
+	assert_eq!(names, vec!["sets/ics.slp", "sets/broken.slp", "sets/dash_back.slp"]);
+	assert!(all[0].1.is_ok());
+	assert!(all[1].1.is_err());
+	assert_eq!(all[2].1.as_ref().map_err(|e| format!("{:?}", e))?, &game("dash_back")?);
+	Ok(())
+}
+
+#[cfg(feature = "tar")]
+#[test]
+fn tar_headers() -> Result<(), String> {
+	fn header_type(name:&str, size:&str, r#type:u8) -> Vec<u8> {
+		let mut h = vec![0; 512];
+		h[.. name.len()].copy_from_slice(name.as_bytes());
+		h[124 .. 124 + size.len()].copy_from_slice(size.as_bytes());
+		h[156] = r#type;
+		h[148 .. 156].copy_from_slice(b"        ");
+		let sum:u32 = h.iter().map(|&b| b as u32).sum();
+		h[148 .. 155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+		h
+	}
+	fn header(name:&str, size:&str) -> Vec<u8> {
+		header_type(name, size, b'0')
+	}
+	// an extended header entry of `type`, then a 3-byte file whose own header has a truncated name
+	fn extended(r#type:u8, data:&[u8]) -> Vec<u8> {
+		let mut archive = header_type("././@LongLink", &format!("{:011o}", data.len()), r#type);
+		archive.extend_from_slice(data);
+		archive.resize(archive.len().div_ceil(512) * 512, 0);
+		archive.extend(header(&"a".repeat(100), "00000000003"));
+		archive.extend_from_slice(b"abc");
+		archive.resize(archive.len() + 509 + 1024, 0);
+		archive
+	}
+
+	let mut archive = header("a.slp", "00000000003");
+	archive.extend_from_slice(b"abc");
+	archive.resize(1024, 0);
+	let members = super::tar::members(&archive[..]).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(members, vec![super::tar::Member { name: "a.slp".to_string(), data: b"abc".to_vec() }]);
+
+	archive[0] = b'b';
+	assert!(super::tar::members(&archive[..]).is_err());
+
+	// a size of ~8 GiB with no data behind it is an error, not an allocation
+	let archive = header("huge.slp", "77777777777");
+	assert!(super::tar::members(&archive[..]).is_err());
+
+	// names over 100 bytes, from GNU and pax extended headers
+	let long_name = format!("sets/{}.slp", "a".repeat(120));
+	let mut gnu_name = long_name.clone().into_bytes();
+	gnu_name.push(0);
+	let mut pax = b"19 comment=ignored\n".to_vec();
+	pax.extend_from_slice(format!("{} path={}\n", long_name.len() + 10, long_name).as_bytes());
+	for archive in &[extended(b'L', &gnu_name), extended(b'x', &pax)] {
+		let members = super::tar::members(&archive[..]).map_err(|e| format!("{:?}", e))?;
+		assert_eq!(members, vec![super::tar::Member { name: long_name.clone(), data: b"abc".to_vec() }]);
+	}
+	assert!(super::tar::members(&extended(b'x', b"99 path=x\n")[..]).is_err());
+	Ok(())
+}
+
+#[test]
+fn read_dir() -> Result<(), String> {
+	let opts = super::parse::ParseOptions::default();
+	let ordered:Vec<_> = super::read_dir(path::Path::new("test/replays"), &opts, true).map_err(|e| format!("{:?}", e))?.collect();
+	let mut names:Vec<_> = fs::read_dir("test/replays").map_err(|e| format!("{:?}", e))?
+		.map(|e| e.map(|e| e.path()).map_err(|e| format!("{:?}", e)))
+		.collect::<Result<_, _>>()?;
+	names.sort();
+	assert_eq!(ordered.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>(), names);
+	for (path, game) in &ordered {
+		assert_eq!(game.as_ref().map_err(|e| format!("{:?}", e))?, &super::game(path).map_err(|e| format!("{:?}", e))?);
+	}
+
+	let mut unordered:Vec<_> = super::read_dir(path::Path::new("test/replays"), &opts, false).map_err(|e| format!("{:?}", e))?
+		.map(|(p, _)| p)
+		.collect();
+	unordered.sort();
+	assert_eq!(unordered, names);
+
+	// a bad file doesn't stop the others, and non-replays are skipped
+	let dir = std::env::temp_dir().join(format!("peppi-read-dir-{}", std::process::id()));
+	fs::create_dir_all(&dir).map_err(|e| format!("{:?}", e))?;
+	fs::copy("test/replays/ics.slp", dir.join("a.slp")).map_err(|e| format!("{:?}", e))?;
+	fs::write(dir.join("b.slp"), b"not a replay").map_err(|e| format!("{:?}", e))?;
+	fs::write(dir.join("c.txt"), b"not a replay").map_err(|e| format!("{:?}", e))?;
+	// ... nor does one that panics the parser (a frame event for port 4)
+	let mut bad_port = fs::read("test/replays/ics.slp").map_err(|e| format!("{:?}", e))?;
+	let (start, _, _) = *raw_event_offsets(&bad_port).iter().find(|(_, _, code)| *code == 0x37).ok_or("no frame events")?;
+	bad_port[start + 5] = 4;
+	fs::write(dir.join("d.slp"), bad_port).map_err(|e| format!("{:?}", e))?;
+	fs::copy("test/replays/ics.slp", dir.join("e.slp")).map_err(|e| format!("{:?}", e))?;
+	let results:Vec<_> = super::read_dir(&dir, &opts, true).map_err(|e| format!("{:?}", e))?.collect();
+	fs::remove_dir_all(&dir).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(results.iter().map(|(p, _)| p.file_name().unwrap().to_str().unwrap()).collect::<Vec<_>>(),
+		vec!["a.slp", "b.slp", "d.slp", "e.slp"]);
+	assert_eq!(results[0].1.as_ref().map_err(|e| format!("{:?}", e))?, &game("ics")?);
+	assert!(results[1].1.is_err());
+	assert!(results[2].1.as_ref().err().ok_or("expected an error")?.to_string().contains("panicked"));
+	assert_eq!(results[3].1.as_ref().map_err(|e| format!("{:?}", e))?, &game("ics")?);
+
+	assert!(super::read_dir(path::Path::new("test/missing"), &opts, true).is_err());
+	Ok(())
+}
+
+#[test]
+fn compressed() -> Result<(), String> {
+	let bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	#[cfg(feature = "gzip")]
+	let gzip = {
+		use std::io::Write;
+		let mut e = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+		e.write_all(&bytes).map_err(|e| format!("{:?}", e))?;
+		e.finish().map_err(|e| format!("{:?}", e))?
+	};
+	#[cfg(not(feature = "gzip"))]
+	let gzip = vec![0x1f, 0x8b, 0x08, 0x00];
+	#[cfg(feature = "zstd")]
+	let zstd = zstd::encode_all(&*bytes, 0).map_err(|e| format!("{:?}", e))?;
+	#[cfg(not(feature = "zstd"))]
+	let zstd = vec![0x28, 0xb5, 0x2f, 0xfd];
+
+	let dir = std::env::temp_dir().join(format!("peppi-compressed-{}", std::process::id()));
+	fs::create_dir_all(&dir).map_err(|e| format!("{:?}", e))?;
+	fs::write(dir.join("game.slp"), &bytes).map_err(|e| format!("{:?}", e))?;
+	fs::write(dir.join("game.slp.gz"), gzip).map_err(|e| format!("{:?}", e))?;
+	fs::write(dir.join("game.slp.zst"), zstd).map_err(|e| format!("{:?}", e))?;
+	let results:Vec<_> = super::read_dir(&dir, &Default::default(), true).map_err(|e| format!("{:?}", e))?.collect();
+	fs::remove_dir_all(&dir).map_err(|e| format!("{:?}", e))?;
+
+	let expected = game("game")?;
+	assert_eq!(results.iter().map(|(p, _)| p.file_name().unwrap().to_str().unwrap()).collect::<Vec<_>>(),
+		vec!["game.slp", "game.slp.gz", "game.slp.zst"]);
+	assert_eq!(results[0].1.as_ref().map_err(|e| format!("{:?}", e))?, &expected);
+	for ((_, result), feature) in results[1 ..].iter().zip(&[cfg!(feature = "gzip"), cfg!(feature = "zstd")]) {
+		match (result, feature) {
+			(Ok(game), true) => assert_eq!(game, &expected),
+			(Err(e), false) => assert!(e.to_string().contains("feature"), "{}", e),
+			(result, _) => Err(format!("unexpected result: {:?}", result.as_ref().map(|_| ())))?,
+		}
+	}
+	Ok(())
+}
+
+#[test]
+fn efficiency_score() -> Result<(), String> {
+	use super::stats::*;
+	let game = game("game")?;
+	let conversions = conversions(&game);
+	let e = Efficiency::new(&conversions, 0);
+	assert_eq!(e.conversions, conversion_rate(&game, 0));
+	assert_eq!(e.neutral_win_ratio, 13.0 / 14.0);
+	assert_eq!(e.openings_per_kill, Some(13.0 / 4.0));
+
+	let expected = 100.0 * (
+		EFFICIENCY_NEUTRAL_WEIGHT * e.neutral_win_ratio as f64 +
+		EFFICIENCY_DAMAGE_WEIGHT * (e.conversions.damage_per_opening as f64 / EFFICIENCY_DAMAGE_SCALE).min(1.0) +
+		EFFICIENCY_KILL_WEIGHT / e.openings_per_kill.unwrap() as f64);
+	assert!((e.score - expected).abs() < 0.001);
+	assert_eq!(efficiency_score(&game, 0), e.score);
+
+	let cpu = Efficiency::new(&conversions, 1);
+	assert!((cpu.neutral_win_ratio - (1.0 - e.neutral_win_ratio)).abs() < 0.0001);
+	assert_eq!(cpu.openings_per_kill, None);
+	assert!(cpu.score < e.score);
+	assert!((0.0 ..= 100.0).contains(&e.score) && (0.0 ..= 100.0).contains(&cpu.score));
+	Ok(())
+}
+
+#[test]
+fn best_display_name() -> Result<(), String> {
+	let mut netplay = game("netplay_name")?;
+	assert_eq!(netplay.display_name(0), Some("Player1".to_string()));
+	assert_eq!(netplay.display_name(2), None);
+
+	let players = netplay.metadata.players.as_mut().ok_or("missing metadata.players")?;
+	let meta = players[1].as_mut().ok_or("missing metadata player 1")?;
+	meta.netplay_name = None;
+	meta.netplay_code = Some("METO#001".to_string());
+	assert_eq!(netplay.display_name(1), Some("METO#001".to_string()));
+
+	let player = netplay.start.players[1].as_mut().ok_or("missing player 1")?;
+	player.v1_0.as_mut().and_then(|v| v.v1_3.as_mut()).ok_or("no v1.3 data")?.name_tag = "MET".to_string();
+	assert_eq!(player.name_tag(), Some("MET"));
+	assert_eq!(player.best_display_name(None), "MET");
+	assert_eq!(netplay.display_name(1), Some("METO#001".to_string()));
+
+	// no netplay names or tags at all
+	let local = game("game")?;
+	assert_eq!(local.display_name(0), Some("Marth".to_string()));
+	assert_eq!(local.display_name(1), Some("Fox".to_string()));
+	let mut falcon = local.start.players[0].clone().ok_or("missing player 0")?;
+	falcon.character = External::CAPTAIN_FALCON;
+	assert_eq!(falcon.best_display_name(None), "Captain Falcon");
+	Ok(())
+}
+
+#[test]
+fn streaming_writer() -> Result<(), String> {
+	use super::streaming_writer::{self, StreamingWriter};
+	for name in &["game", "unknown_event", "ics"] {
+		let expected = game(name)?;
+		let bytes = fs::read(format!("test/replays/{}.slp", name)).map_err(|e| format!("{:?}", e))?;
+
+		let copied = streaming_writer::copy(&*bytes, io::Cursor::new(Vec::new()))
+			.map_err(|e| format!("{:?}", e))?.into_inner();
+		assert_eq!(game_from_bytes(&copied, &mut Default::default())?, expected);
+		assert_eq!(copied, bytes);
+
+		// without `finalize`, the output reads as an in-progress replay
+		let mut writer = StreamingWriter::new(Vec::new());
+		super::parse::parse(&*bytes, &mut writer).map_err(|e| format!("{:?}", e))?;
+		let raw_len = writer.raw_len() as usize;
+		let unfinalized = writer.into_inner();
+		assert_eq!(&unfinalized[11 .. 15], &[0, 0, 0, 0]);
+		assert_eq!(unfinalized[15 .. 15 + raw_len], copied[15 .. 15 + raw_len]);
+		assert_eq!(game_from_bytes(&unfinalized, &mut Default::default())?, expected);
+	}
+	Ok(())
+}
+
+#[test]
+fn frame_to_clock() -> Result<(), String> {
+	use std::time::Duration;
+	use super::game::{FIRST_FRAME_INDEX, TimerType};
+	let mut game = game("game")?;
+	let start = &mut game.start;
+	assert_eq!(start.timer_type(), TimerType::DECREASING);
+	assert_eq!(start.frame_to_clock(FIRST_FRAME_INDEX), Some(Duration::from_secs(480)));
+	assert_eq!(start.frame_to_clock(0), Some(Duration::from_secs(480)));
+	// the last stock was lost at 6:35.25
+	assert_eq!(start.elapsed(5085), Duration::from_millis(84_750));
+	assert_eq!(start.frame_to_clock(5085), Some(Duration::from_millis(395_250)));
+	assert_eq!(start.frame_to_clock(480 * 60 + 1), Some(Duration::from_secs(0)));
+	assert_eq!(start.elapsed(1), Duration::from_nanos(16_666_666));
+
+	start.bitfield[0] = start.bitfield[0] & !0b11 | TimerType::INCREASING.0;
+	assert_eq!(start.frame_to_clock(5085), Some(Duration::from_millis(84_750)));
+	start.bitfield[0] &= !0b11;
+	assert_eq!(start.frame_to_clock(5085), None);
+	Ok(())
+}
+
+#[test]
+fn rules() -> Result<(), String> {
+	use super::game::{GameMode, ItemFrequency, TimerType};
+	let mut game = game("game")?;
+	let rules = game.start.rules();
+	assert_eq!(rules.mode, GameMode::STOCK);
+	assert_eq!(rules.timer_type, TimerType::DECREASING);
+	assert_eq!(rules.timer, 480);
+	assert_eq!(rules.stocks, [Some(4), Some(4), None, None]);
+	assert_eq!(rules.self_destruct_score, -1);
+	assert_eq!(rules.items.frequency, ItemFrequency::OFF);
+	assert_eq!(rules.damage_ratio, 1.0);
+	assert!(!rules.is_teams);
+	assert!(game.start.is_stock_match());
+	assert!(!game.start.is_time_match());
+
+	// no teams replay in the test set, so make one
+	game.start.is_teams = true;
+	game.start.bitfield[1] |= 0b1;
+	let rules = game.start.rules();
+	assert!(rules.is_teams);
+	assert!(rules.friendly_fire);
+	assert!(game.start.is_friendly_fire());
+	game.start.bitfield[1] &= !0b1;
+	assert!(!game.start.rules().friendly_fire);
+
+	#[cfg(feature = "serde")] {
+		let json = serde_json::to_value(rules).map_err(|e| format!("{:?}", e))?;
+		assert_eq!(json["mode"], serde_json::json!(GameMode::STOCK.0));
+		assert_eq!(json["friendly_fire"], serde_json::json!(true));
+	}
+	Ok(())
+}
+
+#[test]
+fn hitstun() -> Result<(), String> {
+	let windows = super::stats::hitstun(&game("v2.0")?, 0);
+	assert!(windows.iter().all(|w| w.end_index >= w.start_index));
+
+	// a six-hit true combo, ending with a hit the victim escaped
+	let combo:Vec<_> = windows.iter().filter(|w| (4732 ..= 4776).contains(&w.start_index)).collect();
+	assert_eq!(combo.iter().map(|w| w.start_index).collect::<Vec<_>>(), vec![4732, 4739, 4740, 4745, 4770, 4776]);
+	assert!(combo[.. 5].iter().all(|w| w.comboed));
+	assert!(!combo[5].comboed);
+	assert_eq!(combo[0].frames, Some(38));
+	assert_eq!(combo[5].frames, Some(45));
+	assert_eq!(combo[5].end_index, 4824);
+
+	// older replays don't record hitstun
+	let old = super::stats::hitstun(&game("v0.1")?, 0);
+	assert!(!old.is_empty());
+	assert!(old.iter().all(|w| w.frames.is_none() && !w.comboed && w.end_index == w.start_index));
+	Ok(())
+}
+
+#[test]
+fn true_combos() -> Result<(), String> {
+	let conversions = super::stats::conversions(&game("v2.0")?);
+	let conversion = |start:i32| conversions.iter().find(|c| c.victim == 0 && c.start_index == start);
+
+	// three hits, each inside the previous one's hitstun
+	let combo = conversion(518).ok_or("missing conversion at 518")?;
+	assert_eq!(combo.hits, 3);
+	assert!(combo.is_true);
+
+	// the victim got out of hitstun between the first and second hits
+	let string = conversion(848).ok_or("missing conversion at 848")?;
+	assert_eq!(string.hits, 3);
+	assert!(!string.is_true);
+
+	assert!(!conversion(161).ok_or("missing conversion at 161")?.is_true); // single hit
+
+	// the same two sequences as combos
+	let combos = super::stats::combos(&game("v2.0")?, 1, super::stats::COMBO_RESET_FRAMES);
+	let combo = |start:i32| combos.iter().find(|c| c.victim == 0 && c.start_index == start);
+	assert!(combo(518).ok_or("missing combo at 518")?.is_true);
+	assert!(!combo(848).ok_or("missing combo at 848")?.is_true);
+
+	let old = super::stats::conversions(&game("v0.1")?);
+	assert!(old.iter().any(|c| c.hits > 1));
+	assert!(old.iter().all(|c| !c.is_true));
+	assert!(super::stats::combos(&game("v0.1")?, 1, super::stats::COMBO_RESET_FRAMES).iter().all(|c| !c.is_true));
+	Ok(())
+}
+
+#[test]
+fn events() -> Result<(), String> {
+	use super::parse::ParsedEvent;
+	let game = game("game")?;
+	let frames = game.ports[0].as_ref().ok_or("port 0 missing")?.leader.pre.len();
+
+	let bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	let events = super::events(&*bytes).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(events.len(), 1 + frames * 4 + 1 + 1);
+	assert_eq!(events[0], ParsedEvent::GameStart(Box::new(game.start.clone())));
+	assert_eq!(events[events.len() - 2], ParsedEvent::GameEnd(game.end.clone()));
+	assert_eq!(events[events.len() - 1], ParsedEvent::Metadata(game.metadata.json.clone()));
+
+	// each frame is pre for both ports, then post for both ports
+	let ports:Vec<(char, u8)> = events[1 .. 5].iter().map(|e| match e {
+		ParsedEvent::FramePre(f) => ('<', f.id.port),
+		ParsedEvent::FramePost(f) => ('>', f.id.port),
+		_ => ('?', 255),
+	}).collect();
+	assert_eq!(ports, vec![('<', 0), ('<', 1), ('>', 0), ('>', 1)]);
+	match &events[1] {
+		ParsedEvent::FramePre(f) => assert_eq!(f.event, game.ports[0].as_ref().unwrap().leader.pre[0]),
+		e => Err(format!("expected frame pre, but got: {:?}", e))?,
+	}
+
+	let unknown = super::events(fs::File::open("test/replays/unknown_event.slp").map_err(|e| format!("{:?}", e))?)
+		.map_err(|e| format!("{:?}", e))?;
+	assert!(unknown.iter().any(|e| matches!(e, ParsedEvent::Unknown(_, _))));
+	Ok(())
+}
+
+#[test]
+fn items() -> Result<(), String> {
+	use super::frame::{Direction, Item, ItemV3_2, ItemV3_6};
+	use super::parse::ParsedEvent;
+
+	let payload = |index:i32, size:usize| -> Vec<u8> {
+		let mut p = vec![0x3b];
+		p.extend_from_slice(&index.to_be_bytes());
+		p.extend_from_slice(&super::item::Item::RED_SHELL.0.to_be_bytes());
+		p.push(2); // state
+		p.extend_from_slice(&(-1.0f32).to_be_bytes());
+		for v in &[1.5f32, -0.5, 10.0, 20.0] {
+			p.extend_from_slice(&v.to_be_bytes());
+		}
+		p.extend_from_slice(&7u16.to_be_bytes()); // damage
+		p.extend_from_slice(&300.0f32.to_be_bytes()); // timer
+		p.extend_from_slice(&42u32.to_be_bytes()); // spawn id
+		p.extend_from_slice(&[1, 2, 3, 4]); // misc
+		p.push(1); // owner
+		p.resize(1 + size, 0xee); // pad or truncate to the declared size
+		p
+	};
+
+	let expected = Item {
+		index: 100,
+		r#type: super::item::Item::RED_SHELL,
+		state: 2,
+		direction: Some(Direction::LEFT),
+		velocity: Position { x: 1.5, y: -0.5 },
+		position: Position { x: 10.0, y: 20.0 },
+		damage: 7,
+		timer: 300.0,
+		id: 42,
+		v3_2: Some(ItemV3_2 { misc: [1, 2, 3, 4], v3_6: Some(ItemV3_6 { owner: Some(1) }) }),
+	};
+
+	// v3.0 (no misc or owner), v3.6, and a future version with extra trailing fields
+	for (size, item) in &[
+		(37, Item { v3_2: None, ..expected }),
+		(42, expected),
+		(50, expected),
+	] {
+		let mut bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+		let mut declaration = vec![0x3b];
+		declaration.extend_from_slice(&(*size as u16).to_be_bytes());
+		bytes.splice(29 .. 29, declaration);
+		bytes[16] += 3;
+		// insert after Game Start, which is the first event after Event Payloads
+		let start = 15 + 1 + bytes[16] as usize;
+		bytes.splice(start + 353 .. start + 353, payload(100, *size));
+		let raw_len = u32::from_be_bytes([bytes[11], bytes[12], bytes[13], bytes[14]]) + 3 + 1 + *size as u32;
+		bytes[11 .. 15].copy_from_slice(&raw_len.to_be_bytes());
+
+		let events = super::events(&*bytes).map_err(|e| format!("{:?}", e))?;
+		match &events[1] {
+			ParsedEvent::Item(i) => {
+				assert_eq!(&i.event, item);
+				assert_eq!((i.id.index, i.id.id), (100, 42));
+			},
+			e => Err(format!("expected item, but got: {:?}", e))?,
+		}
+		assert_eq!(events.iter().filter(|e| matches!(e, ParsedEvent::Item(_))).count(), 1);
+	}
+	Ok(())
+}
+
+#[test]
+fn frame_start() -> Result<(), String> {
+	use super::frame::Start;
+	use super::parse::ParsedEvent;
+
+	let mut bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	bytes.splice(29 .. 29, vec![0x3a, 0x00, 0x0c]);
+	bytes[16] += 3;
+	// right after Game Start, before the first frame's Pre events
+	let first_frame = 32 + 1 + 352;
+	assert_eq!(bytes[first_frame], 0x37);
+	let mut start = vec![0x3a];
+	start.extend_from_slice(&(-123i32).to_be_bytes());
+	start.extend_from_slice(&0x1234_5678u32.to_be_bytes());
+	start.extend_from_slice(&7u32.to_be_bytes());
+	bytes.splice(first_frame .. first_frame, start);
+	let raw_len = u32::from_be_bytes([bytes[11], bytes[12], bytes[13], bytes[14]]) + 3 + 13;
+	bytes[11 .. 15].copy_from_slice(&raw_len.to_be_bytes());
+
+	let events = super::events(&*bytes).map_err(|e| format!("{:?}", e))?;
+	match &events[1] {
+		ParsedEvent::FrameStart(s) => {
+			assert_eq!(s.id, -123);
+			assert_eq!(s.event, Start { index: -123, random_seed: 0x1234_5678, scene_frame_counter: Some(7) });
+		},
+		e => Err(format!("expected frame start, but got: {:?}", e))?,
+	}
+	assert!(matches!(events[2], ParsedEvent::FramePre(_)));
+	assert!(!events.iter().any(|e| matches!(e, ParsedEvent::Unknown(_, _))));
+
+	// frames are otherwise unaffected
+	let parsed = super::game_reader(io::Cursor::new(&bytes), &mut Default::default()).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(parsed.ports, game("game")?.ports);
+	Ok(())
+}
+
+#[test]
+fn frame_bookend() -> Result<(), String> {
+	use super::frame::Bookend;
+	use super::parse::ParsedEvent;
+
+	let mut bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	bytes.splice(29 .. 29, vec![0x3c, 0x00, 0x08]);
+	bytes[16] += 3;
+	let raw_len = u32::from_be_bytes([bytes[11], bytes[12], bytes[13], bytes[14]]) as usize + 3;
+	// replace Game End (the last event) with a bookend, as if the game was cut short
+	let game_end = 15 + raw_len - 2;
+	assert_eq!(bytes[game_end], 0x39);
+	let mut bookend = vec![0x3c];
+	bookend.extend_from_slice(&5124i32.to_be_bytes());
+	bookend.extend_from_slice(&5117i32.to_be_bytes());
+	bytes.splice(game_end .. game_end + 2, bookend);
+	let raw_len = raw_len + 9 - 2;
+
+	for raw_len in &[raw_len as u32, 0] { // the in-progress case has to find the metadata itself
+		bytes[11 .. 15].copy_from_slice(&raw_len.to_be_bytes());
+		let events = super::events(&*bytes).map_err(|e| format!("{:?}", e))?;
+		assert!(matches!(events[events.len() - 1], ParsedEvent::Metadata(_)));
+		match &events[events.len() - 2] {
+			ParsedEvent::FrameBookend(b) => {
+				assert_eq!(b.id, 5124);
+				assert_eq!(b.event, Bookend { index: 5124, latest_finalized_frame: Some(5117) });
+			},
+			e => Err(format!("expected frame bookend, but got: {:?}", e))?,
+		}
+		assert!(!events.iter().any(|e| matches!(e, ParsedEvent::GameEnd(_))));
+	}
+	Ok(())
+}
+
+#[test]
+fn gecko_list() -> Result<(), String> {
+	use super::event_counter::{EventCount, EventCounter};
+	use super::parse::ParsedEvent;
+	let expected = game("game")?;
+
+	let codes:Vec<u8> = (0 .. 200).map(|i| i as u8).collect();
+	let mut bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	bytes.splice(29 .. 29, vec![0x3d, 0x00, 200]);
+	bytes[16] += 3;
+	let start = 15 + 1 + bytes[16] as usize;
+	let mut event = vec![0x3d];
+	event.extend_from_slice(&codes);
+	bytes.splice(start + 353 .. start + 353, event); // right after Game Start
+	let raw_len = u32::from_be_bytes([bytes[11], bytes[12], bytes[13], bytes[14]]) + 3 + 201;
+	bytes[11 .. 15].copy_from_slice(&raw_len.to_be_bytes());
+
+	let events = super::events(&*bytes).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(events[1], ParsedEvent::GeckoList(codes));
+	assert_eq!(game_from_bytes(&bytes, &mut Default::default())?, expected);
+
+	let mut counter = EventCounter::default();
+	super::parse(io::Cursor::new(&bytes), &mut counter).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(counter.counts[&0x3d], EventCount { count: 1, bytes: 201 });
+	Ok(())
+}
+
+/// Offsets and codes of each event in the raw element of `bytes`, after Event Payloads.
+fn raw_event_offsets(bytes:&[u8]) -> Vec<(usize, usize, u8)> {
+	let sizes:HashMap<u8, usize> = bytes[17 .. 16 + bytes[16] as usize].chunks(3)
+		.map(|c| (c[0], u16::from_be_bytes([c[1], c[2]]) as usize))
+		.collect();
+	let raw_end = 15 + u32::from_be_bytes([bytes[11], bytes[12], bytes[13], bytes[14]]) as usize;
+	let mut events = Vec::new();
+	let mut pos = 16 + bytes[16] as usize;
+	while pos < raw_end {
+		let end = pos + 1 + sizes[&bytes[pos]];
+		events.push((pos, end, bytes[pos]));
+		pos = end;
+	}
+	events
+}
+
+#[test]
+fn rollback() -> Result<(), String> {
+	use super::parse::{ParseOptions, RollbackMode};
+	let expected = game("game")?;
+	let original = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	let frames = expected.ports[0].as_ref().unwrap().leader.pre.len();
+
+	// re-send frame 100's events (with port 0's damage changed) after frame 101's
+	let events = raw_event_offsets(&original);
+	let frame_events = |n:usize| &events[1 + n * 4 .. 1 + (n + 1) * 4];
+	let (start, _, _) = frame_events(102)[0];
+	let mut resent = Vec::new();
+	for &(s, e, _) in frame_events(100) {
+		resent.extend_from_slice(&original[s .. e]);
+	}
+	let post_0 = frame_events(100)[2].0 - frame_events(100)[0].0;
+	resent[post_0 + 22 .. post_0 + 26].copy_from_slice(&99.0f32.to_be_bytes()); // damage
+
+	let with_rollback = |extra:&[u8]| -> Vec<u8> {
+		let mut bytes = original.clone();
+		bytes.splice(start .. start, extra.iter().copied());
+		let raw_len = u32::from_be_bytes([bytes[11], bytes[12], bytes[13], bytes[14]]) + extra.len() as u32;
+		bytes[11 .. 15].copy_from_slice(&raw_len.to_be_bytes());
+		bytes
+	};
+
+	let bytes = with_rollback(&resent);
+	let dedup = game_from_bytes(&bytes, &mut Default::default())?;
+	let post = &dedup.ports[0].as_ref().unwrap().leader.post;
+	assert_eq!(post.len(), frames);
+	assert!(post.windows(2).all(|w| w[1].index == w[0].index + 1));
+	assert_eq!(post[100].damage, 99.0);
+	assert_eq!(dedup.ports[1], expected.ports[1]);
+
+	let keep_all = game_from_bytes(&bytes, &mut ParseOptions { rollback: RollbackMode::KeepAll, ..Default::default() })?;
+	let post = &keep_all.ports[0].as_ref().unwrap().leader.post;
+	assert_eq!(post.len(), frames + 1);
+	assert_eq!(post[100], expected.ports[0].as_ref().unwrap().leader.post[100]);
+	assert_eq!((post[102].index, post[102].damage), (expected.first_frame_index() + 100, 99.0));
+	// frame 100 appears twice, so there's no one frame to look up
+	assert_eq!(keep_all.post(0, expected.first_frame_index() + 100), None);
+	assert_eq!(keep_all.pre(0, expected.first_frame_index()), None);
+	assert_eq!(dedup.post(0, expected.first_frame_index() + 100).map(|p| p.damage), Some(99.0));
+
+	// frames already finalized by a bookend aren't replaced
+	let mut finalized = vec![0x3c];
+	finalized.extend_from_slice(&(expected.first_frame_index() + 101).to_be_bytes());
+	finalized.extend_from_slice(&(expected.first_frame_index() + 100).to_be_bytes());
+	finalized.extend_from_slice(&resent);
+	let mut bytes = with_rollback(&finalized);
+	bytes.splice(29 .. 29, vec![0x3c, 0x00, 0x08]);
+	bytes[16] += 3;
+	let raw_len = u32::from_be_bytes([bytes[11], bytes[12], bytes[13], bytes[14]]) + 3;
+	bytes[11 .. 15].copy_from_slice(&raw_len.to_be_bytes());
+	let game = game_from_bytes(&bytes, &mut Default::default())?;
+	assert_eq!(game.ports, expected.ports);
+	Ok(())
+}
+
+#[test]
+fn ubjson_floats() -> Result<(), String> {
+	use super::ubjson::{self, Object};
+	let mut bytes = vec![0x55, 1, b'd', 0x64];
+	bytes.extend_from_slice(&1.5f32.to_be_bytes());
+	bytes.extend_from_slice(&[0x55, 1, b'D', 0x44]);
+	bytes.extend_from_slice(&0.1f64.to_be_bytes());
+	bytes.push(0x7d);
+
+	let m = ubjson::parse_map(&mut &bytes[..]).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(m, map! { "d" => 1.5, "D" => 0.1 });
+	#[cfg(feature = "serde")]
+	assert_eq!(serde_json::to_string(&m["D"]).map_err(|e| format!("{:?}", e))?, "0.1");
+
+	let mut written = Vec::new();
+	ubjson::write_map(&mut written, &m).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(ubjson::parse_map(&mut &written[..]).map_err(|e| format!("{:?}", e))?, m);
+	assert_eq!(m["d"], Object::Float(1.5));
+	Ok(())
+}
+
+#[test]
+fn ubjson_ints() -> Result<(), String> {
+	use super::ubjson;
+	let mut bytes = vec![];
+	bytes.extend_from_slice(&[0x55, 2, b'i', b'8', 0x69, 0xfe]);
+	bytes.extend_from_slice(&[0x55, 2, b'u', b'8', 0x55, 0xfe]);
+	bytes.extend_from_slice(&[0x55, 3, b'i', b'1', b'6', 0x49, 0x80, 0x00]);
+	bytes.extend_from_slice(&[0x55, 3, b'i', b'6', b'4', 0x4c]);
+	bytes.extend_from_slice(&(1i64 << 40).to_be_bytes());
+	bytes.push(0x7d);
+
+	let m = ubjson::parse_map(&mut &bytes[..]).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(m, map! {
+		"i8" => -2i64,
+		"u8" => 254i64,
+		"i16" => -32768i64,
+		"i64" => 1i64 << 40,
+	});
+
+	let mut written = Vec::new();
+	ubjson::write_map(&mut written, &m).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(ubjson::parse_map(&mut &written[..]).map_err(|e| format!("{:?}", e))?, m);
+	Ok(())
+}
+
+#[test]
+fn ubjson_arrays() -> Result<(), String> {
+	#[cfg(feature = "serde")]
+	use super::query::Query;
+	use super::ubjson::{self, Object};
+	let mut bytes = vec![];
+	// untyped: [1, "a"]
+	bytes.extend_from_slice(&[0x55, 1, b'u', 0x5b, 0x69, 1, 0x53, 0x55, 1, b'a', 0x5d]);
+	// counted: [2, 3]
+	bytes.extend_from_slice(&[0x55, 1, b'c', 0x5b, 0x23, 0x55, 2, 0x69, 2, 0x55, 3]);
+	// typed and counted, as for `raw`: [4, 5, 6]
+	bytes.extend_from_slice(&[0x55, 1, b't', 0x5b, 0x24, 0x55, 0x23, 0x69, 3, 4, 5, 6]);
+	bytes.push(0x7d);
+
+	let m = ubjson::parse_map(&mut &bytes[..]).map_err(|e| format!("{:?}", e))?;
+	let ints = |xs:&[i64]| Object::Array(xs.iter().map(|&x| Object::Int(x)).collect());
+	assert_eq!(m["u"], Object::Array(vec![Object::Int(1), Object::Str("a".to_string())]));
+	assert_eq!(m["c"], ints(&[2, 3]));
+	assert_eq!(m["t"], ints(&[4, 5, 6]));
+	#[cfg(feature = "serde")]
+	assert_eq!(m["t"].query_json(&["1"]).map_err(|e| format!("{:?}", e))?, 5);
+
+	let mut written = Vec::new();
+	ubjson::write_map(&mut written, &m).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(ubjson::parse_map(&mut &written[..]).map_err(|e| format!("{:?}", e))?, m);
+	Ok(())
+}
+
+#[test]
+fn ubjson_round_trip() -> Result<(), String> {
+	use super::ubjson;
+	for name in &["game", "ics", "netplay_name", "console_name", "nintendont"] {
+		let json = game(name)?.metadata.json;
+		assert!(!json.is_empty());
+		let mut written = Vec::new();
+		ubjson::write_map(&mut written, &json).map_err(|e| format!("{:?}", e))?;
+		assert_eq!(ubjson::parse_map(&mut &written[..]).map_err(|e| format!("{:?}", e))?, json);
+	}
+
+	let mut written = Vec::new();
+	ubjson::write_map(&mut written, &map! { "k" => "v" }).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(written, vec![0x55, 1, b'k', 0x53, 0x55, 1, b'v', 0x7d]);
+
+	// keys keep the order they were read in
+	let bytes = [0x55, 1, b'b', 0x6c, 0, 0, 0, 1, 0x55, 1, b'a', 0x6c, 0, 0, 0, 2, 0x7d];
+	let m = ubjson::parse_map(&mut &bytes[..]).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(m.keys().collect::<Vec<_>>(), vec!["b", "a"]);
+	let mut written = Vec::new();
+	ubjson::write_map(&mut written, &m).map_err(|e| format!("{:?}", e))?;
+	assert_eq!(written, bytes);
+	Ok(())
+}
+
+#[test]
+fn parse_errors() -> Result<(), String> {
+	use super::parse::{self, Error, Handlers};
+	struct Nop;
+	impl Handlers for Nop {}
+	let original = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	let parse = |bytes:&[u8]| parse::parse(bytes, &mut Nop);
+
+	assert!(matches!(parse(&original[.. 1000]), Err(Error::UnexpectedEof)));
+
+	let mut bytes = original.clone();
+	bytes[3] = b'w';
+	match parse(&bytes) {
+		Err(Error::BadMagic { expected, got }) => assert_eq!((expected[3], got[3]), (b'r', b'w')),
+		r => return Err(format!("expected BadMagic: {:?}", r)),
+	}
+
+	let mut bytes = original.clone();
+	bytes[16] += 1;
+	assert!(matches!(parse(&bytes), Err(Error::InvalidPayloadSize(_))));
+
+	let mut bytes = original.clone();
+	let start = 15 + 1 + bytes[16] as usize;
+	bytes[start] = 0x20;
+	assert!(matches!(parse(&bytes), Err(Error::UnknownEvent(0x20))));
+
+	// through `peppi::ParseError`, as returned by `game`
+	let e = super::game(path::Path::new("test/replays/missing.slp")).unwrap_err();
+	assert!(matches!(e.error, Error::Io(ref e) if e.kind() == io::ErrorKind::NotFound));
+	Ok(())
+}
+
+#[test]
+fn follower_death() -> Result<(), String> {
+	let expected = game("ics")?;
+	let original = fs::read("test/replays/ics.slp").map_err(|e| format!("{:?}", e))?;
+	let last_nana_frame = expected.first_frame_index() + 200;
+
+	// drop Nana's frame events after `last_nana_frame`, as if she'd died
+	let mut bytes = original[.. 16 + original[16] as usize].to_vec();
+	let mut dropped = 0;
+	for (start, end, code) in raw_event_offsets(&original) {
+		let e = &original[start .. end];
+		let is_frame = code == 0x37 || code == 0x38;
+		if is_frame && e[6] != 0 && i32::from_be_bytes([e[1], e[2], e[3], e[4]]) > last_nana_frame {
+			dropped += end - start;
+		} else {
+			bytes.extend_from_slice(e);
+		}
+	}
+	let raw_end = 15 + u32::from_be_bytes([original[11], original[12], original[13], original[14]]) as usize;
+	bytes.extend_from_slice(&original[raw_end ..]);
+	let raw_len = (raw_end - 15 - dropped) as u32;
+	bytes[11 .. 15].copy_from_slice(&raw_len.to_be_bytes());
+
+	let game = game_from_bytes(&bytes, &mut Default::default())?;
+	let port = game.ports[0].as_ref().ok_or("missing port 0")?;
+	let follower = port.follower.as_ref().ok_or("missing follower")?;
+	assert_eq!(port.leader, expected.ports[0].as_ref().unwrap().leader);
+	assert_eq!(follower.pre.last().map(|f| f.index), Some(last_nana_frame));
+	assert_eq!(follower.post.len(), follower.pre.len());
+
+	let solo = game.find_frames(|f| f.pre(0).is_some() && f.follower_pre(0).is_none());
+	assert_eq!(solo.first(), Some(&(last_nana_frame + 1)));
+	assert_eq!(solo.len(), port.leader.pre.len() - follower.pre.len());
+	Ok(())
+}
+
+#[test]
+fn skip_frames() -> Result<(), String> {
+	let expected = game("game")?;
+	let bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	let mut opts = super::parse::ParseOptions { skip_frames: true, ..Default::default() };
+	let game = game_from_bytes(&bytes, &mut opts)?;
+	assert_eq!(game.start, expected.start);
+	assert_eq!(game.end, expected.end);
+	assert_eq!(game.metadata, expected.metadata);
+	assert!(game.ports.iter().all(|p| p.is_none()));
+	Ok(())
+}
+
+#[test]
+fn parse_slice() -> Result<(), String> {
+	use super::parse::Error;
+	for name in ["game", "v0.1", "ics", "transform", "unknown_event"].iter() {
+		let bytes = fs::read(format!("test/replays/{}.slp", name)).map_err(|e| format!("{:?}", e))?;
+		let mut game_parser = super::game_parser::GameParser::default();
+		super::parse::parse_slice(&bytes, &mut game_parser).map_err(|e| format!("{:?}", e))?;
+		assert_eq!(game_parser.into_game().map_err(|e| format!("{:?}", e))?, game(name)?);
+	}
+
+	// a payload cut short by the end of the data
+	let bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	let (start, end, _) = raw_event_offsets(&bytes)[10];
+	let mut game_parser = super::game_parser::GameParser::default();
+	assert!(matches!(super::parse::parse_slice(&bytes[.. (start + end) / 2], &mut game_parser), Err(Error::UnexpectedEof)));
+	Ok(())
+}
+
+#[test]
+fn parse_partial() -> Result<(), String> {
+	use super::game_parser::GameParser;
+	use super::parse::{parse_partial, parse_partial_opts, Error, ParseOptions};
+	let expected = game("game")?;
+	let bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+
+	let mut parser = GameParser::default();
+	assert!(!parse_partial(&*bytes, &mut parser).map_err(|e| format!("{:?}", e))?);
+	assert_eq!(parser.into_game().map_err(|e| format!("{:?}", e))?, expected);
+
+	// cut off between frame events
+	let (start, end, code) = raw_event_offsets(&bytes)[5000];
+	assert!(code == 0x37 || code == 0x38);
+	let mut parser = GameParser::default();
+	assert!(parse_partial(&bytes[.. start], &mut parser).map_err(|e| format!("{:?}", e))?);
+	assert_eq!(parser.start.as_ref(), Some(&expected.start));
+	assert!(parser.end.is_none());
+	let post = &parser.ports[0].as_ref().ok_or("missing port 0")?.leader.post;
+	assert!(!post.is_empty());
+	assert_eq!(post[..], expected.ports[0].as_ref().unwrap().leader.post[.. post.len()]);
+
+	let game = parser.into_partial_game().map_err(|e| format!("{:?}", e))?;
+	assert_eq!(game.end.method, super::game::EndMethod::UNRESOLVED);
+	assert_eq!(game.end.lras_port(), None);
+
+	// but not partway through one, which could just as well be a bad payload size
+	assert!(matches!(parse_partial(&bytes[.. (start + end) / 2], &mut GameParser::default()), Err(Error::UnexpectedEof)));
+
+	// options apply
+	let mut parser = GameParser::default();
+	let mut opts = ParseOptions { skip_frames: true, ..Default::default() };
+	assert!(parse_partial_opts(&bytes[.. start], &mut parser, &mut opts).map_err(|e| format!("{:?}", e))?);
+	assert_eq!(parser.start.as_ref(), Some(&expected.start));
+	assert!(parser.ports.iter().all(|p| p.is_none()));
+
+	// whole games from files
+	let path = std::env::temp_dir().join(format!("peppi-partial-{}.slp", std::process::id()));
+	fs::write(&path, &bytes[.. start]).map_err(|e| format!("{:?}", e))?;
+	let partial = super::game_partial(&path, &mut Default::default());
+	fs::remove_file(&path).map_err(|e| format!("{:?}", e))?;
+	let (game, truncated) = partial.map_err(|e| format!("{:?}", e))?;
+	assert!(truncated);
+	assert_eq!(game.start, expected.start);
+	assert_eq!(game.end.method, super::game::EndMethod::UNRESOLVED);
+	assert_eq!(super::game_partial(path::Path::new("test/replays/game.slp"), &mut Default::default())
+		.map_err(|e| format!("{:?}", e))?, (expected, false));
+
+	// nothing to salvage without Game Start
+	let (start, _, code) = raw_event_offsets(&bytes)[0];
+	assert_eq!(code, 0x36);
+	assert!(matches!(parse_partial(&bytes[.. start + 10], &mut GameParser::default()), Err(Error::UnexpectedEof)));
+	Ok(())
+}
+
+#[test]
+fn event_sizes() -> Result<(), String> {
+	struct Sizes(HashMap<u8, u16>);
+	impl super::parse::Handlers for Sizes {
+		fn payload_sizes(&mut self, sizes:&HashMap<u8, u16>) -> io::Result<()> {
+			self.0 = sizes.clone();
+			Ok(())
+		}
+	}
+
+	for name in ["game", "v0.1", "v2.0", "unknown_event"].iter() {
+		let bytes = fs::read(format!("test/replays/{}.slp", name)).map_err(|e| format!("{:?}", e))?;
+		let mut r = &*bytes;
+		let sizes = super::parse::event_sizes(&mut r).map_err(|e| format!("{:?}", e))?;
+		let mut expected = Sizes(HashMap::new());
+		super::parse::parse(&*bytes, &mut expected).map_err(|e| format!("{:?}", e))?;
+		assert_eq!(sizes, expected.0);
+		// positioned at the first event
+		assert_eq!(bytes.len() - r.len(), raw_event_offsets(&bytes)[0].0);
+	}
+	let sizes = super::parse::event_sizes(&mut &*fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?).map_err(|e| format!("{:?}", e))?;
+	for code in [0x36, 0x37, 0x38, 0x39].iter() {
+		assert!(sizes.contains_key(code), "missing {:#x}", code);
+	}
+
+	assert!(super::parse::event_sizes(&mut &[0u8; 4][..]).is_err());
+	Ok(())
+}
+
+#[test]
+fn peek_version() -> Result<(), String> {
+	for (name, version) in &[("v0.1", SlippiVersion(0, 1, 0)), ("v2.0", SlippiVersion(2, 0, 1)), ("game", game("game")?.start.slippi.version)] {
+		let bytes = fs::read(format!("test/replays/{}.slp", name)).map_err(|e| format!("{:?}", e))?;
+		let mut r = &*bytes;
+		assert_eq!(super::parse::peek_version(&mut r).map_err(|e| format!("{:?}", e))?, *version);
+		// stops right after the version bytes
+		assert_eq!(bytes.len() - r.len(), 16 + bytes[16] as usize + 4);
+	}
+	Ok(())
+}
+
+#[test]
+fn character_names() {
+	assert_eq!(Internal::CAPTAIN_FALCON.to_string(), "Captain Falcon");
+	assert_eq!(External::GAME_AND_WATCH.to_string(), "Mr. Game & Watch");
+	assert_eq!(External::ICE_CLIMBERS.to_string(), "Ice Climbers");
+	assert_eq!(Internal(200).to_string(), "Unknown(200)");
+
+	assert_eq!(External::FOX.to_internal(), Some(Internal::FOX));
+	assert_eq!(External::SHEIK.to_internal(), Some(Internal::SHEIK));
+	assert_eq!(External::ICE_CLIMBERS.to_internal(), Some(Internal::POPO));
+	assert_eq!(External(200).to_internal(), None);
+	assert_eq!(Internal::NANA.to_external(), Some(External::ICE_CLIMBERS));
+	assert_eq!(Internal::ZELDA.to_external(), Some(External::ZELDA));
+	assert_eq!(Internal(200).to_external(), None);
+
+	for i in 0 ..= 32 {
+		let c = Internal(i);
+		assert!(c.display_name().is_some());
+		let e = c.to_external().unwrap();
+		if e != External::ICE_CLIMBERS {
+			assert_eq!(e.to_internal(), Some(c));
+		}
+	}
+}
+
+#[test]
+fn costume_names() -> Result<(), String> {
+	use super::character::costume_name;
+	assert_eq!(costume_name(External::FOX, 0), Some("Default"));
+	assert_eq!(costume_name(External::FOX, 3), Some("Green"));
+	assert_eq!(costume_name(External::FOX, 4), None);
+	assert_eq!(costume_name(External::MARTH, 3), Some("Black"));
+	assert_eq!(costume_name(External::YOSHI, 5), Some("Cyan"));
+	assert_eq!(costume_name(External::MASTER_HAND, 0), None);
+	assert_eq!(costume_name(External(200), 0), None);
+	for c in 0 ..= 25 {
+		assert!(costume_name(External(c), 0).is_some());
+	}
+
+	let g = game("game")?;
+	for p in g.start.players.iter().flatten() {
+		assert_eq!(p.costume_name(), costume_name(p.character, p.costume));
+		assert!(p.costume_name().is_some());
+	}
+	Ok(())
+}
+
+#[test]
+fn stage_names() {
+	assert_eq!(Stage::POKEMON_STADIUM.to_string(), "Pokémon Stadium");
+	assert_eq!(Stage::YOSHIS_STORY.to_string(), "Yoshi's Story");
+	assert_eq!(Stage(21).to_string(), "Unknown(21)");
+	assert!(Stage::DREAM_LAND_N64.is_tournament_legal());
+	assert!(!Stage::YOSHIS_ISLAND_N64.is_tournament_legal());
+}
+
+#[test]
+fn position_helpers() {
+	let origin = Position { x: 0.0, y: 0.0 };
+	let p = Position { x: 3.0, y: -4.0 };
+	assert_eq!(origin.distance(&p), 5.0);
+	assert_eq!(p.distance(&origin), 5.0);
+
+	assert_eq!(origin.is_offstage(Stage::BATTLEFIELD), Some(false));
+	assert_eq!(Position { x: 68.0, y: 10.0 }.is_offstage(Stage::BATTLEFIELD), Some(false));
+	assert_eq!(Position { x: -69.0, y: 10.0 }.is_offstage(Stage::BATTLEFIELD), Some(true));
+	assert_eq!(Position { x: 0.0, y: -30.0 }.is_offstage(Stage::FINAL_DESTINATION), Some(true));
+	assert_eq!(Position { x: 80.0, y: 0.0 }.is_offstage(Stage::FINAL_DESTINATION), Some(false));
+	assert_eq!(origin.is_offstage(Stage::YOSHIS_ISLAND_N64), None);
+}
+
+#[test]
+fn attack_names() -> Result<(), String> {
+	use super::attack::Attack;
+	assert_eq!(Attack::FAIR.to_string(), "Fair");
+	assert_eq!(Attack::UP_SMASH.to_string(), "Up-Smash");
+	assert_eq!(Attack::KIRBY_HAT_FOX_NEUTRAL_SPECIAL.to_string(), "Neutral-B (Fox Hat)");
+	assert_eq!(Attack(47).to_string(), "Unknown(47)");
+	assert_eq!(Attack(0).to_string(), "Unknown(0)");
+
+	// every named constant has a display name
+	for i in 0 ..= u8::MAX {
+		assert_eq!(Attack(i).name().is_some(), Attack(i).display_name().is_some(), "{}", i);
+	}
+
+	let g = game("game")?;
+	let landed = g.ports[0].as_ref().ok_or("missing port 0")?.leader.post.iter()
+		.filter_map(|p| p.last_attack_landed)
+		.next().ok_or("no attacks landed")?;
+	assert!(!landed.to_string().starts_with("Unknown"));
+	Ok(())
+}
+
+#[test]
+fn action_state_names() {
+	assert_eq!(State::from(14, Internal::FOX).to_string(), "WAIT");
+	assert_eq!(State::from(Zelda::TRANSFORM_GROUND.0, Internal::ZELDA).to_string(), "TRANSFORM_GROUND");
+	assert_eq!(Zelda::TRANSFORM_GROUND.to_string(), "TRANSFORM_GROUND");
+	assert_eq!(Common::WAIT.to_string(), "WAIT");
+	// character-specific states depend on the character
+	assert_ne!(State::from(341, Internal::FOX).to_string(), State::from(341, Internal::MARTH).to_string());
+	assert_eq!(State::from(341, Internal::MASTER_HAND).to_string(), "Unknown(341)");
+	assert_eq!(u16::from(State::from(341, Internal::FOX)), 341);
+	assert_eq!(Common(999).to_string(), "Unknown(999)");
+}
+
+#[test]
+fn lras() -> Result<(), String> {
+	use super::game::EndV2_0;
+	let end = game("v2.0")?.end;
+	assert!(!end.is_lras());
+	assert_eq!(end.lras_port(), None);
+
+	let end = End { method: EndMethod::NO_CONTEST, v2_0: Some(EndV2_0 { lras_initiator: 1 }) };
+	assert!(end.is_lras());
+	assert_eq!(end.lras_port(), Some(1));
+	assert_eq!(End { method: EndMethod::GAME, v2_0: None }.lras_port(), None);
+	for &i in &[4, i8::MAX, i8::MIN] {
+		let end = End { method: EndMethod::NO_CONTEST, v2_0: Some(EndV2_0 { lras_initiator: i }) };
+		assert_eq!(end.lras_port(), None);
+	}
+	Ok(())
+}
+
+#[test]
+fn winning_side() -> Result<(), String> {
+	use super::game::{EndV2_0, Team, TeamColor, TeamShade};
+	use super::stats::{winner, winning_side, Side};
+	let mut game = game("game")?;
+	assert_eq!(winning_side(&game), Some(Side::Port(0)));
+
+	// port 0 quits out
+	game.end.v2_0 = Some(EndV2_0 { lras_initiator: 0 });
+	assert_eq!(winner(&game), Some(1));
+	game.end.v2_0 = Some(EndV2_0 { lras_initiator: 4 }); // not a valid port
+	assert_eq!(winner(&game), Some(0));
+	game.end.v2_0 = Some(EndV2_0 { lras_initiator: -1 });
+
+	// equal stocks and percent
+	let last = *game.ports[0].as_ref().unwrap().leader.post.last().unwrap();
+	*game.ports[1].as_mut().unwrap().leader.post.last_mut().unwrap() = last;
+	assert_eq!(winning_side(&game), None);
+
+	// teams: port 1's team is ahead
+	game.start.is_teams = true;
+	for (port, color) in [(0, TeamColor::RED), (1, TeamColor::BLUE)].iter() {
+		game.start.players[*port].as_mut().unwrap().team = Some(Team { color: *color, shade: TeamShade::NORMAL });
+	}
+	game.ports[1].as_mut().unwrap().leader.post.last_mut().unwrap().stocks += 1;
+	assert_eq!(winning_side(&game), Some(Side::Team(TeamColor::BLUE)));
+	assert_eq!(winner(&game), None);
+	Ok(())
+}
+
+#[test]
+fn apm() -> Result<(), String> {
+	use super::game::FIRST_PLAYABLE_FRAME_INDEX;
+	use super::stats::apm;
+	let mut game = game("game")?;
+	let pre = &mut game.ports[0].as_mut().ok_or("missing port 0")?.leader.pre;
+	let idle = pre[0];
+	for p in pre.iter_mut() {
+		*p = super::frame::Pre { index: p.index, ..idle };
+	}
+	let playable = pre.iter().filter(|p| p.index >= FIRST_PLAYABLE_FRAME_INDEX).count();
+	let first = pre.len() - playable;
+	let per_minute = |actions:f32| actions / (playable as f32 / 3600.0);
+	assert_eq!(apm(&game, 0), Some(0.0));
+
+	let pre = &mut game.ports[0].as_mut().unwrap().leader.pre;
+	pre[first + 10].buttons.physical = Physical::A | Physical::START; // Start doesn't count
+	pre[first + 20].joystick = Position { x: 1.0, y: 0.0 };
+	pre[first + 21].joystick = Position { x: 0.7, y: 0.7 }; // new region
+	pre[first + 22].joystick = Position { x: 0.0, y: 0.0 }; // back to neutral doesn't count
+	pre[first + 30].triggers.physical.l = 0.5;
+	pre[first - 5].buttons.physical = Physical::B; // before the first playable frame
+	assert_eq!(apm(&game, 0), Some(per_minute(4.0)));
+	Ok(())
+}
+
+#[test]
+fn l_cancel_rate() -> Result<(), String> {
+	use super::frame::LCancel;
+	use super::stats::l_cancel_rate;
+	assert_eq!(l_cancel_rate(&game("game")?, 0), None); // pre-v2.0
+
+	// as recorded: 30 of port 0's 32 aerial landings, and 38 of port 1's 45
+	let mut game = game("v2.0")?;
+	assert_eq!(l_cancel_rate(&game, 0), Some(30.0 / 32.0));
+	assert_eq!(l_cancel_rate(&game, 1), Some(38.0 / 45.0));
+
+	let post = &mut game.ports[0].as_mut().ok_or("missing port 0")?.leader.post;
+	for p in post.iter_mut() {
+		p.v0_2.as_mut().unwrap().v2_0.as_mut().unwrap().l_cancel = None;
+	}
+	assert_eq!(l_cancel_rate(&game, 0), None);
+
+	let post = &mut game.ports[0].as_mut().unwrap().leader.post;
+	for (i, l_cancel) in [LCancel::SUCCESSFUL, LCancel::UNSUCCESSFUL, LCancel::SUCCESSFUL, LCancel::SUCCESSFUL].iter().enumerate() {
+		post[100 + i * 50].v0_2.as_mut().unwrap().v2_0.as_mut().unwrap().l_cancel = Some(*l_cancel);
+	}
+	assert_eq!(l_cancel_rate(&game, 0), Some(0.75));
+	Ok(())
+}
+
+#[test]
+fn frame_joiner() -> Result<(), String> {
+	use super::frame::Frame;
+	use super::frame_joiner::{FrameHandlers, FrameJoiner};
+	use super::parse::{self, FrameEvent, Handlers};
+
+	#[derive(Default)]
+	struct Frames(Vec<FrameEvent<Frame>>);
+	impl Handlers for Frames { }
+	impl FrameHandlers for Frames {
+		fn frame(&mut self, f:FrameEvent<Frame>) -> io::Result<()> {
+			self.0.push(f);
+			Ok(())
+		}
+	}
+
+	for name in ["game", "ics"].iter() {
+		let expected = game(name)?;
+		let bytes = fs::read(format!("test/replays/{}.slp", name)).map_err(|e| format!("{:?}", e))?;
+		let mut joiner = FrameJoiner::new(Frames::default());
+		parse::parse(&*bytes, &mut joiner).map_err(|e| format!("{:?}", e))?;
+		let frames = joiner.inner.0;
+
+		for (p, port) in expected.ports.iter().enumerate() {
+			let port = match port { Some(port) => port, None => continue };
+			for (is_follower, f) in [(false, Some(&port.leader)), (true, port.follower.as_ref())].iter() {
+				let f = match f { Some(f) => f, None => continue };
+				let joined:Vec<_> = frames.iter()
+					.filter(|e| e.id.port as usize == p && e.id.is_follower == *is_follower)
+					.map(|e| e.event)
+					.collect();
+				let (pre, post):(Vec<_>, Vec<_>) = joined.iter().map(|f| (f.pre, f.post)).unzip();
+				assert_eq!(pre, f.pre);
+				assert_eq!(post, f.post);
+			}
+		}
+	}
+
+	// truncated right after a Pre: that frame is never emitted
+	let bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	let (start, end, _) = raw_event_offsets(&bytes).into_iter()
+		.skip(5000).find(|e| e.2 == 0x37).ok_or("no pre frame")?;
+	let index = i32::from_be_bytes([bytes[start + 1], bytes[start + 2], bytes[start + 3], bytes[start + 4]]);
+	let port = bytes[start + 5];
+	let mut joiner = FrameJoiner::new(Frames::default());
+	assert!(parse::parse_partial(&bytes[.. end], &mut joiner).map_err(|e| format!("{:?}", e))?);
+	let frames = joiner.inner.0;
+	assert!(!frames.is_empty());
+	assert!(!frames.iter().any(|f| f.id.index == index && f.id.port == port));
+	Ok(())
+}
+
+#[test]
+fn frame_lookup() -> Result<(), String> {
+	let g = game("game")?;
+	let port = g.ports[0].as_ref().ok_or("missing port 0")?;
+	for post in &port.leader.post {
+		assert_eq!(g.post(0, post.index), Some(post));
+	}
+	for pre in &port.leader.pre {
+		assert_eq!(g.pre(0, pre.index), Some(pre));
+	}
+
+	let last = port.leader.post.last().ok_or("no frames")?.index;
+	assert!(g.post(0, -124).is_none());
+	assert!(g.post(0, last + 1).is_none());
+	assert!(g.pre(0, i32::MIN).is_none());
+	assert!(g.pre(0, i32::MAX).is_none());
+	assert!(g.post(2, 0).is_none());
+	assert!(g.post(7, 0).is_none());
+	assert_eq!(g.frame(0).post(1), g.post(1, 0));
+	Ok(())
+}
+
+#[test]
+fn try_array_index() -> Result<(), String> {
+	use super::frame::Indexed;
+	let g = game("game")?;
+	let mut post = g.ports[0].as_ref().ok_or("missing port 0")?.leader.post[0];
+	assert_eq!(post.try_array_index(), Some(0));
+	post.index = 77;
+	assert_eq!(post.try_array_index(), Some(200));
+	assert_eq!(post.array_index(), 200);
+	post.index = -124;
+	assert_eq!(post.try_array_index(), None);
+	post.index = i32::MIN;
+	assert_eq!(post.try_array_index(), None);
+	Ok(())
+}
+
+#[test]
+fn neutral_direction() -> Result<(), String> {
+	use super::frame::Direction;
+	use super::game_parser::GameParser;
+	let mut bytes = fs::read("test/replays/game.slp").map_err(|e| format!("{:?}", e))?;
+	let (pre, post) = {
+		let events = raw_event_offsets(&bytes);
+		let pre = events.iter().find(|e| e.2 == 0x37).ok_or("no pre frame")?.0;
+		let post = events.iter().find(|e| e.2 == 0x38).ok_or("no post frame")?.0;
+		(pre, post)
+	};
+	// facing direction follows the frame id, random seed (pre) or character (post), action
+	// state, and position
+	bytes[pre + 21 .. pre + 25].copy_from_slice(&0f32.to_be_bytes());
+	bytes[post + 18 .. post + 22].copy_from_slice(&0f32.to_be_bytes());
+
+	let mut parser = GameParser::default();
+	super::parse::parse(&*bytes, &mut parser).map_err(|e| format!("{:?}", e))?;
+	let g = parser.into_game().map_err(|e| format!("{:?}", e))?;
+	let frames = &g.ports[0].as_ref().ok_or("missing port 0")?.leader;
+	assert_eq!(frames.pre[0].direction, Direction::NEUTRAL);
+	assert_eq!(frames.post[0].direction, Direction::NEUTRAL);
+	assert_ne!(frames.pre[1].direction, Direction::NEUTRAL);
+	Ok(())
+}
+
+#[test]
+fn last_hit_by_port() -> Result<(), String> {
+	let g = game("game")?;
+	let post = &g.ports[0].as_ref().ok_or("missing port 0")?.leader.post;
+	// not hit by anyone yet
+	assert_eq!(post[0].last_hit_by, 6);
+	assert_eq!(post[0].last_hit_by_port(), None);
+	assert!(post.iter().any(|p| p.last_hit_by_port() == Some(1)));
+	for p in post {
+		assert_eq!(p.last_hit_by_port().is_some(), p.last_hit_by < 4);
+	}
+	Ok(())
+}
+
+#[test]
+fn ground_names() -> Result<(), String> {
+	use super::stage::ground_name;
+	assert_eq!(ground_name(Stage::BATTLEFIELD, 3), Some("Top Platform"));
+	assert_eq!(ground_name(Stage::YOSHIS_STORY, 0), Some("Randall"));
+	assert_eq!(ground_name(Stage::YOSHIS_STORY, 7), None);
+	assert_eq!(ground_name(Stage::BATTLEFIELD, u16::MAX), None);
+	assert_eq!(ground_name(Stage(200), 0), None);
+	assert_eq!(ground_name(Stage::CORNERIA, 0), None);
+
+	// platforms are where their names say
+	for name in ["v2.0", "netplay_name"].iter() {
+		let g = game(name)?;
+		for port in g.ports.iter().flatten() {
+			for p in &port.leader.post {
+				let v = match p.v0_2.as_ref().and_then(|v| v.v2_0.as_ref()) {
+					Some(v) if !v.airborne => v,
+					_ => continue,
+				};
+				match ground_name(g.start.stage, v.ground) {
+					Some("Top Platform") => assert!(p.position.y > 40.0),
+					Some("Left Platform") => assert!(p.position.y > 20.0 && p.position.x < 0.0),
+					Some("Right Platform") => assert!(p.position.y > 20.0 && p.position.x > 0.0),
+					Some("Main Stage") => assert!(p.position.y.abs() < 10.0),
+					Some(_) => (),
+					None => panic!("unnamed ground {} on {}", v.ground, g.start.stage),
+				}
+			}
+		}
+	}
+	Ok(())
+}
+
+#[test]
+fn ground_names_final_destination() {
+	use super::stage::ground_name;
+	let fd = Stage::FINAL_DESTINATION;
+	assert_eq!((0 .. 3).map(|g| ground_name(fd, g)).collect::<Vec<_>>(),
+		vec![Some("Left Edge"), Some("Main Stage"), Some("Right Edge")]);
+	assert_eq!(ground_name(fd, 3), None);
+}
+
+#[test]
+fn ground_names_dream_land() {
+	use super::stage::ground_name;
+	let dl = Stage::DREAM_LAND_N64;
+	assert_eq!((0 .. 6).map(|g| ground_name(dl, g)).collect::<Vec<_>>(),
+		vec![Some("Left Platform"), Some("Right Platform"), Some("Top Platform"), Some("Left Edge"), Some("Main Stage"), Some("Right Edge")]);
+	assert_eq!(ground_name(dl, 6), None);
+}
+
+#[test]
+fn ground_names_fountain_of_dreams() {
+	use super::stage::ground_name;
+	let fod = Stage::FOUNTAIN_OF_DREAMS;
+	assert_eq!((0 .. 6).map(|g| ground_name(fod, g)).collect::<Vec<_>>(),
+		vec![Some("Left Platform"), Some("Right Platform"), Some("Top Platform"), Some("Left Edge"), Some("Main Stage"), Some("Right Edge")]);
+	assert_eq!(ground_name(fod, 6), None);
+}
+
+#[test]
+fn ground_names_pokemon_stadium() {
+	use super::stage::ground_name;
+	let ps = Stage::POKEMON_STADIUM;
+	assert_eq!(ground_name(ps, 34), Some("Main Stage"));
+	assert_eq!(ground_name(ps, 35), Some("Left Platform"));
+	assert_eq!(ground_name(ps, 36), Some("Right Platform"));
+	assert_eq!(ground_name(ps, 51), Some("Left Edge"));
+	assert_eq!(ground_name(ps, 52), Some("Right Edge"));
+	assert_eq!(ground_name(ps, 0), None);
+	assert_eq!(ground_name(ps, 37), None);
+}
+
+#[test]
+fn start_at() {
+	use super::metadata::start_at;
+	let expected = "2018-06-22T07:52:59Z".parse::<DateTime<Utc>>().ok();
+	assert_eq!(start_at(&map! { "startAt" => "2018-06-22T07:52:59Z" }), expected);
+	// older replays omit the time zone
+	assert_eq!(start_at(&map! { "startAt" => "2018-06-22T07:52:59" }), expected);
+	assert_eq!(start_at(&map! { "startAt" => "yesterday" }), None);
+	assert_eq!(start_at(&map! { "startAt" => 5 }), None);
+	assert_eq!(start_at(&map! { "lastFrame" => 5 }), None);
+}
@@ -1,13 +1,16 @@
+#[cfg(feature = "serde")]
 use std::io::{Write, Result};
 
 pub type Logical = f32;
 
-#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Physical {
 	pub l: f32,
 	pub r: f32,
 }
 
+#[cfg(feature = "serde")]
 impl super::query::Query for Physical {
 	fn query(&self, f:&mut dyn Write, config:&super::Config, _query:&[&str]) -> Result<()> {
 		match config.json {
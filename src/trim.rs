@@ -0,0 +1,106 @@
+//! Rewrites replays to drop the pre-"Go!" countdown frames.
+
+use std::io::{Read, Result, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use super::{
+	game::FIRST_FRAME_INDEX,
+	parse::{self, Event, METADATA_KEY, RAW_HEADER},
+	ubjson,
+};
+
+/// Events whose payload starts with a frame index: Frame Start, Pre, Post, Item, and
+/// Frame Bookend. These are trimmed whether or not `parse` decodes them.
+const FRAME_EVENT_CODES:[u8; 5] = [Event::FrameStart as u8, Event::FramePre as u8, Event::FramePost as u8, Event::Item as u8, Event::FrameBookend as u8];
+
+/// Copies the replay in `r` to `w`, omitting all frame events with negative indexes
+/// (the countdown before "Go!").
+///
+/// The remaining frames are renumbered to start at `game::FIRST_FRAME_INDEX`, as every
+/// replay does, and `metadata.lastFrame` is rewritten to match, so the output is a
+/// valid replay whose `Metadata::duration` is its frame count. Everything else is
+/// copied verbatim.
+///
+/// This is lossy: the dropped frames can't be recovered, and frame indexes no longer
+/// line up with the in-game timer (the first frame after "Go!" becomes
+/// `FIRST_FRAME_INDEX` rather than 0). In-progress replays (with an unknown `raw`
+/// length) aren't supported.
+pub fn without_countdown<R: Read, W: Write>(mut r: R, mut w: W) -> Result<()> {
+	let mut header = [0; 11];
+	r.read_exact(&mut header)?;
+	if header != RAW_HEADER {
+		Err(err!("expected: {:?}, got: {:?}", RAW_HEADER, header))?;
+	}
+	let raw_len = r.read_u32::<BigEndian>()? as usize;
+	if raw_len == 0 {
+		Err(err!("can't trim an in-progress replay"))?;
+	}
+
+	let mut raw = vec![0; raw_len];
+	r.read_exact(&mut raw)?;
+	let trimmed = trim_raw(&raw)?;
+
+	w.write_all(&RAW_HEADER)?;
+	w.write_u32::<BigEndian>(trimmed.len() as u32)?;
+	w.write_all(&trimmed)?;
+
+	let mut rest = Vec::new();
+	r.read_to_end(&mut rest)?;
+	match rest.strip_prefix(&METADATA_KEY[..]) {
+		Some(mut rest) => {
+			let mut metadata = ubjson::parse_map(&mut rest)?;
+			if let Some(ubjson::Object::Int(last_frame)) = metadata.get_mut("lastFrame") {
+				// a game that ended during the countdown has no frames left
+				*last_frame = (*last_frame).max(-1) + i64::from(FIRST_FRAME_INDEX);
+			}
+			w.write_all(&METADATA_KEY)?;
+			ubjson::write_map(&mut w, &metadata)?;
+			w.write_all(rest)?; // closing brace
+		},
+		None => w.write_all(&rest)?,
+	}
+	Ok(())
+}
+
+fn trim_raw(raw:&[u8]) -> Result<Vec<u8>> {
+	let (payloads_len, sizes) = parse::payload_sizes(&mut &*raw)?;
+	let payloads = &raw[.. payloads_len];
+
+	let mut out = payloads.to_vec();
+	let mut pos = payloads_len;
+	while pos < raw.len() {
+		let code = raw[pos];
+		let size = *sizes.get(&code).ok_or_else(|| err!("unknown event: {}", code))? as usize;
+		let event = raw.get(pos .. pos + 1 + size).ok_or_else(|| err!("truncated event: {}", code))?;
+		match FRAME_EVENT_CODES.contains(&code) {
+			true => {
+				let index = read_i32_at(event, 1)?;
+				if index >= 0 {
+					let start = out.len();
+					out.extend_from_slice(event);
+					renumber(&mut out[start ..], 1)?;
+					// v3.7+ bookends also carry the latest finalized frame
+					if code == Event::FrameBookend as u8 && event.len() >= 9 {
+						renumber(&mut out[start ..], 5)?;
+					}
+				}
+			},
+			_ => out.extend_from_slice(event),
+		}
+		pos += event.len();
+	}
+	Ok(out)
+}
+
+fn read_i32_at(event:&[u8], offset:usize) -> Result<i32> {
+	let mut payload = event.get(offset ..).ok_or_else(|| err!("frame event too short: {:?}", event))?;
+	payload.read_i32::<BigEndian>()
+}
+
+/// Shifts the frame index at `offset` in `event` so that frame 0 becomes `FIRST_FRAME_INDEX`.
+fn renumber(event:&mut [u8], offset:usize) -> Result<()> {
+	let index = read_i32_at(event, offset)?;
+	event[offset .. offset + 4].copy_from_slice(&(index + FIRST_FRAME_INDEX).to_be_bytes());
+	Ok(())
+}
@@ -1,24 +1,188 @@
-use std::io::{Read, Result, Error, ErrorKind};
-use std::collections::HashMap;
+use std::io::{Read, Result, Error, ErrorKind, Write};
+use std::convert::TryFrom;
+use std::fmt;
 
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
-#[derive(Debug, PartialEq, Eq, Clone, serde::Serialize)]
-#[serde(untagged)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(untagged))]
 pub enum Object {
 	Int(i64),
-	Map(HashMap<String, Object>),
+	/// From either a float32 or float64 value.
+	Float(f64),
+	Map(Map),
 	Str(String),
+	Array(Vec<Object>),
 }
 
 query_impl!(Object, self, f, config, query {
 	match self {
 		Object::Int(i) => i.query(f, config, query),
+		Object::Float(x) => x.query(f, config, query),
 		Object::Str(s) => s.query(f, config, query),
 		Object::Map(m) => m.query(f, config, query),
+		Object::Array(a) => a.query(f, config, query),
 	}
 });
 
+/// A string-keyed map that remembers insertion order, so metadata keys can be written
+/// back out in the order they were read. Values don't keep their original encoding
+/// (see `write_map`). Lookups are linear, which is fine for the handful of keys a
+/// metadata map holds. Equality ignores order.
+#[derive(Clone, Default)]
+pub struct Map {
+	entries: Vec<(String, Object)>,
+}
+
+impl Map {
+	pub fn new() -> Map {
+		Map::default()
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	fn position(&self, key:&str) -> Option<usize> {
+		self.entries.iter().position(|(k, _)| k == key)
+	}
+
+	pub fn get(&self, key:&str) -> Option<&Object> {
+		self.position(key).map(|i| &self.entries[i].1)
+	}
+
+	pub fn get_mut(&mut self, key:&str) -> Option<&mut Object> {
+		self.position(key).map(move |i| &mut self.entries[i].1)
+	}
+
+	pub fn contains_key(&self, key:&str) -> bool {
+		self.position(key).is_some()
+	}
+
+	/// Inserts `value` at `key`, returning the previous value. An existing key keeps
+	/// its position; a new one goes at the end.
+	pub fn insert(&mut self, key:String, value:Object) -> Option<Object> {
+		match self.position(&key) {
+			Some(i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+			None => {
+				self.entries.push((key, value));
+				None
+			},
+		}
+	}
+
+	/// Removes `key`, shifting later entries down to preserve their order.
+	pub fn remove(&mut self, key:&str) -> Option<Object> {
+		self.position(key).map(|i| self.entries.remove(i).1)
+	}
+
+	/// Entries in insertion order.
+	pub fn iter(&self) -> impl Iterator<Item = (&String, &Object)> {
+		self.entries.iter().map(|(k, v)| (k, v))
+	}
+
+	pub fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut Object)> {
+		self.entries.iter_mut().map(|(k, v)| (&*k, v))
+	}
+
+	pub fn keys(&self) -> impl Iterator<Item = &String> {
+		self.entries.iter().map(|(k, _)| k)
+	}
+
+	pub fn values(&self) -> impl Iterator<Item = &Object> {
+		self.entries.iter().map(|(_, v)| v)
+	}
+
+	pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Object> {
+		self.entries.iter_mut().map(|(_, v)| v)
+	}
+}
+
+impl PartialEq for Map {
+	fn eq(&self, other:&Map) -> bool {
+		self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+	}
+}
+
+impl fmt::Debug for Map {
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+		f.debug_map().entries(self.iter()).finish()
+	}
+}
+
+impl std::ops::Index<&str> for Map {
+	type Output = Object;
+	fn index(&self, key:&str) -> &Object {
+		self.get(key).unwrap_or_else(|| panic!("key not found: {}", key))
+	}
+}
+
+impl std::iter::FromIterator<(String, Object)> for Map {
+	fn from_iter<I:IntoIterator<Item = (String, Object)>>(iter:I) -> Map {
+		let mut m = Map::new();
+		for (k, v) in iter {
+			m.insert(k, v);
+		}
+		m
+	}
+}
+
+impl<'a> IntoIterator for &'a Map {
+	type Item = (&'a String, &'a Object);
+	type IntoIter = std::iter::Map<std::slice::Iter<'a, (String, Object)>, fn(&'a (String, Object)) -> (&'a String, &'a Object)>;
+	fn into_iter(self) -> Self::IntoIter {
+		self.entries.iter().map(|(k, v)| (k, v))
+	}
+}
+
+impl IntoIterator for Map {
+	type Item = (String, Object);
+	type IntoIter = std::vec::IntoIter<(String, Object)>;
+	fn into_iter(self) -> Self::IntoIter {
+		self.entries.into_iter()
+	}
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Map {
+	fn serialize<S:serde::Serializer>(&self, serializer:S) -> std::result::Result<S::Ok, S::Error> {
+		serializer.collect_map(self.iter())
+	}
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Map {
+	fn deserialize<D:serde::Deserializer<'de>>(deserializer:D) -> std::result::Result<Map, D::Error> {
+		struct MapVisitor;
+
+		impl<'de> serde::de::Visitor<'de> for MapVisitor {
+			type Value = Map;
+
+			fn expecting(&self, f:&mut fmt::Formatter) -> fmt::Result {
+				f.write_str("a map")
+			}
+
+			fn visit_map<A:serde::de::MapAccess<'de>>(self, mut access:A) -> std::result::Result<Map, A::Error> {
+				let mut m = Map::new();
+				while let Some((k, v)) = access.next_entry()? {
+					m.insert(k, v);
+				}
+				Ok(m)
+			}
+		}
+
+		deserializer.deserialize_map(MapVisitor)
+	}
+}
+
+query_impl!(Map, self, f, config, query {
+	self.get(query[0]).query(f, config, &query[1..])
+});
+
 pub trait ToObject {
 	fn to_object(self) -> Object;
 }
@@ -29,6 +193,12 @@ impl ToObject for i64 {
 	}
 }
 
+impl ToObject for f64 {
+	fn to_object(self) -> Object {
+		Object::Float(self)
+	}
+}
+
 impl ToObject for String {
 	fn to_object(self) -> Object {
 		Object::Str(self)
@@ -41,7 +211,13 @@ impl ToObject for &str {
 	}
 }
 
-impl ToObject for HashMap<String, Object> {
+impl ToObject for Vec<Object> {
+	fn to_object(self) -> Object {
+		Object::Array(self)
+	}
+}
+
+impl ToObject for Map {
 	fn to_object(self) -> Object {
 		Object::Map(self)
 	}
@@ -55,19 +231,46 @@ fn parse_utf8<R:Read>(r:&mut R) -> Result<String> {
 }
 
 fn parse_val<R:Read>(r:&mut R) -> Result<Object> {
-	match r.read_u8()? {
+	let marker = r.read_u8()?;
+	parse_typed_val(r, marker)
+}
+
+/// Parses a value whose type marker (`marker`) has already been read.
+fn parse_typed_val<R:Read>(r:&mut R, marker:u8) -> Result<Object> {
+	match marker {
 		0x53 => { // "S": str
 			match r.read_u8()? {
 				0x55 => Ok(Object::Str(parse_utf8(r)?)),
 				c => Err(Error::new(ErrorKind::InvalidData, format!("Expected 0x55 for string length, but got: {}", c))),
 			}
 		},
+		0x69 => { // "i": i8
+			Ok(Object::Int(r.read_i8()? as i64))
+		},
+		0x55 => { // "U": u8
+			Ok(Object::Int(r.read_u8()? as i64))
+		},
+		0x49 => { // "I": i16
+			Ok(Object::Int(r.read_i16::<BigEndian>()? as i64))
+		},
 		0x6c => { // "l": i32
 			Ok(Object::Int(r.read_i32::<BigEndian>()? as i64))
 		},
+		0x4c => { // "L": i64
+			Ok(Object::Int(r.read_i64::<BigEndian>()?))
+		},
+		0x64 => { // "d": f32
+			Ok(Object::Float(r.read_f32::<BigEndian>()? as f64))
+		},
+		0x44 => { // "D": f64
+			Ok(Object::Float(r.read_f64::<BigEndian>()?))
+		},
 		0x7b => { // "{": map
 			Ok(Object::Map(parse_map(r)?))
-		}
+		},
+		0x5b => { // "[": array
+			Ok(Object::Array(parse_array(r)?))
+		},
 		c => Err(Error::new(ErrorKind::InvalidData, format!("unexpected UBJSON value type: {}", c)))
 	}
 }
@@ -80,11 +283,107 @@ fn parse_key<R:Read>(r:&mut R) -> Result<Option<String>> {
 	}
 }
 
-pub fn parse_map<R:Read>(r:&mut R) -> Result<HashMap<String, Object>> {
-	let mut m = HashMap::new();
+/// Parses the entries of a map, after the opening brace, keeping them in the order read.
+pub fn parse_map<R:Read>(r:&mut R) -> Result<Map> {
+	let mut m = Map::new();
 	while match parse_key(r)? {
 		Some(k) => {m.insert(k, parse_val(r)?); true},
 		None => false,
 	} {}
 	Ok(m)
 }
+
+/// Parses the elements of an array, after the opening bracket. Handles both the
+/// plain form (terminated by `]`) and the optimized form with a `#` count, which
+/// may be preceded by a `$` type marker shared by all elements.
+pub fn parse_array<R:Read>(r:&mut R) -> Result<Vec<Object>> {
+	let mut marker = r.read_u8()?;
+	let mut elem_type = None;
+	if marker == 0x24 { // "$": element type
+		elem_type = Some(r.read_u8()?);
+		marker = r.read_u8()?;
+		if marker != 0x23 {
+			return Err(Error::new(ErrorKind::InvalidData, format!("expected UBJSON array count after type, but got: {}", marker)));
+		}
+	}
+
+	if marker == 0x23 { // "#": element count
+		let count = match parse_val(r)? {
+			Object::Int(n) => usize::try_from(n)
+				.map_err(|_| Error::new(ErrorKind::InvalidData, format!("invalid UBJSON array count: {}", n)))?,
+			v => return Err(Error::new(ErrorKind::InvalidData, format!("invalid UBJSON array count: {:?}", v))),
+		};
+		let mut a = Vec::new();
+		for _ in 0 .. count {
+			a.push(match elem_type {
+				Some(t) => parse_typed_val(r, t)?,
+				None => parse_val(r)?,
+			});
+		}
+		return Ok(a);
+	}
+
+	let mut a = Vec::new();
+	while marker != 0x5d { // "]"
+		a.push(parse_typed_val(r, marker)?);
+		marker = r.read_u8()?;
+	}
+	Ok(a)
+}
+
+fn write_utf8<W:Write>(w:&mut W, s:&str) -> Result<()> {
+	let length = u8::try_from(s.len())
+		.map_err(|_| Error::new(ErrorKind::InvalidInput, format!("UBJSON string too long: {}", s.len())))?;
+	w.write_u8(length)?;
+	w.write_all(s.as_bytes())
+}
+
+fn write_val<W:Write>(w:&mut W, v:&Object) -> Result<()> {
+	match v {
+		Object::Str(s) => {
+			w.write_all(&[0x53, 0x55])?; // "S", then "U" for the length
+			write_utf8(w, s)
+		},
+		Object::Int(i) => match i32::try_from(*i) {
+			Ok(i) => {
+				w.write_u8(0x6c)?; // "l": i32
+				w.write_i32::<BigEndian>(i)
+			},
+			Err(_) => {
+				w.write_u8(0x4c)?; // "L": i64
+				w.write_i64::<BigEndian>(*i)
+			},
+		},
+		Object::Float(x) => {
+			w.write_u8(0x44)?; // "D": f64
+			w.write_f64::<BigEndian>(*x)
+		},
+		Object::Map(m) => {
+			w.write_u8(0x7b)?; // "{"
+			write_map(w, m)
+		},
+		Object::Array(a) => {
+			w.write_u8(0x5b)?; // "["
+			for v in a {
+				write_val(w, v)?;
+			}
+			w.write_u8(0x5d) // "]"
+		},
+	}
+}
+
+/// Inverse of `parse_map`: writes the entries of `m` (in order) and the closing brace,
+/// but not the opening brace. Only the types `parse_map` understands are supported.
+///
+/// `Object` doesn't record the source's type markers, so ints are always written as
+/// `l` (or `L` if they don't fit), floats as `D`, and arrays in the plain form. The
+/// output only matches the input byte-for-byte if it was written that way, as Slippi's
+/// recorders do; otherwise it parses to the same values but in a different form.
+pub fn write_map<W:Write>(w:&mut W, m:&Map) -> Result<()> {
+	for (k, v) in m {
+		w.write_u8(0x55)?;
+		write_utf8(w, k)?;
+		write_val(w, v)?;
+	}
+	w.write_u8(0x7d) // "}"
+}